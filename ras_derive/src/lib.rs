@@ -0,0 +1,165 @@
+// #[derive(Vertex)] for ras::graphics::vertex::Vertex.
+//
+// Every field becomes one vertex attribute. Options go in #[vertex(...)]:
+//   location = N   attribute index, defaults to one after the previous field
+//   normalized     integer data is mapped to 0..1 (or -1..1) floats
+//   integer        passed to the shader as int/uint with VertexAttribIPointer
+//   divisor = N    advance once per N instances instead of once per vertex
+//   skip           not an attribute, just padding or CPU side data
+// divisor can also be put on the struct to apply to every field.
+//
+//   #[repr(C)]
+//   #[derive(Clone, Copy, Vertex)]
+//   struct SpriteVertex {
+//       pos: [f32; 2],
+//       tex_coord: [f32; 2],
+//       #[vertex(normalized)]
+//       color: [u8; 4],
+//   }
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{Attribute, Data, DeriveInput, Fields, Lit, Member, Meta, NestedMeta};
+
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = match syn::parse(input) {
+        Ok(input) => input,
+        Err(e) => { return e.to_compile_error().into(); }
+    };
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    location: Option<u32>,
+    normalized: bool,
+    integer: bool,
+    divisor: Option<u32>,
+    skip: bool,
+}
+
+fn expand(input: &DeriveInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref f) => f.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(ref f) => f.unnamed.iter().collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(), "Vertex can only be derived for structs"));
+        }
+    };
+
+    let struct_options = parse_options(&input.attrs)?;
+    if struct_options.location.is_some() || struct_options.normalized
+        || struct_options.integer || struct_options.skip {
+        return Err(syn::Error::new(
+            Span::call_site(), "only divisor can be set on the struct"));
+    }
+
+    let mut attributes = Vec::new();
+    let mut next_location = 0;
+    for (i, field) in fields.iter().enumerate() {
+        let options = parse_options(&field.attrs)?;
+        if options.skip {
+            continue;
+        }
+        let location = options.location.unwrap_or(next_location);
+        next_location = location + 1;
+
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(syn::Index::from(i)),
+        };
+        let ty = &field.ty;
+        let normalized = options.normalized;
+        let integer = options.integer;
+        let divisor = options.divisor.or(struct_options.divisor).unwrap_or(0);
+        attributes.push(quote! {
+            ::ras::graphics::vertex::VertexAttribute::of::<#ty>(
+                #location,
+                unsafe { ::std::ptr::addr_of!((*uninit.as_ptr()).#member) } as usize - base
+            )
+            .with_normalized(#normalized)
+            .with_integer(#integer)
+            .with_divisor(#divisor)
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ras::graphics::vertex::Vertex for #name #ty_generics #where_clause {
+            fn attributes() -> ::std::vec::Vec<::ras::graphics::vertex::VertexAttribute> {
+                // Only used for field addresses, never read
+                let uninit = ::std::mem::MaybeUninit::<Self>::uninit();
+                let base = uninit.as_ptr() as usize;
+                vec![#(#attributes),*]
+            }
+        }
+    })
+}
+
+fn parse_options(attrs: &[Attribute]) -> Result<Options, syn::Error> {
+    let mut options = Options::default();
+    for attr in attrs.iter() {
+        if !attr.path.is_ident("vertex") {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(meta, "expected #[vertex(...)]"));
+            }
+        };
+        for nested in list.nested.iter() {
+            match *nested {
+                NestedMeta::Meta(Meta::Word(ref word)) => {
+                    if word == "normalized" {
+                        options.normalized = true;
+                    } else if word == "integer" {
+                        options.integer = true;
+                    } else if word == "skip" {
+                        options.skip = true;
+                    } else {
+                        return Err(syn::Error::new_spanned(word, "unknown vertex option"));
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(ref pair)) => {
+                    let value = match pair.lit {
+                        Lit::Int(ref int) => int.value() as u32,
+                        ref lit => {
+                            return Err(syn::Error::new_spanned(lit, "expected an integer"));
+                        }
+                    };
+                    if pair.ident == "location" {
+                        options.location = Some(value);
+                    } else if pair.ident == "divisor" {
+                        options.divisor = Some(value);
+                    } else {
+                        return Err(syn::Error::new_spanned(
+                            &pair.ident, "unknown vertex option"));
+                    }
+                }
+                ref other => {
+                    return Err(syn::Error::new_spanned(other, "unknown vertex option"));
+                }
+            }
+        }
+    }
+    if options.normalized && options.integer {
+        return Err(syn::Error::new(
+            Span::call_site(), "a vertex attribute can't be both normalized and integer"));
+    }
+    Ok(options)
+}
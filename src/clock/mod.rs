@@ -0,0 +1,439 @@
+// Game clock, timers and cooldowns.
+// The clock keeps real time (wall clock, never paused) separate from game
+// time (paused and scaled), so gameplay can freeze or slow down while menus
+// keep animating on real time.
+#![allow(dead_code)]
+
+use events::{Event, EventType, EventPool};
+
+#[derive(Serialize, Deserialize)]
+pub struct GameClock {
+    real_time_ms: u64,
+    game_time_ms: u64,
+    real_delta_ms: u64,
+    game_delta_ms: u64,
+    paused: bool,
+    time_scale: f64,
+    // Scaled time below a whole ms, carried so slow-mo doesn't lose time
+    carry_ms: f64,
+    frame: u64,
+}
+
+impl GameClock {
+    pub fn new() -> Self {
+        GameClock {
+            real_time_ms: 0,
+            game_time_ms: 0,
+            real_delta_ms: 0,
+            game_delta_ms: 0,
+            paused: false,
+            time_scale: 1.0,
+            carry_ms: 0.0,
+            frame: 0,
+        }
+    }
+
+    // Advances the clock by one frame of real time
+    pub fn tick(&mut self, real_delta_ms: u64) {
+        self.frame += 1;
+        self.real_delta_ms = real_delta_ms;
+        self.real_time_ms += real_delta_ms;
+
+        if self.paused {
+            self.game_delta_ms = 0;
+            return;
+        }
+        let scaled = real_delta_ms as f64 * self.time_scale + self.carry_ms;
+        self.game_delta_ms = scaled.floor() as u64;
+        self.carry_ms = scaled - self.game_delta_ms as f64;
+        self.game_time_ms += self.game_delta_ms;
+    }
+
+    // Time since the last tick, ignoring pause and time scale
+    pub fn real_delta_ms(&self) -> u64 {
+        self.real_delta_ms
+    }
+
+    // Time since the last tick as seen by gameplay, 0 while paused
+    pub fn game_delta_ms(&self) -> u64 {
+        self.game_delta_ms
+    }
+
+    pub fn delta_ms(&self, source: TimeSource) -> u64 {
+        match source {
+            TimeSource::Game => self.game_delta_ms,
+            TimeSource::Real => self.real_delta_ms,
+        }
+    }
+
+    pub fn real_time_ms(&self) -> u64 {
+        self.real_time_ms
+    }
+
+    pub fn game_time_ms(&self) -> u64 {
+        self.game_time_ms
+    }
+
+    // Number of ticks so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // 1.0 is normal speed, 0.5 half speed, 2.0 double speed
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.time_scale = scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock::new()
+    }
+}
+
+// Which of the clock's times a timer follows
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TimeSource {
+    // Stops when paused and follows the time scale
+    Game,
+    // Always runs, for menus and UI
+    Real,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Timer {
+    duration_ms: u64,
+    elapsed_ms: u64,
+    // Restarts after finishing instead of stopping
+    repeating: bool,
+    paused: bool,
+    finished: bool,
+    source: TimeSource,
+}
+
+impl Timer {
+    pub fn new(duration_ms: u64) -> Self {
+        Timer {
+            duration_ms,
+            elapsed_ms: 0,
+            repeating: false,
+            paused: false,
+            finished: false,
+            source: TimeSource::Game,
+        }
+    }
+
+    pub fn repeating(duration_ms: u64) -> Self {
+        let mut timer = Timer::new(duration_ms);
+        timer.repeating = true;
+        timer
+    }
+
+    pub fn with_source(mut self, source: TimeSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn source(&self) -> TimeSource {
+        self.source
+    }
+
+    // Advances the timer and returns how many times it went off,
+    // which can be more than once for short repeating timers
+    pub fn update(&mut self, delta_ms: u64) -> u32 {
+        if self.paused || self.finished {
+            return 0;
+        }
+        self.elapsed_ms += delta_ms;
+        if self.elapsed_ms < self.duration_ms {
+            return 0;
+        }
+        if !self.repeating || self.duration_ms == 0 {
+            self.elapsed_ms = self.duration_ms;
+            self.finished = true;
+            return 1;
+        }
+        let fired = self.elapsed_ms / self.duration_ms;
+        self.elapsed_ms %= self.duration_ms;
+        fired as u32
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed_ms = 0;
+        self.finished = false;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Only ever true for non-repeating timers
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn remaining_ms(&self) -> u64 {
+        self.duration_ms - self.elapsed_ms
+    }
+
+    // 0.0 when started, 1.0 when finished
+    pub fn progress(&self) -> f32 {
+        if self.duration_ms == 0 {
+            return 1.0;
+        }
+        self.elapsed_ms as f32 / self.duration_ms as f32
+    }
+}
+
+// Tracks when an action can be used again, e.g. an attack or a dash
+#[derive(Serialize, Deserialize)]
+pub struct Cooldown {
+    duration_ms: u64,
+    remaining_ms: u64,
+}
+
+impl Cooldown {
+    // Starts ready to use
+    pub fn new(duration_ms: u64) -> Self {
+        Cooldown { duration_ms, remaining_ms: 0 }
+    }
+
+    pub fn update(&mut self, delta_ms: u64) {
+        self.remaining_ms = self.remaining_ms.saturating_sub(delta_ms);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.remaining_ms == 0
+    }
+
+    // Starts the cooldown if it's ready, returning whether it was
+    pub fn trigger(&mut self) -> bool {
+        if !self.is_ready() {
+            return false;
+        }
+        self.remaining_ms = self.duration_ms;
+        true
+    }
+
+    // Makes it ready immediately
+    pub fn reset(&mut self) {
+        self.remaining_ms = 0;
+    }
+
+    pub fn set_duration(&mut self, duration_ms: u64) {
+        self.duration_ms = duration_ms;
+        self.remaining_ms = self.remaining_ms.min(duration_ms);
+    }
+
+    pub fn remaining_ms(&self) -> u64 {
+        self.remaining_ms
+    }
+
+    // 1.0 right after triggering, 0.0 when ready
+    pub fn remaining_fraction(&self) -> f32 {
+        if self.duration_ms == 0 {
+            return 0.0;
+        }
+        self.remaining_ms as f32 / self.duration_ms as f32
+    }
+}
+
+// Owns timers and signals an OnTimerFinished event each time one goes off.
+// Finished non-repeating timers are removed.
+#[derive(Serialize, Deserialize)]
+pub struct Timers {
+    timers: Vec<(u32, Timer)>,
+    next_id: u32,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Timers { timers: Vec::new(), next_id: 1 }
+    }
+
+    // Starts a timer and returns its id
+    pub fn start(&mut self, timer: Timer) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.push((id, timer));
+        id
+    }
+
+    pub fn cancel(&mut self, id: u32) {
+        self.timers.retain(|&(timer_id, _)| timer_id != id);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Timer> {
+        self.timers.iter().find(|t| t.0 == id).map(|t| &t.1)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Timer> {
+        self.timers.iter_mut().find(|t| t.0 == id).map(|t| &mut t.1)
+    }
+
+    pub fn update(&mut self, clock: &GameClock, event_pool: &mut EventPool) {
+        for &mut (id, ref mut timer) in self.timers.iter_mut() {
+            let fired = timer.update(clock.delta_ms(timer.source()));
+            for _ in 0..fired {
+                event_pool.signal_event(
+                    EventType::OnTimerFinished,
+                    Event::OnTimerFinished { trigger_id: id });
+            }
+        }
+        self.timers.retain(|t| !t.1.is_finished());
+    }
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Timers::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(pool: &mut EventPool, id: u32) -> u32 {
+        let mut count = 0;
+        while pool.poll_events(EventType::OnTimerFinished, id, true).is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn pause_stops_game_time_only() {
+        let mut clock = GameClock::new();
+        clock.tick(16);
+        clock.pause();
+        clock.tick(20);
+        assert_eq!(clock.real_delta_ms(), 20);
+        assert_eq!(clock.game_delta_ms(), 0);
+        assert_eq!(clock.delta_ms(TimeSource::Real), 20);
+        assert_eq!(clock.delta_ms(TimeSource::Game), 0);
+        assert_eq!((clock.real_time_ms(), clock.game_time_ms()), (36, 16));
+
+        clock.toggle_pause();
+        clock.tick(10);
+        assert_eq!((clock.real_time_ms(), clock.game_time_ms()), (46, 26));
+        assert_eq!(clock.frame(), 3);
+    }
+
+    #[test]
+    fn time_scale_carries_fractions() {
+        let mut clock = GameClock::new();
+        clock.set_time_scale(0.25);
+        let deltas: Vec<u64> = (0..8).map(|_| {
+            clock.tick(3);
+            clock.game_delta_ms()
+        }).collect();
+        // 0.75ms a tick, no time is lost to rounding
+        assert_eq!(deltas, vec![0, 1, 1, 1, 0, 1, 1, 1]);
+        assert_eq!(clock.game_time_ms(), 6);
+        assert_eq!(clock.real_time_ms(), 24);
+
+        clock.set_time_scale(-1.0);
+        assert_eq!(clock.time_scale(), 0.0);
+    }
+
+    #[test]
+    fn repeating_timer_fires_several_times_in_one_tick() {
+        let mut timer = Timer::repeating(100);
+        assert_eq!(timer.update(50), 0);
+        assert_eq!(timer.update(270), 3);
+        assert_eq!(timer.remaining_ms(), 80);
+        assert!(!timer.is_finished());
+    }
+
+    #[test]
+    fn one_shot_timer_fires_once() {
+        let mut timer = Timer::new(100);
+        assert_eq!(timer.update(250), 1);
+        assert!(timer.is_finished());
+        assert_eq!(timer.update(250), 0);
+        assert_eq!(timer.progress(), 1.0);
+
+        timer.reset();
+        timer.set_paused(true);
+        assert_eq!(timer.update(250), 0);
+        timer.set_paused(false);
+        assert_eq!(timer.update(100), 1);
+    }
+
+    #[test]
+    fn cooldown_trigger_and_reset() {
+        let mut cooldown = Cooldown::new(100);
+        assert!(cooldown.is_ready());
+        assert!(cooldown.trigger());
+        assert!(!cooldown.trigger());
+        assert_eq!(cooldown.remaining_fraction(), 1.0);
+
+        cooldown.update(60);
+        assert!(!cooldown.is_ready());
+        assert_eq!(cooldown.remaining_ms(), 40);
+        cooldown.update(60);
+        assert!(cooldown.is_ready());
+
+        assert!(cooldown.trigger());
+        cooldown.reset();
+        assert!(cooldown.is_ready());
+        assert!(cooldown.trigger());
+        cooldown.set_duration(30);
+        assert_eq!(cooldown.remaining_ms(), 30);
+    }
+
+    #[test]
+    fn timers_post_events_by_id() {
+        let mut clock = GameClock::new();
+        let mut pool = EventPool::new();
+        let mut timers = Timers::new();
+        let once = timers.start(Timer::new(100));
+        let repeat = timers.start(Timer::repeating(40));
+        let ui = timers.start(Timer::new(50).with_source(TimeSource::Real));
+
+        clock.pause();
+        clock.tick(60);
+        timers.update(&clock, &mut pool);
+        assert_eq!((finished(&mut pool, once), finished(&mut pool, repeat)), (0, 0));
+        // Real time timers run while paused
+        assert_eq!(finished(&mut pool, ui), 1);
+        assert!(timers.get(ui).is_none());
+
+        clock.resume();
+        clock.tick(120);
+        timers.update(&clock, &mut pool);
+        assert_eq!(finished(&mut pool, once), 1);
+        assert_eq!(finished(&mut pool, repeat), 3);
+        assert!(timers.get(once).is_none());
+        assert!(timers.get(repeat).is_some());
+    }
+}
@@ -12,10 +12,7 @@ pub struct Controllers {
 impl Controllers {
     pub fn new(controller_subsystem: &GameControllerSubsystem) -> Self {
         let available =
-            match controller_subsystem.num_joysticks() {
-                Ok(n) => n,
-                Err(_e) => 0,
-            };
+            controller_subsystem.num_joysticks().unwrap_or_default();
         let mut result = Controllers { 
             pool: Vec::new() 
         };
@@ -33,7 +30,7 @@ impl Controllers {
     }
 
     pub fn any(&self) -> bool {
-        self.pool.len() > 0
+        !self.pool.is_empty()
     }
 
     pub fn exists(&self, number: usize) -> bool {
@@ -41,7 +38,7 @@ impl Controllers {
     }
 
     pub fn num(&self) -> usize {
-        return self.pool.len();
+        self.pool.len()
     }
 
     pub fn name(&self, number: usize) -> Option<String> {
@@ -91,10 +88,7 @@ impl Controllers {
                         -> Option<bool> {
         match self.button(number, button) {
             Some(down) => {
-                match state.button(number, button) {
-                    Some(was_down) => Some(down && !was_down),
-                    None => None,
-                }
+                state.button(number, button).map(|was_down| down && !was_down)
             }
             None => None,
         }
@@ -105,10 +99,7 @@ impl Controllers {
                         -> Option<bool> {
         match self.button(number, button) {
             Some(down) => {
-                match state.button(number, button) {
-                    Some(was_down) => Some(!down && was_down),
-                    None => None,
-                }
+                state.button(number, button).map(|was_down| !down && was_down)
             }
             None => None,
         }
@@ -124,15 +115,15 @@ impl ButtonState {
     }
 
     fn activate(&mut self, button: Button) {
-        self.0 = self.0 | ((1 as u16) << button as i32);
+        self.0 |= 1_u16 << button as i32;
     }
     
     fn deactivate(&mut self, button: Button) {
-        self.0 = self.0 & !((1 as u16) << button as i32);
+        self.0 &= !(1_u16 << button as i32);
     }
     
     fn button(&self, button: Button) -> bool {
-        self.0 & ((1 as u16) << button as i32) > 0
+        self.0 & (1_u16 << button as i32) > 0
     }
 }
 
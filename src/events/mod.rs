@@ -1,85 +1,196 @@
-use std::collections::HashSet;
-
 // Contains all possible events
 #[derive(Debug)]
 pub enum Event {
     // Example event for sending a name
-    OnNameEntered{ trigger_id: u32, name: &'static str}
+    OnNameEntered{ trigger_id: u32, name: &'static str},
+    // Sent by physics when a body starts overlapping a trigger volume,
+    // trigger_id is the trigger's body id
+    OnTriggerEnter{ trigger_id: u32, other_id: u32 },
+    // Sent by physics when a body stops overlapping a trigger volume
+    OnTriggerExit{ trigger_id: u32, other_id: u32 },
+    // Sent when an animation started with Tweens finishes,
+    // trigger_id is the id returned by Tweens::start
+    OnTweenComplete{ trigger_id: u32 },
+    // Sent each time a timer started with Timers goes off,
+    // trigger_id is the id returned by Timers::start
+    OnTimerFinished{ trigger_id: u32 },
+    // Sent when an animation frame with an event starts showing,
+    // trigger_id is the Animator's id
+    OnAnimationEvent{ trigger_id: u32, event_id: u32 },
+}
+
+impl Event {
+    // Id used by EventPool::poll_events to find events for a target
+    pub fn trigger_id(&self) -> u32 {
+        match *self {
+            Event::OnNameEntered { trigger_id, .. } => trigger_id,
+            Event::OnTriggerEnter { trigger_id, .. } => trigger_id,
+            Event::OnTriggerExit { trigger_id, .. } => trigger_id,
+            Event::OnTweenComplete { trigger_id } => trigger_id,
+            Event::OnTimerFinished { trigger_id } => trigger_id,
+            Event::OnAnimationEvent { trigger_id, .. } => trigger_id,
+        }
+    }
 }
 
 // Contains all possible event types
 //  NOTE: this is expected to be in sync with both Event above and LAST_EVENT_TYPE below
 pub enum EventType {
-    OnNameEntered = 0,    
+    OnNameEntered = 0,
+    OnTriggerEnter = 1,
+    OnTriggerExit = 2,
+    OnTweenComplete = 3,
+    OnTimerFinished = 4,
+    OnAnimationEvent = 5,
 }
 
 // Make sure to update this as EventTypes are added
-const LAST_EVENT_TYPE: usize = EventType::OnNameEntered as usize +1;
+const LAST_EVENT_TYPE: usize = EventType::OnAnimationEvent as usize +1;
 
-// Stores all events in data, along with a HashSet for marking events for removal
+// An event and its bookkeeping
+struct PooledEvent {
+    event: Event,
+    // Set by poll_events, events nobody polls only live for one frame
+    polled: bool,
+    // Set by poll_events when asked to remove it
+    remove: bool,
+    // Set once remove_events has run since it was signaled
+    seen: bool,
+}
+
+// Stores all events in data, one Vec per EventType in the order they were signaled
 pub struct EventPool { 
-    data : [Vec<Event>; LAST_EVENT_TYPE],
-    events_to_remove: HashSet<(usize, usize)>,    
+    data : [Vec<PooledEvent>; LAST_EVENT_TYPE],
 }
 
 impl EventPool {
     pub fn new() -> Self {
         EventPool { 
-            data: [Vec::new(); LAST_EVENT_TYPE],
-            events_to_remove: HashSet::new(),
+            // One Vec per EventType
+            data: [Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()],
         }
     }
     
     // Adds an event to the pool
     pub fn signal_event(&mut self, event_type: EventType, event: Event) {
-        self.data[event_type as usize].push(event);
+        self.data[event_type as usize].push(PooledEvent {
+            event,
+            polled: false,
+            remove: false,
+            seen: false,
+        });
     }
     
-    // Returns the first event of the proper type and with the proper id, and marks it for removal if remove_event is true
+    // Returns the first event of the proper type and with the proper id, and marks it for removal if remove_event is true.
+    // Events already marked are skipped, so polling in a loop goes through every matching event.
     pub fn poll_events(&mut self, event_type: EventType, target_id: u32, remove_event: bool) -> Option<&Event> {
-        let mut result = None;
-        
-        let mut event_idx = None;
-
-        let event_type_id = event_type as usize;
-
-        {
-            let vec = &self.data[event_type_id];
-
-            for idx in 0..vec.len() {
-                let event = &vec[idx];
-                match event {
-                    Event::OnNameEntered {trigger_id, name:_name } => {
-                        if target_id == *trigger_id { 
-                            result = Some(event);
-                            event_idx = Some(idx);
-                            break;
-                        }
-                    }
-                }
+        let events = &mut self.data[event_type as usize];
+        let idx = events.iter()
+            .position(|e| !e.remove && e.event.trigger_id() == target_id)?;
+        let pooled = &mut events[idx];
+        pooled.polled = true;
+        pooled.remove = remove_event;
+        Some(&pooled.event)
+    }
+
+    // Removes all events currently marked for removal from the pool, along
+    // with events nobody polled since the last call. Call once per frame, so
+    // events signaled during a frame can still be read during the next one.
+    pub fn remove_events(&mut self) {
+        for events in self.data.iter_mut() {
+            events.retain(|e| !e.remove && (e.polled || !e.seen));
+            for pooled in events.iter_mut() {
+                pooled.seen = true;
             }
         }
+    }
 
-        if remove_event && event_idx.is_some() {
-            self.events_to_remove.insert((event_type_id, event_idx.unwrap()));
+    // Removes all events, whether or not they are marked for removal
+    pub fn clear_all_events(&mut self) {
+        for events in self.data.iter_mut() {
+            events.clear();
         }
-        result
     }
 
-    // Removes all events currently marked for removal from the pool
-    pub fn remove_events(&mut self) {
-        for (event_type_id, event_idx) in &self.events_to_remove {
-            self.data[*event_type_id].swap_remove(*event_idx);
+    // Events of a type still in the pool
+    pub fn count(&self, event_type: EventType) -> usize {
+        self.data[event_type as usize].len()
+    }
+}
+
+impl Default for EventPool {
+    fn default() -> Self {
+        EventPool::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(id: u32) -> Event {
+        Event::OnTimerFinished { trigger_id: id }
+    }
+
+    fn ids(pool: &mut EventPool, ids: &[u32]) -> Vec<u32> {
+        ids.iter()
+            .filter_map(|&id| pool.poll_events(EventType::OnTimerFinished, id, false)
+                .map(|e| e.trigger_id()))
+            .collect()
+    }
+
+    #[test]
+    fn removes_several_marked_events_of_one_type() {
+        let mut pool = EventPool::new();
+        for id in 1..=5 {
+            pool.signal_event(EventType::OnTimerFinished, timer(id));
+        }
+        // Marked out of order, including the last one
+        for &id in [5, 2, 4].iter() {
+            assert_eq!(pool.poll_events(EventType::OnTimerFinished, id, true).unwrap().trigger_id(), id);
         }
-        self.events_to_remove.clear();
+        pool.poll_events(EventType::OnTimerFinished, 1, false);
+        pool.poll_events(EventType::OnTimerFinished, 3, false);
+        pool.remove_events();
+        assert_eq!(ids(&mut pool, &[1, 2, 3, 4, 5]), vec![1, 3]);
     }
 
-    // Removes all events, whether or not they are marked for removal
-    pub fn clear_all_events(&mut self) {
-        for i in 0..LAST_EVENT_TYPE {
-            unsafe {
-                self.data[i].set_len(0);
-            }
+    #[test]
+    fn polling_in_a_loop_drains_matching_events() {
+        let mut pool = EventPool::new();
+        pool.signal_event(EventType::OnTimerFinished, timer(7));
+        pool.signal_event(EventType::OnTimerFinished, timer(8));
+        pool.signal_event(EventType::OnTimerFinished, timer(7));
+        let mut count = 0;
+        while pool.poll_events(EventType::OnTimerFinished, 7, true).is_some() {
+            count += 1;
         }
+        assert_eq!(count, 2);
+        pool.remove_events();
+        assert_eq!(pool.count(EventType::OnTimerFinished), 1);
+    }
+
+    #[test]
+    fn unpolled_events_last_one_frame() {
+        let mut pool = EventPool::new();
+        pool.signal_event(EventType::OnTimerFinished, timer(1));
+        pool.signal_event(EventType::OnTimerFinished, timer(2));
+        // Signaled during this frame, still there for the next one
+        pool.remove_events();
+        assert_eq!(pool.count(EventType::OnTimerFinished), 2);
+
+        // 2 is polled but kept, 1 was never polled
+        assert_eq!(ids(&mut pool, &[2]), vec![2]);
+        pool.signal_event(EventType::OnTimerFinished, timer(3));
+        pool.remove_events();
+        assert_eq!(ids(&mut pool, &[1, 2]), vec![2]);
+        assert_eq!(pool.count(EventType::OnTimerFinished), 2);
+
+        // Nobody read 3 during the frame after it was signaled
+        pool.remove_events();
+        assert_eq!(ids(&mut pool, &[1, 2, 3]), vec![2]);
+
+        pool.clear_all_events();
+        assert_eq!(pool.count(EventType::OnTimerFinished), 0);
     }
 }
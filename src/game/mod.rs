@@ -1,14 +1,63 @@
 extern crate sdl2;
+extern crate nalgebra as na;
+
+use self::na::{Point3, Vector2};
 
 use events::{Event, EventType, EventPool};
 use controller::{Controllers, ControllerState};
-use controller::sdl2::controller::{Axis, Button};
+use controller::sdl2::controller::Button;
+use tween::Tweens;
+use clock::{GameClock, Timers};
+use save::{Saveable, SaveData, SaveError};
+use graphics::{debug_draw, Color};
+use graphics::animation::{Animator, Clip, Frame, PlaybackMode};
+use graphics::atlas::Atlas;
+use graphics::camera::Camera;
+use graphics::sprite_batch::{SpriteBatch, SpriteParams};
+use graphics::postprocess::PostEffects;
+use physics::{Body, BodyKind, World};
+use physics::collider::Collider;
+
+// Bump when the saved state changes shape, and register a migration
+// from the old version with SaveManager::add_migration
+pub const SAVE_VERSION: u32 = 1;
+
+// World units from the bottom to the top of the screen
+const VIEW_HEIGHT: f32 = 10.0;
+// Debug shapes outline thickness in world units
+const DEBUG_THICKNESS: f32 = 0.05;
+// trigger_id of the example animator's OnAnimationEvent
+const ANIMATOR_ID: u32 = 1;
+
+// Example state that isn't owned by any other system
+#[derive(Serialize, Deserialize)]
+struct ExampleState {
+    signaled: bool,
+    check_signal_count: u32,
+}
 
 pub struct Game<'a> {
     // Required to use events
     event_pool: &'a mut EventPool,
     controllers: &'a Controllers,
     controller_state: ControllerState,
+    // Real and game time, pausing it freezes gameplay
+    clock: GameClock,
+    timers: Timers,
+    // Animations that follow game time and stop while paused
+    tweens: Tweens,
+    // Animations that follow real time, for menus and UI
+    ui_tweens: Tweens,
+    // Full screen effects, main applies them to the post chain
+    post_effects: PostEffects,
+    // Example physics scene, steps with game time
+    world: World,
+    // Example sprite animation, main draws it with draw_sprites
+    animator: Animator,
+    // Looks at the world on the z = 0 plane
+    camera: Camera,
+    // Zoomed out view used while camera bounds are shown, so they're on screen
+    debug_camera: Camera,
     // Used for example logic
     signaled: bool,
     check_signal_count: u32
@@ -22,13 +71,77 @@ impl<'a> Game<'a> {
         Game { 
             event_pool, 
             controllers,
-            controller_state: ControllerState::new(&controllers),
+            controller_state: ControllerState::new(controllers),
+            clock: GameClock::new(),
+            timers: Timers::new(),
+            tweens: Tweens::new(),
+            ui_tweens: Tweens::new(),
+            post_effects: PostEffects::default(),
+            world: example_world(),
+            animator: example_animator(),
+            camera: view_camera(VIEW_HEIGHT),
+            debug_camera: view_camera(VIEW_HEIGHT * 1.5),
             signaled: false, 
             check_signal_count: 0
         }
     }
 
+    pub fn clock(&mut self) -> &mut GameClock {
+        &mut self.clock
+    }
+
+    pub fn timers(&mut self) -> &mut Timers {
+        &mut self.timers
+    }
+
+    pub fn tweens(&mut self) -> &mut Tweens {
+        &mut self.tweens
+    }
+
+    pub fn ui_tweens(&mut self) -> &mut Tweens {
+        &mut self.ui_tweens
+    }
+
+    pub fn post_effects(&mut self) -> &mut PostEffects {
+        &mut self.post_effects
+    }
+
+    pub fn world(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn camera(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    // Call when the window is resized
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.camera.set_aspect(aspect);
+        self.debug_camera.set_aspect(aspect);
+    }
+
+    // What DebugRenderer::render should draw with this frame
+    pub fn debug_view_projection(&self) -> na::Matrix4<f32> {
+        if debug_draw::show_camera_bounds() {
+            self.debug_camera.view_projection()
+        } else {
+            self.camera.view_projection()
+        }
+    }
+
+    // Draws the game's sprites with the world camera. The atlas needs the
+    // regions example_animator uses.
+    pub fn draw_sprites<'b>(&self, batch: &mut SpriteBatch<'b>, atlas: &'b Atlas) {
+        batch.begin(&self.camera.view_matrix(), &self.camera.projection_matrix());
+        self.animator.draw(
+            batch, atlas, SpriteParams::new(Vector2::new(0.0, 2.5), Vector2::new(2.0, 2.0)));
+        batch.end();
+    }
+
+    // delta_time_ms is real time since the last frame
     pub fn update(&mut self, delta_time_ms: u64) {
+        self.clock.tick(delta_time_ms);
+        let game_delta_ms = self.clock.game_delta_ms();
 
         // Example logic using events
         if !self.signaled {
@@ -44,24 +157,117 @@ impl<'a> Game<'a> {
             let event = self.event_pool.poll_events(
                 EventType::OnNameEntered,
                 23,
-                if self.check_signal_count > 5 { true } else { false }
+                self.check_signal_count > 5
             );
-            match event {
-                Some(e) => println!("Got event: {:?}", e),
-                None => (),
+            if let Some(e) = event {
+                println!("Got event: {:?}", e);
             }
             self.check_signal_count += 1;
         }
 
-        match self.controllers.button_down(0, Button::A, &self.controller_state) {
-            Some(b) => if b { println!("Got A") },
-            None => (),
+        if self.controllers.button_down(0, Button::A, &self.controller_state) == Some(true) {
+            println!("Got A");
+        }
+
+        // Pause gameplay with Start
+        if self.controllers.button_down(0, Button::Start, &self.controller_state) == Some(true) {
+            self.clock.toggle_pause();
         }
 
-        // Remove events marked for removal each frame
+        // Show collision shapes and camera bounds with Back
+        if self.controllers.button_down(0, Button::Back, &self.controller_state) == Some(true) {
+            let show = !debug_draw::show_colliders();
+            debug_draw::set_show_colliders(show);
+            debug_draw::set_show_camera_bounds(show);
+        }
+
+        // Toggle post-processing effects
+        {
+            let controllers = self.controllers;
+            let state = &self.controller_state;
+            let pressed = |button| controllers.button_down(0, button, state) == Some(true);
+            let effects = &mut self.post_effects;
+            if pressed(Button::X) { effects.bloom = !effects.bloom; }
+            if pressed(Button::Y) { effects.crt = !effects.crt; }
+            if pressed(Button::DPadUp) { effects.color_grading = !effects.color_grading; }
+            if pressed(Button::DPadLeft) { effects.vignette = !effects.vignette; }
+            if pressed(Button::DPadRight) { effects.fxaa = !effects.fxaa; }
+        }
+
+        // Advance timers, animations and physics, their events are read next frame
+        self.timers.update(&self.clock, self.event_pool);
+        self.tweens.update(game_delta_ms, self.event_pool);
+        self.ui_tweens.update(delta_time_ms, self.event_pool);
+        self.world.update(game_delta_ms, self.event_pool);
+        self.animator.update(&self.clock, self.event_pool);
+
+        // Queued for DebugRenderer::render, only drawn when toggled on
+        debug_draw::draw_colliders(&self.world, DEBUG_THICKNESS);
+        debug_draw::draw_camera_bounds(&self.camera, Color::CYAN, DEBUG_THICKNESS);
+
+        // Remove events marked for removal and ones nobody read since last frame
         self.event_pool.remove_events();
 
         // Update controller state
         self.controller_state.update(self.controllers);
     }
-}
\ No newline at end of file
+}
+
+// Orthographic camera at z = 10 looking down -Z at the origin. The aspect
+// is set by main once the window size is known.
+fn view_camera(height: f32) -> Camera {
+    let half = height / 2.0;
+    let mut camera = Camera::orthographic(-half, half, -half, half, 0.1, 100.0);
+    camera.set_position(Point3::new(0.0, 0.0, 10.0));
+    camera
+}
+
+// Flips between the two images main packs into its atlas, named after
+// res/awesomeface.png and res/cat.png
+fn example_animator() -> Animator {
+    Animator::new(ANIMATOR_ID, "idle", Clip::new(
+        vec![Frame::new("awesomeface", 500), Frame::new("cat", 500)],
+        PlaybackMode::Loop))
+}
+
+// A few shapes dropping onto the ground
+fn example_world() -> World {
+    let mut world = World::new(Vector2::new(0.0, -9.81));
+    world.add_body(Body::new(
+        BodyKind::Static, Collider::aabb(8.0, 0.5), Vector2::new(0.0, -4.0)));
+    world.add_body(Body::new(
+        BodyKind::Dynamic, Collider::aabb(1.0, 1.0), Vector2::new(-2.0, 2.0)));
+    world.add_body(Body::new(
+        BodyKind::Dynamic, Collider::circle(0.5), Vector2::new(0.0, 3.0)));
+    if let Some(triangle) = Collider::polygon(vec![
+        Vector2::new(-0.6, -0.4), Vector2::new(0.6, -0.4), Vector2::new(0.0, 0.6)]) {
+        world.add_body(Body::new(BodyKind::Dynamic, triangle, Vector2::new(2.0, 1.0)));
+    }
+    world
+}
+
+impl<'a> Saveable for Game<'a> {
+    fn save(&self, data: &mut SaveData) -> Result<(), SaveError> {
+        data.put("clock", &self.clock)?;
+        data.put("timers", &self.timers)?;
+        data.put("example", &ExampleState {
+            signaled: self.signaled,
+            check_signal_count: self.check_signal_count,
+        })
+    }
+
+    // Sections missing from the save keep their current values
+    fn load(&mut self, data: &SaveData) -> Result<(), SaveError> {
+        if let Some(clock) = data.get("clock")? {
+            self.clock = clock;
+        }
+        if let Some(timers) = data.get("timers")? {
+            self.timers = timers;
+        }
+        if let Some(example) = data.get::<ExampleState>("example")? {
+            self.signaled = example.signaled;
+            self.check_signal_count = example.check_signal_count;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+// Frame-based sprite animation from atlas regions.
+//
+// A Clip is a list of frames, an Animator plays clips as states of a small
+// state machine (idle, run, jump, ...) and switches between them when the
+// game sets parameters or triggers, or when a clip finishes.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use clock::GameClock;
+use events::{Event, EventType, EventPool};
+use graphics::atlas::Atlas;
+use graphics::sprite_batch::{SpriteBatch, SpriteParams};
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    // Name of the atlas region to show
+    pub region: String,
+    pub duration_ms: u64,
+    // Signaled as an OnAnimationEvent when the frame starts showing
+    pub event: Option<u32>,
+}
+
+impl Frame {
+    pub fn new(region: &str, duration_ms: u64) -> Self {
+        Frame { region: region.to_owned(), duration_ms, event: None }
+    }
+
+    pub fn with_event(mut self, event: u32) -> Self {
+        self.event = Some(event);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlaybackMode {
+    // Stops on the last frame
+    Once,
+    Loop,
+    // Plays forward then backward, forever
+    PingPong,
+}
+
+#[derive(Clone, Debug)]
+pub struct Clip {
+    frames: Vec<Frame>,
+    mode: PlaybackMode,
+}
+
+impl Clip {
+    pub fn new(frames: Vec<Frame>, mode: PlaybackMode) -> Self {
+        Clip { frames, mode }
+    }
+
+    // Regions named "<prefix><n>" for n in 0..count, all the same duration,
+    // e.g. run0, run1, ... as packed from run0.png, run1.png, ...
+    pub fn from_sequence(prefix: &str, count: usize, frame_ms: u64, mode: PlaybackMode)
+        -> Self {
+        let frames = (0..count)
+            .map(|i| Frame::new(&format!("{}{}", prefix, i), frame_ms))
+            .collect();
+        Clip::new(frames, mode)
+    }
+
+    // Adds an event to an existing frame
+    pub fn with_event(mut self, frame: usize, event: u32) -> Self {
+        if frame < self.frames.len() {
+            self.frames[frame].event = Some(event);
+        }
+        self
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+}
+
+// Playback position within one clip
+#[derive(Clone, Debug)]
+pub struct ClipPlayer {
+    frame: usize,
+    elapsed_ms: u64,
+    // Only goes backward in ping-pong mode
+    forward: bool,
+    finished: bool,
+}
+
+impl ClipPlayer {
+    pub fn new() -> Self {
+        ClipPlayer { frame: 0, elapsed_ms: 0, forward: true, finished: false }
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Moves through the clip, calling on_frame with the index of every
+    // frame that starts showing
+    pub fn advance<F: FnMut(usize)>(&mut self, clip: &Clip, delta_ms: u64, mut on_frame: F) {
+        let count = clip.frames.len();
+        if count == 0 || self.finished {
+            return;
+        }
+        self.elapsed_ms += delta_ms;
+        loop {
+            // Zero length frames would otherwise spin forever
+            let duration = clip.frames[self.frame].duration_ms.max(1);
+            if self.elapsed_ms < duration {
+                return;
+            }
+            self.elapsed_ms -= duration;
+
+            let next = match clip.mode {
+                PlaybackMode::Once => {
+                    if self.frame + 1 == count {
+                        self.finished = true;
+                        self.elapsed_ms = 0;
+                        return;
+                    }
+                    self.frame + 1
+                }
+                PlaybackMode::Loop => (self.frame + 1) % count,
+                PlaybackMode::PingPong => {
+                    if count == 1 {
+                        0
+                    } else {
+                        if self.forward && self.frame + 1 == count {
+                            self.forward = false;
+                        } else if !self.forward && self.frame == 0 {
+                            self.forward = true;
+                        }
+                        if self.forward { self.frame + 1 } else { self.frame - 1 }
+                    }
+                }
+            };
+            self.frame = next;
+            on_frame(next);
+        }
+    }
+}
+
+impl Default for ClipPlayer {
+    fn default() -> Self {
+        ClipPlayer::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    // Set with Animator::trigger, consumed when the transition is taken or
+    // at the end of the next update
+    Trigger(String),
+    // Parameter set with Animator::set_bool equals the value
+    Bool(String, bool),
+    // The current clip finished (only happens for PlaybackMode::Once)
+    Finished,
+}
+
+#[derive(Clone, Debug)]
+struct Transition {
+    // None means from any state
+    from: Option<String>,
+    to: String,
+    conditions: Vec<Condition>,
+}
+
+// Plays clips as states and transitions between them, e.g.
+//   idle -> run when "running" is true, any -> jump on trigger "jump",
+//   jump -> idle when finished
+pub struct Animator {
+    id: u32,
+    states: HashMap<String, Clip>,
+    transitions: Vec<Transition>,
+    bools: HashMap<String, bool>,
+    triggers: Vec<String>,
+    current: String,
+    player: ClipPlayer,
+    // Events from the first frame of a new state are sent on the next update
+    entered: bool,
+    speed: f32,
+    // Scaled time below a whole ms, same as GameClock
+    carry_ms: f64,
+}
+
+impl Animator {
+    // id is sent as trigger_id in OnAnimationEvent
+    pub fn new(id: u32, initial_state: &str, clip: Clip) -> Self {
+        let mut states = HashMap::new();
+        states.insert(initial_state.to_owned(), clip);
+        Animator {
+            id,
+            states,
+            transitions: Vec::new(),
+            bools: HashMap::new(),
+            triggers: Vec::new(),
+            current: initial_state.to_owned(),
+            player: ClipPlayer::new(),
+            entered: true,
+            speed: 1.0,
+            carry_ms: 0.0,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn add_state(&mut self, name: &str, clip: Clip) {
+        self.states.insert(name.to_owned(), clip);
+    }
+
+    // Taken when every condition holds. Transitions are checked in the
+    // order they were added.
+    pub fn add_transition(&mut self, from: &str, to: &str, conditions: Vec<Condition>) {
+        self.transitions.push(Transition {
+            from: Some(from.to_owned()),
+            to: to.to_owned(),
+            conditions,
+        });
+    }
+
+    // Like add_transition but from any state other than to itself
+    pub fn add_any_transition(&mut self, to: &str, conditions: Vec<Condition>) {
+        self.transitions.push(Transition { from: None, to: to.to_owned(), conditions });
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bools.insert(name.to_owned(), value);
+    }
+
+    // Only lasts until the end of the next update, a trigger no transition
+    // from the current state reads is dropped rather than firing later
+    pub fn trigger(&mut self, name: &str) {
+        if !self.triggers.iter().any(|t| t == name) {
+            self.triggers.push(name.to_owned());
+        }
+    }
+
+    // Playback rate, 1.0 is normal
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn state(&self) -> &str {
+        &self.current
+    }
+
+    // Jumps straight to a state, restarting its clip
+    pub fn play(&mut self, state: &str) {
+        if !self.states.contains_key(state) {
+            println!(
+                "Warning: Animator {} has no state {} ({}:{}:{})",
+                self.id, state, file!(), line!(), column!());
+            return;
+        }
+        self.current = state.to_owned();
+        self.player = ClipPlayer::new();
+        self.entered = true;
+    }
+
+    // Atlas region of the frame currently showing
+    pub fn current_region(&self) -> Option<&str> {
+        let clip = self.states.get(&self.current)?;
+        clip.frames.get(self.player.frame()).map(|f| f.region.as_str())
+    }
+
+    // Advances by the clock's game time, so animations stop while paused
+    pub fn update(&mut self, clock: &GameClock, event_pool: &mut EventPool) {
+        let scaled = clock.game_delta_ms() as f64 * self.speed as f64 + self.carry_ms;
+        let delta_ms = scaled.floor() as u64;
+        self.carry_ms = scaled - delta_ms as f64;
+        self.advance(delta_ms, event_pool);
+    }
+
+    pub fn advance(&mut self, delta_ms: u64, event_pool: &mut EventPool) {
+        // Triggers are used up whether or not a transition read them
+        self.check_transitions();
+        self.triggers.clear();
+
+        let id = self.id;
+        let clip = match self.states.get(&self.current) {
+            Some(c) => c,
+            None => { return; }
+        };
+        let mut signal = |frame: usize| {
+            if let Some(event_id) = clip.frames[frame].event {
+                event_pool.signal_event(
+                    EventType::OnAnimationEvent,
+                    Event::OnAnimationEvent { trigger_id: id, event_id });
+            }
+        };
+        if self.entered {
+            self.entered = false;
+            if !clip.frames.is_empty() {
+                signal(0);
+            }
+        }
+        self.player.advance(clip, delta_ms, signal);
+    }
+
+    fn check_transitions(&mut self) {
+        let mut taken = None;
+        for (i, transition) in self.transitions.iter().enumerate() {
+            let from_matches = match transition.from {
+                Some(ref from) => *from == self.current,
+                None => transition.to != self.current,
+            };
+            if from_matches && transition.conditions.iter().all(|c| self.holds(c)) {
+                taken = Some(i);
+                break;
+            }
+        }
+        if let Some(i) = taken {
+            let to = self.transitions[i].to.clone();
+            self.play(&to);
+        }
+    }
+
+    fn holds(&self, condition: &Condition) -> bool {
+        match *condition {
+            Condition::Trigger(ref name) => self.triggers.iter().any(|t| t == name),
+            Condition::Bool(ref name, value) =>
+                self.bools.get(name).cloned().unwrap_or(false) == value,
+            Condition::Finished => self.player.is_finished(),
+        }
+    }
+
+    // Queues the current frame, params.uv is replaced with the region's UVs
+    pub fn draw<'a>(&self, batch: &mut SpriteBatch<'a>, atlas: &'a Atlas, mut params: SpriteParams) {
+        let region = match self.current_region() {
+            Some(r) => r,
+            None => { return; }
+        };
+        match atlas.get(region) {
+            Some((texture, uv)) => {
+                params.uv = uv;
+                batch.draw(texture, params);
+            }
+            None => {
+                println!(
+                    "Warning: atlas has no region {} ({}:{}:{})",
+                    region, file!(), line!(), column!());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animator() -> Animator {
+        let mut animator = Animator::new(
+            1, "idle", Clip::from_sequence("idle", 4, 100, PlaybackMode::Loop));
+        animator.add_state("jump", Clip::from_sequence("jump", 2, 100, PlaybackMode::Once));
+        animator.add_transition("idle", "jump", vec![Condition::Trigger("jump".to_owned())]);
+        animator.add_transition("jump", "idle", vec![Condition::Finished]);
+        animator
+    }
+
+    #[test]
+    fn slow_speed_still_advances() {
+        let mut animator = animator();
+        let mut clock = GameClock::new();
+        let mut pool = EventPool::new();
+        animator.set_speed(0.05);
+        // 16ms frames at 0.05 are 0.8ms each, 125 of them make one 100ms frame
+        for _ in 0..124 {
+            clock.tick(16);
+            animator.update(&clock, &mut pool);
+        }
+        assert_eq!(animator.current_region(), Some("idle0"));
+        clock.tick(16);
+        animator.update(&clock, &mut pool);
+        assert_eq!(animator.current_region(), Some("idle1"));
+    }
+
+    #[test]
+    fn fractional_speed_keeps_time() {
+        let mut animator = animator();
+        let mut clock = GameClock::new();
+        let mut pool = EventPool::new();
+        animator.set_speed(0.3);
+        // 0.3 * 16 * 250 = 1200ms, three times through the 400ms loop
+        for _ in 0..250 {
+            clock.tick(16);
+            animator.update(&clock, &mut pool);
+        }
+        assert_eq!(animator.current_region(), Some("idle0"));
+        assert!(animator.carry_ms < 1.0);
+    }
+
+    #[test]
+    fn trigger_taken_once() {
+        let mut animator = animator();
+        let mut pool = EventPool::new();
+        animator.trigger("jump");
+        animator.advance(0, &mut pool);
+        assert_eq!(animator.state(), "jump");
+        animator.advance(200, &mut pool);
+        animator.advance(0, &mut pool);
+        assert_eq!(animator.state(), "idle");
+        // Already used, doesn't jump again
+        animator.advance(0, &mut pool);
+        assert_eq!(animator.state(), "idle");
+    }
+
+    #[test]
+    fn unused_trigger_is_dropped() {
+        let mut animator = animator();
+        let mut pool = EventPool::new();
+        animator.play("jump");
+        // No jump -> jump transition, so this does nothing
+        animator.trigger("jump");
+        animator.advance(200, &mut pool);
+        animator.advance(0, &mut pool);
+        assert_eq!(animator.state(), "idle");
+        // And isn't still queued once a transition could read it
+        animator.advance(0, &mut pool);
+        assert_eq!(animator.state(), "idle");
+    }
+}
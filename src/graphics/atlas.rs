@@ -0,0 +1,472 @@
+// Texture atlases: many small images packed into a few large pages so
+// sprites can share texture bindings.
+//
+// An AtlasBuilder packs images (at build time or at load time) into a
+// PackedAtlas, which can be saved as PNG pages plus a JSON manifest and
+// loaded back onto the GPU as an Atlas.
+#![allow(dead_code)]
+
+extern crate image;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use self::image::{DynamicImage, GenericImage, RgbaImage};
+use graphics::sprite_batch::UvRect;
+use graphics::textures::{Texture, TextureOptions};
+
+// Places rectangles with the skyline bottom-left heuristic
+pub struct RectPacker {
+    width: u32,
+    height: u32,
+    // (x, y, width) segments of the top edge of everything placed so far
+    skyline: Vec<(u32, u32, u32)>,
+}
+
+impl RectPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        RectPacker { width, height, skyline: vec![(0, 0, width)] }
+    }
+
+    // Returns the top left corner of the placed rectangle, or None if full
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let mut best: Option<(usize, u32, u32)> = None;
+        for i in 0..self.skyline.len() {
+            let x = self.skyline[i].0;
+            if let Some(y) = self.fit(i, width, height) {
+                // Lowest position, then leftmost
+                let better = match best {
+                    Some((_, best_x, best_y)) => y < best_y || (y == best_y && x < best_x),
+                    None => true,
+                };
+                if better {
+                    best = Some((i, x, y));
+                }
+            }
+        }
+        let (index, x, y) = best?;
+        self.add_segment(index, x, y + height, width);
+        Some((x, y))
+    }
+
+    // Height the rectangle would sit at if its left edge is at segment i
+    fn fit(&self, i: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[i].0;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut width_left = width as i64;
+        let mut j = i;
+        while width_left > 0 {
+            if j >= self.skyline.len() {
+                return None;
+            }
+            y = y.max(self.skyline[j].1);
+            if y + height > self.height {
+                return None;
+            }
+            width_left -= self.skyline[j].2 as i64;
+            j += 1;
+        }
+        Some(y)
+    }
+
+    fn add_segment(&mut self, index: usize, x: u32, y: u32, width: u32) {
+        self.skyline.insert(index, (x, y, width));
+
+        // Shrink or remove the segments now covered by the new one
+        let i = index + 1;
+        while i < self.skyline.len() {
+            let (prev_x, _, prev_width) = self.skyline[i - 1];
+            let (seg_x, seg_y, seg_width) = self.skyline[i];
+            let prev_end = prev_x + prev_width;
+            if seg_x >= prev_end {
+                break;
+            }
+            let shrink = prev_end - seg_x;
+            if seg_width <= shrink {
+                self.skyline.remove(i);
+            } else {
+                self.skyline[i] = (seg_x + shrink, seg_y, seg_width - shrink);
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].1 == self.skyline[i + 1].1 {
+                self.skyline[i].2 += self.skyline[i + 1].2;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+// Where a named image ended up, in pixels from the page's top left
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRegion {
+    // UVs for a page uploaded with the default vertical flip, so v runs
+    // bottom to top like the rest of the renderer
+    pub fn uv(&self, page_width: u32, page_height: u32) -> UvRect {
+        let (w, h) = (page_width as f32, page_height as f32);
+        UvRect {
+            u0: self.x as f32 / w,
+            v0: 1.0 - (self.y + self.height) as f32 / h,
+            u1: (self.x + self.width) as f32 / w,
+            v1: 1.0 - self.y as f32 / h,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct ManifestPage {
+    file: String,
+    width: u32,
+    height: u32,
+}
+
+// JSON written next to the packed pages
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Manifest {
+    pages: Vec<ManifestPage>,
+    regions: BTreeMap<String, AtlasRegion>,
+}
+
+pub struct AtlasBuilder {
+    page_width: u32,
+    page_height: u32,
+    // Empty pixels between images
+    padding: u32,
+    // Pixels of each image's edge repeated outward, so linear filtering
+    // and mipmaps don't pull in neighbouring images
+    extrude: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_width: u32, page_height: u32) -> Self {
+        AtlasBuilder {
+            page_width,
+            page_height,
+            padding: 1,
+            extrude: 0,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_extrude(mut self, extrude: u32) -> Self {
+        self.extrude = extrude;
+        self
+    }
+
+    pub fn add(&mut self, name: &str, image: RgbaImage) {
+        self.images.push((name.to_owned(), image));
+    }
+
+    // Named after the file name without its extension
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(n) => n.to_owned(),
+            None => { return Err(format!("bad image path {:?}", path)); }
+        };
+        let image = image::open(path).map_err(|e| format!("{:?}: {}", path, e))?;
+        self.add(&name, image.to_rgba());
+        Ok(())
+    }
+
+    // Adds every png in a directory, e.g. "res/sprites"
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), String> {
+        let entries = fs::read_dir(dir.as_ref()).map_err(|e| e.to_string())?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("png"))
+            .collect();
+        // Same input order every time so packing is deterministic
+        paths.sort();
+        for path in paths {
+            self.add_file(&path)?;
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<PackedAtlas, String> {
+        let border = self.padding + self.extrude;
+
+        // Tallest first packs tighter
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| {
+            let (w, h) = self.images[i].1.dimensions();
+            (u32::MAX - h, u32::MAX - w)
+        });
+
+        let mut packers: Vec<RectPacker> = Vec::new();
+        let mut pages: Vec<RgbaImage> = Vec::new();
+        let mut regions = BTreeMap::new();
+
+        for i in order {
+            let (ref name, ref image) = self.images[i];
+            if regions.contains_key(name) {
+                return Err(format!("duplicate atlas image name {}", name));
+            }
+            let (width, height) = image.dimensions();
+            let (cell_w, cell_h) = (width + border * 2, height + border * 2);
+            if cell_w > self.page_width || cell_h > self.page_height {
+                return Err(format!(
+                    "{} ({}x{}) doesn't fit in a {}x{} page",
+                    name, width, height, self.page_width, self.page_height));
+            }
+
+            let mut placed = None;
+            for (page, packer) in packers.iter_mut().enumerate() {
+                if let Some(pos) = packer.insert(cell_w, cell_h) {
+                    placed = Some((page, pos));
+                    break;
+                }
+            }
+            let (page, (cell_x, cell_y)) = match placed {
+                Some(p) => p,
+                None => {
+                    let mut packer = RectPacker::new(self.page_width, self.page_height);
+                    let pos = packer.insert(cell_w, cell_h).unwrap();
+                    packers.push(packer);
+                    pages.push(RgbaImage::new(self.page_width, self.page_height));
+                    (pages.len() - 1, pos)
+                }
+            };
+
+            let (x, y) = (cell_x + border, cell_y + border);
+            pages[page].copy_from(image, x, y);
+            extrude_edges(&mut pages[page], x, y, width, height, self.extrude);
+            regions.insert(name.clone(), AtlasRegion { page, x, y, width, height });
+        }
+
+        Ok(PackedAtlas { pages, regions })
+    }
+}
+
+// Repeats the outermost pixels of the rectangle outward by amount
+fn extrude_edges(page: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, amount: u32) {
+    if amount == 0 || width == 0 || height == 0 {
+        return;
+    }
+    // Rows first, so the columns below copy the extruded rows into the
+    // corners too
+    for i in 1..(amount + 1) {
+        for px in x..(x + width) {
+            let top = *page.get_pixel(px, y);
+            let bottom = *page.get_pixel(px, y + height - 1);
+            page.put_pixel(px, y - i, top);
+            page.put_pixel(px, y + height - 1 + i, bottom);
+        }
+    }
+    for i in 1..(amount + 1) {
+        for py in (y - amount)..(y + height + amount) {
+            let left = *page.get_pixel(x, py);
+            let right = *page.get_pixel(x + width - 1, py);
+            page.put_pixel(x - i, py, left);
+            page.put_pixel(x + width - 1 + i, py, right);
+        }
+    }
+}
+
+// Packed pages still in CPU memory
+pub struct PackedAtlas {
+    pub pages: Vec<RgbaImage>,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl PackedAtlas {
+    // Writes <name>_<page>.png for each page and <name>.json into dir
+    pub fn save<P: AsRef<Path>>(&self, dir: P, name: &str) -> Result<(), String> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let mut manifest = Manifest { pages: Vec::new(), regions: self.regions.clone() };
+        for (i, page) in self.pages.iter().enumerate() {
+            let file = format!("{}_{}.png", name, i);
+            page.save(dir.join(&file)).map_err(|e| e.to_string())?;
+            manifest.pages.push(ManifestPage {
+                file,
+                width: page.width(),
+                height: page.height(),
+            });
+        }
+        let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        fs::write(dir.join(format!("{}.json", name)), json).map_err(|e| e.to_string())
+    }
+
+    // Reads a manifest and its pages, page files are relative to the manifest
+    pub fn load<P: AsRef<Path>>(manifest_path: P) -> Result<PackedAtlas, String> {
+        let manifest_path = manifest_path.as_ref();
+        let json = fs::read_to_string(manifest_path).map_err(|e| e.to_string())?;
+        let manifest: Manifest = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        let dir = manifest_path.parent().unwrap_or(Path::new("."));
+        let mut pages = Vec::new();
+        for page in manifest.pages.iter() {
+            let image = image::open(dir.join(&page.file))
+                .map_err(|e| format!("{}: {}", page.file, e))?;
+            pages.push(image.to_rgba());
+        }
+        for (name, region) in manifest.regions.iter() {
+            if region.page >= pages.len() {
+                return Err(format!("{} is on missing page {}", name, region.page));
+            }
+        }
+        Ok(PackedAtlas { pages, regions: manifest.regions })
+    }
+
+    // Uploads the pages, options should keep the default vertical flip
+    pub fn upload(self, options: &TextureOptions) -> Result<Atlas, String> {
+        let mut pages = Vec::new();
+        for page in self.pages {
+            pages.push(Texture::from_image(&DynamicImage::ImageRgba8(page), options)?);
+        }
+        Ok(Atlas { pages, regions: self.regions })
+    }
+}
+
+// Atlas pages on the GPU with regions looked up by name
+pub struct Atlas {
+    pages: Vec<Texture>,
+    regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl Atlas {
+    pub fn load<P: AsRef<Path>>(manifest_path: P, options: &TextureOptions)
+        -> Result<Atlas, String> {
+        PackedAtlas::load(manifest_path)?.upload(options)
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    // Texture and UVs to draw the named image with
+    pub fn get(&self, name: &str) -> Option<(&Texture, UvRect)> {
+        let region = self.regions.get(name)?;
+        let page = &self.pages[region.page];
+        Some((page, region.uv(page.width(), page.height())))
+    }
+
+    pub fn page(&self, index: usize) -> Option<&Texture> {
+        self.pages.get(index)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.regions.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::image::Rgba;
+
+    fn overlaps(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool {
+        a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+    }
+
+    #[test]
+    fn packer_places_without_overlap() {
+        let mut packer = RectPacker::new(64, 64);
+        let sizes = [(30, 20), (20, 30), (16, 16), (40, 10), (10, 20), (8, 8), (24, 12)];
+        let mut placed = Vec::new();
+        for &(w, h) in sizes.iter() {
+            let (x, y) = packer.insert(w, h).unwrap();
+            assert!(x + w <= 64 && y + h <= 64);
+            let rect = (x, y, w, h);
+            assert!(placed.iter().all(|&other| !overlaps(rect, other)));
+            placed.push(rect);
+        }
+    }
+
+    #[test]
+    fn packer_fills_up() {
+        let mut packer = RectPacker::new(32, 32);
+        for _ in 0..4 {
+            assert!(packer.insert(16, 16).is_some());
+        }
+        assert_eq!(packer.insert(1, 1), None);
+        assert_eq!(RectPacker::new(32, 32).insert(33, 1), None);
+    }
+
+    #[test]
+    fn extrude_fills_the_whole_border() {
+        let (x, y, width, height, amount) = (3, 3, 2, 2, 2);
+        let mut page = RgbaImage::new(9, 9);
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [255, 255, 0, 255]];
+        page.put_pixel(x, y, Rgba(colors[0]));
+        page.put_pixel(x + 1, y, Rgba(colors[1]));
+        page.put_pixel(x, y + 1, Rgba(colors[2]));
+        page.put_pixel(x + 1, y + 1, Rgba(colors[3]));
+        extrude_edges(&mut page, x, y, width, height, amount);
+
+        // Every border pixel, corners included, repeats the nearest one
+        // inside the rectangle
+        for py in (y - amount)..(y + height + amount) {
+            for px in (x - amount)..(x + width + amount) {
+                let nearest = (
+                    px.max(x).min(x + width - 1),
+                    py.max(y).min(y + height - 1));
+                assert_eq!(page.get_pixel(px, py), page.get_pixel(nearest.0, nearest.1),
+                           "pixel {},{}", px, py);
+            }
+        }
+        // Nothing outside the border
+        assert_eq!(page.get_pixel(0, 0)[3], 0);
+        assert_eq!(page.get_pixel(8, 8)[3], 0);
+    }
+
+    #[test]
+    fn build_leaves_room_for_border() {
+        let atlas = AtlasBuilder::new(32, 32)
+            .with_padding(1)
+            .with_extrude(2)
+            .build_with(&[("a", 8, 8), ("b", 4, 12), ("c", 10, 3)]);
+        for (name, region) in atlas.regions.iter() {
+            assert!(region.x >= 3 && region.y >= 3, "{}", name);
+            assert_eq!(atlas.pages[region.page].get_pixel(region.x - 2, region.y - 2)[3], 255);
+            assert_eq!(atlas.pages[region.page].get_pixel(region.x - 3, region.y - 3)[3], 0);
+        }
+        let cells: Vec<_> = atlas.regions.values()
+            .map(|r| (r.x - 3, r.y - 3, r.width + 6, r.height + 6))
+            .collect();
+        for i in 0..cells.len() {
+            for j in (i + 1)..cells.len() {
+                assert!(!overlaps(cells[i], cells[j]));
+            }
+        }
+    }
+
+    impl AtlasBuilder {
+        // Builds from opaque white images of the given sizes
+        fn build_with(mut self, images: &[(&str, u32, u32)]) -> PackedAtlas {
+            for &(name, w, h) in images.iter() {
+                self.add(name, RgbaImage::from_pixel(w, h, Rgba([255, 255, 255, 255])));
+            }
+            self.build().unwrap()
+        }
+    }
+}
@@ -1,6 +1,9 @@
 extern crate gl;
 
-use std::mem;
+use std::{mem, ptr, slice};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut, Range};
+use std::os::raw::c_void;
 
 #[repr(u32)]
 #[derive(Clone, Copy)]
@@ -22,7 +25,7 @@ pub enum BufferTarget {
 }
 
 #[repr(u32)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum BufferUsage {
     StreamDraw = gl::STREAM_DRAW, 
     StreamRead = gl::STREAM_READ, 
@@ -35,6 +38,14 @@ pub enum BufferUsage {
     DynamicCopy = gl::DYNAMIC_COPY,
 }
 
+impl BufferUsage {
+    // Usages the driver keeps somewhere the CPU can read back from quickly
+    pub fn is_read(&self) -> bool {
+        matches!(*self,
+            BufferUsage::StreamRead | BufferUsage::StaticRead | BufferUsage::DynamicRead)
+    }
+}
+
 pub struct Buffer{ 
     id: u32,
     target: BufferTarget,
@@ -85,9 +96,40 @@ impl Buffer {
         self.unbind();
     }
 
+    // Same as set_data without taking ownership, for data reused every frame
+    pub fn set_data_from_slice<T>(&self, data: &[T]) {
+        self.bind();
+        unsafe {
+            gl::BufferData(
+                self.target as u32,
+                mem::size_of_val(data) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+                self.usage as u32
+            );
+        }
+        self.unbind();
+    }
+
     pub fn target(&self) -> BufferTarget {
         self.target
     }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // Generates a buffer without any storage
+    fn generate(target: BufferTarget, usage: BufferUsage) -> Self {
+        let mut id: u32 = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+        }
+        Buffer { id, target, usage }
+    }
 }
 
 impl Drop for Buffer {
@@ -96,4 +138,415 @@ impl Drop for Buffer {
             gl::DeleteBuffers(1, &self.id);
         }
     }
-}
\ No newline at end of file
+}
+
+// A buffer of T that remembers how many elements it holds and how many fit
+// in its storage, so updates can reuse the storage instead of reallocating.
+// T should be plain data (repr(C), no pointers).
+pub struct TypedBuffer<T: Copy> {
+    buffer: Buffer,
+    len: usize,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> TypedBuffer<T> {
+    pub fn new(target: BufferTarget, usage: BufferUsage) -> Self {
+        TypedBuffer {
+            buffer: Buffer::generate(target, usage),
+            len: 0,
+            capacity: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_capacity(target: BufferTarget, usage: BufferUsage, capacity: usize) -> Self {
+        let mut buffer = TypedBuffer::new(target, usage);
+        buffer.allocate(capacity, ptr::null());
+        buffer
+    }
+
+    pub fn from_slice(target: BufferTarget, usage: BufferUsage, data: &[T]) -> Self {
+        let mut buffer = TypedBuffer::new(target, usage);
+        buffer.allocate(data.len(), data.as_ptr() as *const c_void);
+        buffer.len = data.len();
+        buffer
+    }
+
+    // The untyped buffer, for binding and VAO setup
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.buffer.unbind();
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.len * mem::size_of::<T>()
+    }
+
+    // Replaces the contents. Reuses the storage if data fits.
+    pub fn set_data(&mut self, data: &[T]) {
+        if data.len() > self.capacity {
+            self.allocate(data.len(), data.as_ptr() as *const c_void);
+        } else {
+            self.sub_data(0, data);
+        }
+        self.len = data.len();
+    }
+
+    // Replaces the contents for data that changes every frame. Orphans the
+    // old storage first so the driver doesn't wait for draws still reading it.
+    pub fn stream_data(&mut self, data: &[T]) {
+        if data.len() > self.capacity {
+            self.allocate(data.len(), data.as_ptr() as *const c_void);
+        } else {
+            self.orphan();
+            self.sub_data(0, data);
+        }
+        self.len = data.len();
+    }
+
+    // Overwrites elements starting at offset, the range must be within len
+    pub fn update_range(&mut self, offset: usize, data: &[T]) -> Result<(), String> {
+        if offset + data.len() > self.len {
+            return Err(format!(
+                "update of {}..{} is outside buffer of length {}",
+                offset, offset + data.len(), self.len));
+        }
+        self.sub_data(offset, data);
+        Ok(())
+    }
+
+    // Appends, growing the storage by doubling when it's full (see reserve)
+    pub fn extend_from_slice(&mut self, data: &[T]) {
+        let needed = self.len + data.len();
+        if needed > self.capacity {
+            let capacity = needed.max(self.capacity * 2);
+            self.reserve(capacity);
+        }
+        let offset = self.len;
+        self.sub_data(offset, data);
+        self.len = needed;
+    }
+
+    // Grows the storage to hold at least capacity elements, keeping the
+    // contents. The GL buffer keeps its id so VAOs set up with it stay
+    // valid. The contents are copied out to a temporary buffer and back on
+    // the GPU, nothing is read back.
+    pub fn reserve(&mut self, capacity: usize) {
+        if capacity <= self.capacity {
+            return;
+        }
+        if self.len == 0 {
+            self.allocate(capacity, ptr::null());
+            return;
+        }
+        let bytes = self.size_in_bytes() as gl::types::GLsizeiptr;
+        let temp = Buffer::generate(BufferTarget::CopyWriteBuffer, BufferUsage::StreamCopy);
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, self.buffer.id);
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, temp.id);
+            gl::BufferData(gl::COPY_WRITE_BUFFER, bytes, ptr::null(), temp.usage as u32);
+            gl::CopyBufferSubData(gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER, 0, 0, bytes);
+        }
+        self.allocate(capacity, ptr::null());
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, temp.id);
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.buffer.id);
+            gl::CopyBufferSubData(gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER, 0, 0, bytes);
+            gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+    }
+
+    // Keeps the storage, forgets the contents past len
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // Gives the storage back to the driver and gets a fresh one of the same
+    // size. Contents are lost.
+    pub fn orphan(&mut self) {
+        let capacity = self.capacity;
+        self.allocate(capacity, ptr::null());
+        self.len = 0;
+    }
+
+    // Copies elements back from the GPU. Works with any usage, but is only
+    // fast for the *Read ones.
+    pub fn read(&self) -> Vec<T> {
+        self.read_range(0..self.len).unwrap_or_default()
+    }
+
+    pub fn read_range(&self, range: Range<usize>) -> Result<Vec<T>, String> {
+        self.check_range(&range)?;
+        if cfg!(debug_assertions) && !self.buffer.usage.is_read() {
+            println!(
+                "Warning: reading back buffer {} that wasn't created with a *Read usage ({}:{}:{})",
+                self.buffer.id, file!(), line!(), column!());
+        }
+        let count = range.end - range.start;
+        let mut data: Vec<T> = Vec::with_capacity(count);
+        self.bind();
+        unsafe {
+            gl::GetBufferSubData(
+                self.buffer.target as u32,
+                (range.start * mem::size_of::<T>()) as gl::types::GLintptr,
+                (count * mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                data.as_mut_ptr() as *mut c_void);
+            data.set_len(count);
+        }
+        self.unbind();
+        Ok(data)
+    }
+
+    // Maps elements for reading. The buffer can't be used until the guard
+    // is dropped.
+    pub fn map_read<'a>(&'a mut self, range: Range<usize>) -> Result<MapRead<'a, T>, String> {
+        let ptr = self.map_range(&range, gl::MAP_READ_BIT)?;
+        Ok(MapRead { buffer: self, ptr: ptr as *const T, len: range.end - range.start })
+    }
+
+    // Maps elements for writing. With invalidate the old contents of the
+    // range are thrown away, so the driver doesn't have to wait for draws
+    // still reading them, but the slice starts out undefined. Writes only
+    // reach the GPU for ranges passed to MapWrite::flush, or the whole
+    // range when the guard is dropped if nothing was flushed.
+    pub fn map_write<'a>(&'a mut self, range: Range<usize>, invalidate: bool)
+        -> Result<MapWrite<'a, T>, String> {
+        let mut access = gl::MAP_WRITE_BIT | gl::MAP_FLUSH_EXPLICIT_BIT;
+        if invalidate {
+            access |= gl::MAP_INVALIDATE_RANGE_BIT;
+        }
+        let ptr = self.map_range(&range, access)?;
+        Ok(MapWrite {
+            buffer: self,
+            ptr: ptr as *mut T,
+            len: range.end - range.start,
+            flushed: false,
+        })
+    }
+
+    fn map_range(&self, range: &Range<usize>, access: gl::types::GLbitfield)
+        -> Result<*mut c_void, String> {
+        self.check_range(range)?;
+        if range.start == range.end {
+            return Err("can't map an empty range".to_owned());
+        }
+        let size = mem::size_of::<T>();
+        self.bind();
+        let ptr = unsafe {
+            gl::MapBufferRange(
+                self.buffer.target as u32,
+                (range.start * size) as gl::types::GLintptr,
+                ((range.end - range.start) * size) as gl::types::GLsizeiptr,
+                access)
+        };
+        if ptr.is_null() {
+            self.unbind();
+            return Err(format!("mapping buffer {} failed", self.buffer.id));
+        }
+        Ok(ptr)
+    }
+
+    fn unmap(&self) {
+        unsafe {
+            gl::UnmapBuffer(self.buffer.target as u32);
+        }
+        self.unbind();
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<(), String> {
+        if range.start > range.end || range.end > self.len {
+            return Err(format!(
+                "range {}..{} is outside buffer of length {}",
+                range.start, range.end, self.len));
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self, capacity: usize, data: *const c_void) {
+        self.bind();
+        unsafe {
+            gl::BufferData(
+                self.buffer.target as u32,
+                (capacity * mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                data,
+                self.buffer.usage as u32
+            );
+        }
+        self.unbind();
+        self.capacity = capacity;
+    }
+
+    fn sub_data(&self, offset: usize, data: &[T]) {
+        if data.is_empty() {
+            return;
+        }
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                self.buffer.target as u32,
+                (offset * mem::size_of::<T>()) as gl::types::GLintptr,
+                mem::size_of_val(data) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const c_void
+            );
+        }
+        self.unbind();
+    }
+}
+
+// Mapped elements for reading, unmapped on drop
+pub struct MapRead<'a, T: Copy + 'a> {
+    buffer: &'a mut TypedBuffer<T>,
+    ptr: *const T,
+    len: usize,
+}
+
+impl<'a, T: Copy> Deref for MapRead<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a, T: Copy> Drop for MapRead<'a, T> {
+    fn drop(&mut self) {
+        self.buffer.bind();
+        self.buffer.unmap();
+    }
+}
+
+// Mapped elements for writing, flushed and unmapped on drop
+pub struct MapWrite<'a, T: Copy + 'a> {
+    buffer: &'a mut TypedBuffer<T>,
+    ptr: *mut T,
+    len: usize,
+    flushed: bool,
+}
+
+impl<'a, T: Copy> MapWrite<'a, T> {
+    // Sends written elements to the GPU, range is relative to the mapping
+    pub fn flush(&mut self, range: Range<usize>) {
+        if range.start >= range.end || range.end > self.len {
+            println!(
+                "Warning: flush of {}..{} is outside mapping of length {} ({}:{}:{})",
+                range.start, range.end, self.len, file!(), line!(), column!());
+            return;
+        }
+        let size = mem::size_of::<T>();
+        self.buffer.bind();
+        unsafe {
+            gl::FlushMappedBufferRange(
+                self.buffer.buffer.target as u32,
+                (range.start * size) as gl::types::GLintptr,
+                ((range.end - range.start) * size) as gl::types::GLsizeiptr);
+        }
+        self.flushed = true;
+    }
+}
+
+impl<'a, T: Copy> Deref for MapWrite<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a, T: Copy> DerefMut for MapWrite<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<'a, T: Copy> Drop for MapWrite<'a, T> {
+    fn drop(&mut self) {
+        if !self.flushed {
+            let len = self.len;
+            self.flush(0..len);
+        }
+        self.buffer.bind();
+        self.buffer.unmap();
+    }
+}
+
+// Fixed size buffer that stays mapped for writing for its whole life
+// (GL 4.4 or ARB_buffer_storage). Writes are visible to the GPU without
+// flushing, but nothing stops the CPU overwriting elements a draw is still
+// reading, so callers should write to different parts each frame or wait
+// on a fence.
+pub struct PersistentBuffer<T: Copy> {
+    buffer: Buffer,
+    ptr: *mut T,
+    capacity: usize,
+}
+
+impl<T: Copy> PersistentBuffer<T> {
+    pub fn new(target: BufferTarget, capacity: usize) -> Result<Self, String> {
+        if !gl::BufferStorage::is_loaded() {
+            return Err("persistent mapping needs GL 4.4 or ARB_buffer_storage".to_owned());
+        }
+        if capacity == 0 {
+            return Err("persistent buffer needs a capacity".to_owned());
+        }
+        let buffer = Buffer::generate(target, BufferUsage::StreamDraw);
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        let bytes = (capacity * mem::size_of::<T>()) as gl::types::GLsizeiptr;
+        buffer.bind();
+        let ptr = unsafe {
+            gl::BufferStorage(target as u32, bytes, ptr::null(), flags);
+            gl::MapBufferRange(target as u32, 0, bytes, flags)
+        };
+        buffer.unbind();
+        if ptr.is_null() {
+            return Err(format!("mapping buffer {} failed", buffer.id));
+        }
+        Ok(PersistentBuffer { buffer, ptr: ptr as *mut T, capacity })
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.capacity) }
+    }
+}
+
+impl<T: Copy> Drop for PersistentBuffer<T> {
+    fn drop(&mut self) {
+        self.buffer.bind();
+        unsafe {
+            gl::UnmapBuffer(self.buffer.target as u32);
+        }
+        self.buffer.unbind();
+    }
+}
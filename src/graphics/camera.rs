@@ -0,0 +1,466 @@
+#![allow(dead_code)]
+
+extern crate nalgebra as na;
+extern crate sdl2;
+
+use std::f32::consts::PI;
+use self::na::{Matrix4, Orthographic3, Perspective3, Point3, Vector3, Vector4};
+use self::sdl2::keyboard::{KeyboardState, Scancode};
+use controller::Controllers;
+use controller::sdl2::controller::{Axis, Button};
+use graphics::program;
+
+// Keeps pitch away from straight up/down so the view matrix stays valid
+const MAX_PITCH: f32 = PI / 2.0 - 0.01;
+
+// Controller sticks below this magnitude are treated as centered
+const AXIS_DEADZONE: f32 = 0.15;
+
+pub enum Projection {
+    Perspective(Perspective3<f32>),
+    Orthographic(Orthographic3<f32>),
+}
+
+pub struct Camera {
+    position: Point3<f32>,
+    // Rotation around the world up axis and around the camera's right axis,
+    // both in radians. A yaw of 0 looks down -Z like OpenGL's default.
+    yaw: f32,
+    pitch: f32,
+    up: Vector3<f32>,
+    projection: Projection,
+}
+
+impl Camera {
+    // fovy is the vertical field of view in radians
+    pub fn perspective(aspect: f32, fovy: f32, znear: f32, zfar: f32) -> Self {
+        Camera::with_projection(
+            Projection::Perspective(Perspective3::new(aspect, fovy, znear, zfar)))
+    }
+
+    pub fn orthographic(
+        left: f32, right: f32, bottom: f32, top: f32,
+        znear: f32, zfar: f32) -> Self {
+        Camera::with_projection(
+            Projection::Orthographic(
+                Orthographic3::new(left, right, bottom, top, znear, zfar)))
+    }
+
+    fn with_projection(projection: Projection) -> Self {
+        Camera {
+            position: Point3::new(0.0, 0.0, 0.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            up: Vector3::y(),
+            projection,
+        }
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Point3<f32>) {
+        self.position = position;
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw % (2.0 * PI);
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn rotate(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        let (yaw, pitch) = (self.yaw + delta_yaw, self.pitch + delta_pitch);
+        self.set_rotation(yaw, pitch);
+    }
+
+    // Points the camera at target without moving it
+    pub fn look_at(&mut self, target: &Point3<f32>) {
+        let dir = target - self.position;
+        if dir.norm() <= f32::EPSILON {
+            return;
+        }
+        let dir = dir.normalize();
+        let yaw = dir.x.atan2(-dir.z);
+        let pitch = dir.y.asin();
+        self.set_rotation(yaw, pitch);
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            -self.yaw.cos() * self.pitch.cos())
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.forward().cross(&self.up).normalize()
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.right().cross(&self.forward())
+    }
+
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    pub fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    // Call when the window is resized. Orthographic cameras keep their
+    // height and widen or narrow around the center.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        match self.projection {
+            Projection::Perspective(ref mut p) => p.set_aspect(aspect),
+            Projection::Orthographic(ref mut o) => {
+                let half_width = (o.top() - o.bottom()) * aspect / 2.0;
+                let center = (o.left() + o.right()) / 2.0;
+                o.set_left_and_right(center - half_width, center + half_width);
+            }
+        }
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let target = self.position + self.forward();
+        Matrix4::look_at_rh(&self.position, &target, &self.up)
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective(ref p) => p.to_homogeneous(),
+            Projection::Orthographic(ref o) => o.to_homogeneous(),
+        }
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection())
+    }
+
+    // Uploads the view and projection matrices to the program's
+    // "view" and "projection" uniforms. Leaves the program in use.
+    pub fn upload(&self, program: &program::Program) -> Result<(), String> {
+        program.set_used(true);
+        program.set_uniform("view", &self.view_matrix())?;
+        program.set_uniform("projection", &self.projection_matrix())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum FrustumPlane {
+    Left = 0,
+    Right = 1,
+    Bottom = 2,
+    Top = 3,
+    Near = 4,
+    Far = 5,
+}
+
+// Six normalized planes in the form ax + by + cz + d = 0, normals pointing in
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // Extracts the planes from a view-projection matrix
+    // (Gribb & Hartmann method)
+    pub fn from_matrix(m: &Matrix4<f32>) -> Self {
+        let row = |i: usize| Vector4::new(m[(i, 0)], m[(i, 1)], m[(i, 2)], m[(i, 3)]);
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let mut planes = [
+            r3 + r0, // left
+            r3 - r0, // right
+            r3 + r1, // bottom
+            r3 - r1, // top
+            r3 + r2, // near
+            r3 - r2, // far
+        ];
+        for plane in planes.iter_mut() {
+            let len = Vector3::new(plane.x, plane.y, plane.z).norm();
+            if len > 0.0 {
+                *plane /= len;
+            }
+        }
+        Frustum { planes }
+    }
+
+    pub fn plane(&self, plane: FrustumPlane) -> Vector4<f32> {
+        self.planes[plane as usize]
+    }
+
+    fn distance(plane: &Vector4<f32>, point: &Point3<f32>) -> f32 {
+        plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+    }
+
+    pub fn contains_point(&self, point: &Point3<f32>) -> bool {
+        self.planes.iter().all(|p| Frustum::distance(p, point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: &Point3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|p| Frustum::distance(p, center) >= -radius)
+    }
+
+    // Conservative test, may report true for boxes just outside a corner
+    pub fn intersects_aabb(&self, min: &Point3<f32>, max: &Point3<f32>) -> bool {
+        for p in self.planes.iter() {
+            // Corner furthest along the plane normal
+            let positive = Point3::new(
+                if p.x >= 0.0 { max.x } else { min.x },
+                if p.y >= 0.0 { max.y } else { min.y },
+                if p.z >= 0.0 { max.z } else { min.z });
+            if Frustum::distance(p, &positive) < 0.0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// One frame of camera movement input, each axis in -1.0..1.0.
+// Movement is relative to the camera (x right, y up, z forward).
+#[derive(Clone, Copy, Default)]
+pub struct CameraInput {
+    pub move_x: f32,
+    pub move_y: f32,
+    pub move_z: f32,
+    pub look_x: f32,
+    pub look_y: f32,
+    pub zoom: f32,
+}
+
+impl CameraInput {
+    // Left stick moves, right stick looks, triggers move down/up and the
+    // shoulder buttons zoom
+    pub fn from_controller(controllers: &Controllers, number: usize) -> Self {
+        let axis = |a: Axis| match controllers.axis(number, a) {
+            Some(v) => normalize_axis(v),
+            None => 0.0,
+        };
+        let button = |b: Button| if controllers.button(number, b) == Some(true) { 1.0 } else { 0.0 };
+        CameraInput {
+            move_x: axis(Axis::LeftX),
+            move_y: axis(Axis::TriggerRight) - axis(Axis::TriggerLeft),
+            // Stick up is negative in SDL
+            move_z: -axis(Axis::LeftY),
+            look_x: axis(Axis::RightX),
+            look_y: -axis(Axis::RightY),
+            zoom: button(Button::RightShoulder) - button(Button::LeftShoulder),
+        }
+    }
+
+    // WASD moves, Space/LShift move up/down, arrow keys look, Q/E zoom
+    pub fn from_keyboard(keyboard: &KeyboardState) -> Self {
+        let key = |s: Scancode| if keyboard.is_scancode_pressed(s) { 1.0 } else { 0.0 };
+        CameraInput {
+            move_x: key(Scancode::D) - key(Scancode::A),
+            move_y: key(Scancode::Space) - key(Scancode::LShift),
+            move_z: key(Scancode::W) - key(Scancode::S),
+            look_x: key(Scancode::Right) - key(Scancode::Left),
+            look_y: key(Scancode::Up) - key(Scancode::Down),
+            zoom: key(Scancode::E) - key(Scancode::Q),
+        }
+    }
+
+    // Adds two inputs together so keyboard and controller can both be used
+    pub fn combine(&self, other: &CameraInput) -> Self {
+        let clamp = |v: f32| v.clamp(-1.0, 1.0);
+        CameraInput {
+            move_x: clamp(self.move_x + other.move_x),
+            move_y: clamp(self.move_y + other.move_y),
+            move_z: clamp(self.move_z + other.move_z),
+            look_x: clamp(self.look_x + other.look_x),
+            look_y: clamp(self.look_y + other.look_y),
+            zoom: clamp(self.zoom + other.zoom),
+        }
+    }
+}
+
+fn normalize_axis(value: i16) -> f32 {
+    let v = value as f32 / i16::MAX as f32;
+    if v.abs() < AXIS_DEADZONE { 0.0 } else { v.clamp(-1.0, 1.0) }
+}
+
+// Free-flying camera, moves along its own axes
+pub struct FlyController {
+    // Units per second
+    pub move_speed: f32,
+    // Radians per second
+    pub look_speed: f32,
+}
+
+impl FlyController {
+    pub fn new(move_speed: f32, look_speed: f32) -> Self {
+        FlyController { move_speed, look_speed }
+    }
+
+    pub fn update(&self, camera: &mut Camera, input: &CameraInput,
+                  delta_time_ms: u64) {
+        let dt = delta_time_ms as f32 / 1000.0;
+        camera.rotate(input.look_x * self.look_speed * dt,
+                      input.look_y * self.look_speed * dt);
+
+        let movement = camera.right() * input.move_x
+            + Vector3::y() * input.move_y
+            + camera.forward() * input.move_z;
+        let position = camera.position() + movement * self.move_speed * dt;
+        camera.set_position(position);
+    }
+}
+
+// Camera that circles a target point at a distance
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    // Radians per second
+    pub orbit_speed: f32,
+    // Units per second
+    pub zoom_speed: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            orbit_speed: PI,
+            zoom_speed: 10.0,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &CameraInput,
+                  delta_time_ms: u64) {
+        let dt = delta_time_ms as f32 / 1000.0;
+        self.yaw += input.look_x * self.orbit_speed * dt;
+        self.pitch = (self.pitch - input.look_y * self.orbit_speed * dt)
+            .clamp(-MAX_PITCH, MAX_PITCH);
+        self.distance = (self.distance - input.zoom * self.zoom_speed * dt)
+            .clamp(self.min_distance, self.max_distance.max(self.min_distance));
+
+        // Offset from the target, yaw 0 places the camera on +Z looking back
+        let offset = Vector3::new(
+            -self.yaw.sin() * self.pitch.cos(),
+            self.pitch.sin(),
+            self.yaw.cos() * self.pitch.cos()) * self.distance;
+        camera.set_position(self.target + offset);
+        camera.look_at(&self.target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).norm() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn orthographic_frustum() {
+        let mut camera = Camera::orthographic(-1.0, 1.0, -1.0, 1.0, 0.1, 10.0);
+        camera.set_position(Point3::new(0.0, 0.0, 5.0));
+        let frustum = camera.frustum();
+        assert!(frustum.contains_point(&Point3::new(0.0, 0.0, 0.0)));
+        assert!(frustum.contains_point(&Point3::new(0.9, -0.9, -4.0)));
+        assert!(!frustum.contains_point(&Point3::new(1.5, 0.0, 0.0)));
+        // Behind the camera and past the far plane
+        assert!(!frustum.contains_point(&Point3::new(0.0, 0.0, 6.0)));
+        assert!(!frustum.contains_point(&Point3::new(0.0, 0.0, -6.0)));
+
+        assert!(frustum.intersects_sphere(&Point3::new(1.3, 0.0, 0.0), 0.5));
+        assert!(!frustum.intersects_sphere(&Point3::new(1.6, 0.0, 0.0), 0.5));
+        assert!(frustum.intersects_aabb(&Point3::new(0.5, 0.5, -1.0), &Point3::new(3.0, 3.0, 1.0)));
+        assert!(!frustum.intersects_aabb(&Point3::new(1.5, -1.0, -1.0), &Point3::new(3.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn perspective_frustum() {
+        // 90 degrees vertically and horizontally, looking down -Z
+        let camera = Camera::perspective(1.0, PI / 2.0, 0.1, 100.0);
+        let frustum = camera.frustum();
+        assert!(frustum.contains_point(&Point3::new(0.0, 0.0, -10.0)));
+        assert!(frustum.contains_point(&Point3::new(9.0, -9.0, -10.0)));
+        assert!(!frustum.contains_point(&Point3::new(11.0, 0.0, -10.0)));
+        assert!(!frustum.contains_point(&Point3::new(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(&Point3::new(0.0, 0.0, -0.05)));
+        assert!(!frustum.contains_point(&Point3::new(0.0, 0.0, -200.0)));
+
+        // Planes point inwards
+        let near = frustum.plane(FrustumPlane::Near);
+        assert!(near.z < 0.0);
+    }
+
+    #[test]
+    fn orbit_keeps_distance() {
+        let target = Point3::new(1.0, 2.0, 3.0);
+        let mut camera = Camera::perspective(1.0, 1.0, 0.1, 100.0);
+        let mut orbit = OrbitController::new(target, 5.0);
+        orbit.update(&mut camera, &CameraInput::default(), 16);
+        assert_close(camera.position() - target, Vector3::new(0.0, 0.0, 5.0));
+
+        let input = CameraInput { look_x: 1.0, look_y: -0.3, ..CameraInput::default() };
+        for _ in 0..10 {
+            orbit.update(&mut camera, &input, 50);
+            let offset = camera.position() - target;
+            assert!((offset.norm() - 5.0).abs() < 1e-4);
+            assert_close(camera.forward(), -offset / 5.0);
+        }
+
+        // Zoom stops at min_distance
+        orbit.min_distance = 2.0;
+        let zoom = CameraInput { zoom: 1.0, ..CameraInput::default() };
+        orbit.update(&mut camera, &zoom, 1000);
+        assert!(((camera.position() - target).norm() - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fly_moves_relative_to_yaw() {
+        let mut camera = Camera::perspective(1.0, 1.0, 0.1, 100.0);
+        let fly = FlyController::new(2.0, PI / 2.0);
+
+        let forward = CameraInput { move_z: 1.0, ..CameraInput::default() };
+        fly.update(&mut camera, &forward, 1000);
+        assert_close(camera.position().coords, Vector3::new(0.0, 0.0, -2.0));
+
+        // A quarter turn right, then forward is +X and right is +Z
+        let turn = CameraInput { look_x: 1.0, ..CameraInput::default() };
+        fly.update(&mut camera, &turn, 1000);
+        assert_close(camera.forward(), Vector3::new(1.0, 0.0, 0.0));
+        fly.update(&mut camera, &forward, 500);
+        assert_close(camera.position().coords, Vector3::new(1.0, 0.0, -2.0));
+        let right = CameraInput { move_x: 1.0, move_y: 1.0, ..CameraInput::default() };
+        fly.update(&mut camera, &right, 500);
+        assert_close(camera.position().coords, Vector3::new(1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn combined_input_is_clamped() {
+        let a = CameraInput { move_x: 0.8, zoom: -1.0, ..CameraInput::default() };
+        let b = CameraInput { move_x: 0.5, zoom: 0.25, ..CameraInput::default() };
+        let c = a.combine(&b);
+        assert_eq!(c.move_x, 1.0);
+        assert_eq!(c.zoom, -0.75);
+        assert_eq!(normalize_axis(1000), 0.0);
+        assert_eq!(normalize_axis(i16::MIN), -1.0);
+    }
+}
@@ -0,0 +1,268 @@
+// Screenshots and frame sequence recording. Pixels are read back with
+// glReadPixels, so capture before Renderer::end_frame, after the swap the
+// back buffer's contents are undefined.
+#![allow(dead_code)]
+
+extern crate gl;
+extern crate image;
+
+use std::fs;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use self::image::{imageops, RgbaImage};
+use graphics::framebuffer::Framebuffer;
+
+// Frames the recorder can have waiting to be encoded before capturing
+// blocks, so a slow disk slows the game down instead of eating memory
+const MAX_PENDING_FRAMES: usize = 8;
+
+// The window's back buffer, width x height from its drawable size. The
+// window shows it opaque whatever alpha blending left behind, so the image
+// is made opaque too.
+pub fn capture_window(width: u32, height: u32) -> RgbaImage {
+    let mut image = read_pixels(0, gl::BACK, width, height);
+    make_opaque(&mut image);
+    image
+}
+
+// A color attachment of a framebuffer. Multisampled framebuffers have to be
+// resolved into one without samples first.
+pub fn capture_framebuffer(framebuffer: &Framebuffer, index: usize)
+    -> Result<RgbaImage, String> {
+    if framebuffer.samples() > 1 {
+        return Err(String::from(
+            "can't read a multisampled framebuffer, resolve it first"));
+    }
+    if index >= framebuffer.color_count() {
+        return Err(format!(
+            "framebuffer has {} color attachments, can't read attachment {}",
+            framebuffer.color_count(), index));
+    }
+    Ok(read_pixels(
+        framebuffer.id(), gl::COLOR_ATTACHMENT0 + index as u32,
+        framebuffer.width(), framebuffer.height()))
+}
+
+// Saves as dir/screenshot_<UTC time>.png, creating dir if needed. Returns
+// where it was saved.
+pub fn save_screenshot<P: AsRef<Path>>(image: &RgbaImage, dir: P) -> Result<PathBuf, String> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let stamp = timestamp();
+    let mut path = dir.join(format!("screenshot_{}.png", stamp));
+    // Two in the same millisecond get a suffix
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("screenshot_{}_{}.png", stamp, n));
+        n += 1;
+    }
+    image.save(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// capture_window then save_screenshot
+pub fn screenshot<P: AsRef<Path>>(width: u32, height: u32, dir: P) -> Result<PathBuf, String> {
+    save_screenshot(&capture_window(width, height), dir)
+}
+
+// Reads a whole buffer of a framebuffer, 0 being the window, and flips it so
+// the top row comes first like image files expect
+fn read_pixels(framebuffer: u32, buffer: gl::types::GLenum, width: u32, height: u32)
+    -> RgbaImage {
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    let mut previous: gl::types::GLint = 0;
+    unsafe {
+        gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+        gl::ReadBuffer(buffer);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0, 0, width as i32, height as i32,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        // Framebuffers read from their first attachment otherwise
+        gl::ReadBuffer(if framebuffer == 0 { gl::BACK } else { gl::COLOR_ATTACHMENT0 });
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as u32);
+    }
+    // Sized to match above, so from_raw can't fail
+    let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    imageops::flip_vertical(&image)
+}
+
+fn make_opaque(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        pixel[3] = 255;
+    }
+}
+
+// Current UTC time as 2018-06-30_14-05-09_250, sorts in time order
+fn timestamp() -> String {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}_{:03}",
+        year, month, day, time / 3600, time / 60 % 60, time % 60,
+        elapsed.subsec_millis())
+}
+
+// Days since 1970-01-01 to (year, month, day), Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Saves the window as numbered PNGs while recording, for turning into GIFs
+// or videos afterwards (e.g. ffmpeg -i frame_%05d.png). Encoding happens on
+// a worker thread.
+pub struct FrameRecorder {
+    base_dir: PathBuf,
+    // Capture every nth frame
+    frame_step: u32,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    dir: PathBuf,
+    sender: SyncSender<(PathBuf, RgbaImage)>,
+    worker: JoinHandle<Result<(), String>>,
+    // Frames seen and frames saved since start
+    ticks: u32,
+    frames: u32,
+}
+
+impl FrameRecorder {
+    // Each recording gets its own directory under base_dir
+    pub fn new<P: AsRef<Path>>(base_dir: P) -> Self {
+        FrameRecorder {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            frame_step: 1,
+            recording: None,
+        }
+    }
+
+    // 2 records at half the frame rate, and so on
+    pub fn with_frame_step(mut self, frame_step: u32) -> Self {
+        self.frame_step = frame_step.max(1);
+        self
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Directory frames are going into
+    pub fn dir(&self) -> Option<&Path> {
+        self.recording.as_ref().map(|r| r.dir.as_path())
+    }
+
+    // Starts a new recording in base_dir/recording_<UTC time>
+    pub fn start(&mut self) -> Result<PathBuf, String> {
+        if let Some(ref recording) = self.recording {
+            return Ok(recording.dir.clone());
+        }
+        let dir = self.base_dir.join(format!("recording_{}", timestamp()));
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let (sender, receiver) = mpsc::sync_channel::<(PathBuf, RgbaImage)>(MAX_PENDING_FRAMES);
+        let worker = thread::spawn(move || {
+            // Keep going after an error so the channel keeps draining, and
+            // report the first one from stop
+            let mut result = Ok(());
+            for (path, image) in receiver {
+                if let Err(e) = image.save(&path) {
+                    if result.is_ok() {
+                        result = Err(format!("{}: {}", path.display(), e));
+                    }
+                }
+            }
+            result
+        });
+        self.recording = Some(Recording { dir: dir.clone(), sender, worker, ticks: 0, frames: 0 });
+        Ok(dir)
+    }
+
+    // Waits for queued frames to be written. Returns how many were saved,
+    // or the first error writing them.
+    pub fn stop(&mut self) -> Result<u32, String> {
+        let recording = match self.recording.take() {
+            Some(recording) => recording,
+            None => { return Ok(0); }
+        };
+        let Recording { sender, worker, frames, .. } = recording;
+        // Closing the channel ends the worker's loop
+        drop(sender);
+        match worker.join() {
+            Ok(result) => result.map(|_| frames),
+            Err(_) => Err(String::from("frame recorder worker panicked")),
+        }
+    }
+
+    // Starts or stops, returns whether it's now recording
+    pub fn toggle(&mut self) -> Result<bool, String> {
+        if self.is_recording() {
+            self.stop().map(|_| false)
+        } else {
+            self.start().map(|_| true)
+        }
+    }
+
+    // Call once per frame before Renderer::end_frame, with the window's
+    // drawable size. Does nothing unless recording.
+    pub fn capture_frame(&mut self, width: u32, height: u32) {
+        let frame_step = self.frame_step;
+        let recording = match self.recording {
+            Some(ref mut recording) => recording,
+            None => { return; }
+        };
+        recording.ticks += 1;
+        if (recording.ticks - 1) % frame_step != 0 {
+            return;
+        }
+        let path = recording.dir.join(format!("frame_{:05}.png", recording.frames));
+        let image = capture_window(width, height);
+        if recording.sender.send((path, image)).is_err() {
+            println!(
+                "Warning: frame recorder stopped unexpectedly ({}:{}:{})",
+                file!(), line!(), column!());
+            return;
+        }
+        recording.frames += 1;
+    }
+}
+
+impl Drop for FrameRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::image::Rgba;
+
+    #[test]
+    fn window_captures_are_opaque() {
+        let mut image = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 0]));
+        image.put_pixel(1, 1, Rgba([40, 50, 60, 128]));
+        make_opaque(&mut image);
+        assert_eq!(image.get_pixel(0, 0), &Rgba([10, 20, 30, 255]));
+        assert_eq!(image.get_pixel(1, 1), &Rgba([40, 50, 60, 255]));
+    }
+}
@@ -0,0 +1,298 @@
+// RGBA color with float channels, usually 0.0 to 1.0. Colors are sRGB unless
+// converted with to_linear, which is what lighting and blending math wants.
+#![allow(dead_code)]
+
+extern crate gl;
+
+use std::ops::{Add, Mul, Sub};
+use graphics::GLDataType;
+use graphics::uniform::UniformValue;
+use graphics::vertex::AttributeType;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+    pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    pub const GRAY: Color = Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 };
+    pub const RED: Color = Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const GREEN: Color = Color { r: 0.0, g: 1.0, b: 0.0, a: 1.0 };
+    pub const BLUE: Color = Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
+    pub const YELLOW: Color = Color { r: 1.0, g: 1.0, b: 0.0, a: 1.0 };
+    pub const CYAN: Color = Color { r: 0.0, g: 1.0, b: 1.0, a: 1.0 };
+    pub const MAGENTA: Color = Color { r: 1.0, g: 0.0, b: 1.0, a: 1.0 };
+    pub const ORANGE: Color = Color { r: 1.0, g: 0.5, b: 0.0, a: 1.0 };
+    pub const PURPLE: Color = Color { r: 0.5, g: 0.0, b: 1.0, a: 1.0 };
+
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+
+    // Opaque
+    pub fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    pub fn from_u8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color::new(
+            r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0)
+    }
+
+    // 0xRRGGBBAA
+    pub fn from_rgba_u32(rgba: u32) -> Self {
+        Color::from_u8((rgba >> 24) as u8, (rgba >> 16) as u8, (rgba >> 8) as u8, rgba as u8)
+    }
+
+    // "#rgb", "#rgba", "#rrggbb" or "#rrggbbaa", the # is optional
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let digits = hex.trim_start_matches('#');
+        let invalid = || format!("invalid hex color \"{}\"", hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let value = |s: &str| u8::from_str_radix(s, 16).map_err(|_| invalid());
+        // Short forms repeat each digit, so "f80" is "ff8800"
+        let short = |i: usize| value(&digits[i..i + 1]).map(|v| v * 17);
+        match digits.len() {
+            3 => Ok(Color::from_u8(short(0)?, short(1)?, short(2)?, 255)),
+            4 => Ok(Color::from_u8(short(0)?, short(1)?, short(2)?, short(3)?)),
+            6 => Ok(Color::from_u8(
+                value(&digits[0..2])?, value(&digits[2..4])?, value(&digits[4..6])?, 255)),
+            8 => Ok(Color::from_u8(
+                value(&digits[0..2])?, value(&digits[2..4])?, value(&digits[4..6])?,
+                value(&digits[6..8])?)),
+            _ => Err(invalid()),
+        }
+    }
+
+    // Hue in degrees, wraps around. Saturation and value from 0.0 to 1.0.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, a: f32) -> Self {
+        let hue = ((hue % 360.0) + 360.0) % 360.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+        let (r, g, b) = match (hue / 60.0) as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        Color::new(r + m, g + m, b + m, a)
+    }
+
+    // Hue in degrees from 0.0 to 360.0, saturation and value from 0.0 to 1.0.
+    // Alpha is dropped.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+        let hue = if delta <= 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / delta)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / delta + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / delta + 4.0)
+        };
+        let saturation = if max <= 0.0 { 0.0 } else { delta / max };
+        ((hue + 360.0) % 360.0, saturation, max)
+    }
+
+    // Channels clamped to 0.0..1.0 and rounded
+    pub fn to_u8(&self) -> [u8; 4] {
+        let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [byte(self.r), byte(self.g), byte(self.b), byte(self.a)]
+    }
+
+    // "#rrggbbaa"
+    pub fn to_hex(&self) -> String {
+        let [r, g, b, a] = self.to_u8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn with_alpha(mut self, a: f32) -> Self {
+        self.a = a;
+        self
+    }
+
+    // sRGB to linear, alpha is already linear
+    pub fn to_linear(&self) -> Self {
+        let convert = |c: f32| if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+        Color::new(convert(self.r), convert(self.g), convert(self.b), self.a)
+    }
+
+    // Linear to sRGB, alpha is left as is
+    pub fn to_srgb(&self) -> Self {
+        let convert = |c: f32| if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        Color::new(convert(self.r), convert(self.g), convert(self.b), self.a)
+    }
+
+    // For BlendMode::PremultipliedAlpha
+    pub fn premultiplied(&self) -> Self {
+        Color::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    // t of 0.0 gives self, 1.0 gives other. Blends the channels as they
+    // are, convert to linear first for physically correct fades.
+    pub fn lerp(&self, other: &Color, t: f32) -> Self {
+        *self + (*other - *self) * t
+    }
+
+    // Every channel clamped to 0.0..1.0
+    pub fn clamped(&self) -> Self {
+        let clamp = |v: f32| v.clamp(0.0, 1.0);
+        Color::new(clamp(self.r), clamp(self.g), clamp(self.b), clamp(self.a))
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color::WHITE
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from(c: [f32; 4]) -> Self {
+        Color::new(c[0], c[1], c[2], c[3])
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(c: Color) -> Self {
+        c.to_array()
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        Color::new(self.r + other.r, self.g + other.g, self.b + other.b, self.a + other.a)
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, other: Color) -> Color {
+        Color::new(self.r - other.r, self.g - other.g, self.b - other.b, self.a - other.a)
+    }
+}
+
+// Channel by channel, like tinting in a shader
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::new(self.r * other.r, self.g * other.g, self.b * other.b, self.a * other.a)
+    }
+}
+
+impl Mul<f32> for Color {
+    type Output = Color;
+
+    fn mul(self, s: f32) -> Color {
+        Color::new(self.r * s, self.g * s, self.b * s, self.a * s)
+    }
+}
+
+// vec4 in shaders, as a vertex attribute or a uniform
+impl AttributeType for Color {
+    const COMPONENTS: i32 = 4;
+    const DATA_TYPE: GLDataType = GLDataType::Float;
+}
+
+impl UniformValue for Color {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform4f(location, self.r, self.g, self.b, self.a);
+    }
+}
+
+impl UniformValue for [Color] {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform4fv(location, self.len() as i32, self.as_ptr() as *const f32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Color, b: Color) {
+        let (a, b) = (a.to_array(), b.to_array());
+        for i in 0..4 {
+            assert!((a[i] - b[i]).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn from_hex_forms() {
+        assert_eq!(Color::from_hex("#ff8800").unwrap().to_u8(), [255, 136, 0, 255]);
+        assert_eq!(Color::from_hex("ff880080").unwrap().to_u8(), [255, 136, 0, 128]);
+        // Short forms repeat each digit
+        assert_eq!(Color::from_hex("#f80").unwrap().to_u8(), [255, 136, 0, 255]);
+        assert_eq!(Color::from_hex("F808").unwrap().to_u8(), [255, 136, 0, 136]);
+        assert_eq!(Color::from_hex("#12345678").unwrap().to_hex(), "#12345678");
+    }
+
+    #[test]
+    fn from_hex_invalid() {
+        for hex in ["", "#", "#ff888", "#ff8800f", "#gg8800", "+f8", "#ff 800", "#ff88€"].iter() {
+            assert!(Color::from_hex(hex).is_err(), "{} parsed", hex);
+        }
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for &(h, s, v) in [(0.0, 1.0, 1.0), (30.0, 0.5, 0.8), (120.0, 1.0, 0.5),
+                           (200.0, 0.25, 1.0), (300.0, 0.75, 0.3), (359.0, 1.0, 1.0)].iter() {
+            let color = Color::from_hsv(h, s, v, 1.0);
+            let (h2, s2, v2) = color.to_hsv();
+            assert!((h - h2).abs() < 1e-3 && (s - s2).abs() < 1e-4 && (v - v2).abs() < 1e-4,
+                    "({}, {}, {}) came back as ({}, {}, {})", h, s, v, h2, s2, v2);
+            assert_close(Color::from_hsv(h2, s2, v2, 1.0), color);
+        }
+        // Hue wraps around
+        assert_close(Color::from_hsv(-120.0, 1.0, 1.0, 1.0), Color::BLUE);
+        assert_close(Color::from_hsv(480.0, 1.0, 1.0, 1.0), Color::GREEN);
+        // Grays have no hue or saturation
+        assert_eq!(Color::GRAY.to_hsv(), (0.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn srgb_linear_round_trip() {
+        for i in 0..=20 {
+            let v = i as f32 / 20.0;
+            let color = Color::new(v, 1.0 - v, v * 0.5, 0.5);
+            assert_close(color.to_linear().to_srgb(), color);
+            assert_close(color.to_srgb().to_linear(), color);
+        }
+        // Middle gray is about 0.214 linear, alpha is untouched
+        let linear = Color::new(0.5, 0.5, 0.5, 0.5).to_linear();
+        assert!((linear.r - 0.2140).abs() < 1e-3);
+        assert_eq!(linear.a, 0.5);
+    }
+}
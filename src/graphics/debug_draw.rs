@@ -0,0 +1,283 @@
+// Immediate mode shapes for debugging. The draw_* functions can be called
+// from anywhere on the GL thread (usually Game::update), they only queue
+// triangles. DebugRenderer::render then draws everything queued this frame
+// in one draw call on top of the scene and empties the queue.
+//
+// Positions and thickness are in world units, on the z = 0 plane.
+#![allow(dead_code)]
+
+extern crate gl;
+extern crate nalgebra as na;
+
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use self::na::{Matrix4, Vector2, Vector4};
+use graphics::{buffer, program, vertex_array, Color};
+use graphics::camera::Camera;
+use graphics::renderer::Renderer;
+use graphics::vertex::Vertex;
+use physics::{BodyKind, World};
+use physics::collider::Shape;
+
+// Segments used for a full circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrawStyle {
+    Filled,
+    // Outline thickness
+    Outline(f32),
+}
+
+// Locations match debug.vert
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct DebugVertex {
+    pos: [f32; 2],
+    color: Color,
+}
+
+struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+    show_colliders: bool,
+    show_camera_bounds: bool,
+}
+
+thread_local! {
+    // Like GL objects the queue belongs to the thread with the context
+    static DEBUG_DRAW: RefCell<DebugDraw> = const { RefCell::new(DebugDraw {
+        vertices: Vec::new(),
+        show_colliders: false,
+        show_camera_bounds: false,
+    }) };
+}
+
+// Whether draw_colliders draws anything
+pub fn set_show_colliders(show: bool) {
+    DEBUG_DRAW.with(|d| d.borrow_mut().show_colliders = show);
+}
+
+pub fn show_colliders() -> bool {
+    DEBUG_DRAW.with(|d| d.borrow().show_colliders)
+}
+
+// Whether draw_camera_bounds draws anything
+pub fn set_show_camera_bounds(show: bool) {
+    DEBUG_DRAW.with(|d| d.borrow_mut().show_camera_bounds = show);
+}
+
+pub fn show_camera_bounds() -> bool {
+    DEBUG_DRAW.with(|d| d.borrow().show_camera_bounds)
+}
+
+pub fn draw_line(from: Vector2<f32>, to: Vector2<f32>, color: Color, thickness: f32) {
+    DEBUG_DRAW.with(|d| push_line(&mut d.borrow_mut().vertices, from, to, color, thickness));
+}
+
+pub fn draw_rect(min: Vector2<f32>, max: Vector2<f32>, color: Color, style: DrawStyle) {
+    draw_polygon(&[
+        min,
+        Vector2::new(max.x, min.y),
+        max,
+        Vector2::new(min.x, max.y),
+    ], color, style);
+}
+
+pub fn draw_circle(center: Vector2<f32>, radius: f32, color: Color, style: DrawStyle) {
+    let points: Vec<Vector2<f32>> = (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let (sin, cos) = (i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI).sin_cos();
+            center + Vector2::new(cos, sin) * radius
+        })
+        .collect();
+    draw_polygon(&points, color, style);
+}
+
+// Filled polygons must be convex, outlines can be any shape
+pub fn draw_polygon(points: &[Vector2<f32>], color: Color, style: DrawStyle) {
+    if points.len() < 2 {
+        return;
+    }
+    DEBUG_DRAW.with(|d| {
+        let vertices = &mut d.borrow_mut().vertices;
+        match style {
+            DrawStyle::Filled => {
+                for i in 1..points.len() - 1 {
+                    for p in [points[0], points[i], points[i + 1]].iter() {
+                        vertices.push(DebugVertex { pos: [p.x, p.y], color });
+                    }
+                }
+            }
+            DrawStyle::Outline(thickness) => {
+                for i in 0..points.len() {
+                    let next = points[(i + 1) % points.len()];
+                    push_line(vertices, points[i], next, color, thickness);
+                }
+            }
+        }
+    });
+}
+
+// Outlines every collider in the world if show_colliders is on. Static
+// bodies are gray, kinematic blue, dynamic green and triggers yellow.
+pub fn draw_colliders(world: &World, thickness: f32) {
+    if !show_colliders() {
+        return;
+    }
+    for body in world.bodies().iter() {
+        let color = if body.is_trigger {
+            Color::YELLOW
+        } else {
+            match body.kind {
+                BodyKind::Static => Color::GRAY,
+                BodyKind::Kinematic => Color::BLUE,
+                BodyKind::Dynamic => Color::GREEN,
+            }
+        };
+        let style = DrawStyle::Outline(thickness);
+        match body.collider.shape {
+            Shape::Circle { radius } => {
+                let center = body.collider.world_center(&body.position, body.rotation);
+                draw_circle(center, radius, color, style);
+                // Spoke so rotation is visible
+                let spoke = Vector2::new(body.rotation.cos(), body.rotation.sin()) * radius;
+                draw_line(center, center + spoke, color, thickness);
+            }
+            _ => {
+                let vertices = body.collider.world_vertices(&body.position, body.rotation);
+                draw_polygon(&vertices, color, style);
+            }
+        }
+    }
+}
+
+// Outlines what camera sees of the z = 0 plane if show_camera_bounds is on.
+// Meant for checking a gameplay camera while viewing through another one.
+pub fn draw_camera_bounds(camera: &Camera, color: Color, thickness: f32) {
+    if !show_camera_bounds() {
+        return;
+    }
+    if let Some(corners) = visible_area(&camera.view_projection()) {
+        draw_polygon(&corners, color, DrawStyle::Outline(thickness));
+    }
+}
+
+// Throws away everything queued, for frames that skip DebugRenderer::render
+pub fn clear() {
+    DEBUG_DRAW.with(|d| d.borrow_mut().vertices.clear());
+}
+
+// Triangles queued since the last render or clear
+pub fn len() -> usize {
+    DEBUG_DRAW.with(|d| d.borrow().vertices.len() / 3)
+}
+
+// A quad along the segment, extended by half the thickness at both ends so
+// outline corners meet
+fn push_line(
+    vertices: &mut Vec<DebugVertex>,
+    from: Vector2<f32>, to: Vector2<f32>,
+    color: Color, thickness: f32) {
+    let delta = to - from;
+    let length = delta.norm();
+    if length <= f32::EPSILON || thickness <= 0.0 {
+        return;
+    }
+    let along = delta / length * (thickness * 0.5);
+    let side = Vector2::new(-along.y, along.x);
+    let corners = [
+        from - along - side,
+        to + along - side,
+        to + along + side,
+        from - along + side,
+    ];
+    for &i in [0, 1, 2, 2, 3, 0].iter() {
+        let p = corners[i];
+        vertices.push(DebugVertex { pos: [p.x, p.y], color });
+    }
+}
+
+// Where the corners of the screen land on the z = 0 plane, counter-clockwise
+// from bottom left. None if the camera doesn't see the plane at every corner.
+fn visible_area(view_projection: &Matrix4<f32>) -> Option<Vec<Vector2<f32>>> {
+    let inverse = view_projection.try_inverse()?;
+    let unproject = |x: f32, y: f32, z: f32| {
+        let p = inverse * Vector4::new(x, y, z, 1.0);
+        p.xyz() / p.w
+    };
+    let mut corners = Vec::with_capacity(4);
+    for &(x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+        let near = unproject(x, y, -1.0);
+        let far = unproject(x, y, 1.0);
+        let dz = far.z - near.z;
+        if dz.abs() <= f32::EPSILON {
+            return None;
+        }
+        let t = -near.z / dz;
+        if t < 0.0 {
+            return None;
+        }
+        let hit = near + (far - near) * t;
+        corners.push(Vector2::new(hit.x, hit.y));
+    }
+    Some(corners)
+}
+
+// Owns the GL side of debug drawing
+pub struct DebugRenderer {
+    vbo: buffer::TypedBuffer<DebugVertex>,
+    vao: vertex_array::VertexArrayObject,
+    program: program::Program,
+    // Reused between frames so the queue keeps its capacity
+    vertices: Vec<DebugVertex>,
+}
+
+impl DebugRenderer {
+    pub fn new() -> Result<Self, String> {
+        let vbo = buffer::TypedBuffer::<DebugVertex>::new(
+            buffer::BufferTarget::ArrayBuffer,
+            buffer::BufferUsage::StreamDraw
+        );
+        let program = program::Program::debug()?;
+        let vao = vertex_array::VertexArrayObject::with_layout::<DebugVertex>(vbo.buffer());
+        vao.validate(&program)?;
+
+        Ok(DebugRenderer { vbo, vao, program, vertices: Vec::new() })
+    }
+
+    // Draws everything queued since the last render over whatever is on
+    // screen, then empties the queue. Depth testing is off for the draw and
+    // put back after.
+    pub fn render(&mut self, renderer: &mut Renderer, view_projection: &Matrix4<f32>) {
+        DEBUG_DRAW.with(|d| {
+            ::std::mem::swap(&mut self.vertices, &mut d.borrow_mut().vertices);
+        });
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        // Orphaning, same as SpriteBatch, so the draw doesn't wait on the
+        // last frame's
+        self.vbo.stream_data(&self.vertices);
+
+        self.program.set_used(true);
+        if let Err(e) = self.program.set_uniform("projection", view_projection) {
+            println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!());
+        }
+        let depth_test = renderer.depth_test();
+        renderer.set_depth_test(false);
+        self.vao.bind();
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, self.vertices.len() as i32);
+        }
+        self.vao.unbind();
+        renderer.set_depth_test(depth_test);
+        self.program.set_used(false);
+
+        // Hand the emptied buffer back to the queue
+        self.vertices.clear();
+        DEBUG_DRAW.with(|d| {
+            ::std::mem::swap(&mut self.vertices, &mut d.borrow_mut().vertices);
+        });
+    }
+}
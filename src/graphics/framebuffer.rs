@@ -0,0 +1,453 @@
+// Offscreen render targets. Color attachments stored as textures can be
+// handed to a Material like any other Texture once rendered to.
+//
+// Draw into one with Renderer::set_render_target, which also sets the
+// viewport to the framebuffer's size.
+#![allow(dead_code)]
+
+extern crate gl;
+
+use graphics::textures::{Filter, Texture, TextureFormat, TextureOptions, Wrap};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Storage {
+    // Can be sampled afterwards, e.g. through a Material
+    Texture,
+    // Can only be drawn to, read back or blitted, but the driver is free to
+    // store it however is fastest. Multisampled attachments must be these.
+    Renderbuffer,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct AttachmentOptions {
+    texture: TextureOptions,
+    storage: Storage,
+}
+
+// What a framebuffer is made of, kept so resize can rebuild it
+#[derive(Clone, PartialEq, Debug)]
+pub struct FramebufferOptions {
+    colors: Vec<AttachmentOptions>,
+    depth: Option<AttachmentOptions>,
+    samples: u32,
+}
+
+impl FramebufferOptions {
+    // No attachments, add them with the with_ methods
+    pub fn new() -> Self {
+        FramebufferOptions { colors: Vec::new(), depth: None, samples: 1 }
+    }
+
+    // One Rgba8 color texture clamped to the edge, plus a depth and stencil
+    // renderbuffer. What most render-to-texture and post-processing wants.
+    pub fn standard() -> Self {
+        FramebufferOptions::new()
+            .with_color(target_options(TextureFormat::Rgba8))
+            .with_depth(TextureFormat::Depth24Stencil8, Storage::Renderbuffer)
+    }
+
+    // Adds a color texture. Fragment shader output n goes to the nth color
+    // attachment added. Mipmaps aren't regenerated after drawing.
+    pub fn with_color(mut self, options: TextureOptions) -> Self {
+        self.colors.push(AttachmentOptions { texture: options, storage: Storage::Texture });
+        self
+    }
+
+    pub fn with_color_renderbuffer(mut self, format: TextureFormat) -> Self {
+        self.colors.push(AttachmentOptions {
+            texture: target_options(format),
+            storage: Storage::Renderbuffer,
+        });
+        self
+    }
+
+    // Depth24Stencil8 gives a stencil buffer too
+    pub fn with_depth(mut self, format: TextureFormat, storage: Storage) -> Self {
+        self.depth = Some(AttachmentOptions { texture: target_options(format), storage });
+        self
+    }
+
+    // MSAA sample count, 1 turns it off. Resolve into a framebuffer without
+    // samples to read the result as a texture.
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut max_attachments: gl::types::GLint = 0;
+        let mut max_samples: gl::types::GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_attachments);
+            gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+        }
+        if self.colors.len() > max_attachments as usize {
+            return Err(format!(
+                "{} color attachments requested, the driver supports {}",
+                self.colors.len(), max_attachments));
+        }
+        if self.samples > max_samples.max(1) as u32 {
+            return Err(format!(
+                "{} samples requested, the driver supports {}", self.samples, max_samples));
+        }
+        for color in self.colors.iter() {
+            if color.texture.format().is_depth() {
+                return Err(format!(
+                    "{:?} can't be a color attachment", color.texture.format()));
+            }
+        }
+        if let Some(depth) = self.depth {
+            if !depth.texture.format().is_depth() {
+                return Err(format!(
+                    "{:?} can't be a depth attachment", depth.texture.format()));
+            }
+        }
+        let mut all = self.colors.iter().chain(self.depth.iter());
+        if self.samples > 1 && all.any(|a| a.storage == Storage::Texture) {
+            return Err(String::from(
+                "multisampled attachments must be renderbuffers, resolve into a \
+                 framebuffer with textures to sample them"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for FramebufferOptions {
+    fn default() -> Self {
+        FramebufferOptions::standard()
+    }
+}
+
+// Clamped to the edge so filtering near the borders doesn't pull in pixels
+// from the other side of the screen
+fn target_options(format: TextureFormat) -> TextureOptions {
+    TextureOptions::new()
+        .with_format(format)
+        .with_filter(Filter::Linear)
+        .with_wrap(Wrap::ClampToEdge)
+}
+
+struct Renderbuffer {
+    id: u32,
+}
+
+impl Renderbuffer {
+    fn new(internal_format: u32, width: u32, height: u32, samples: u32) -> Self {
+        let mut id: u32 = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut id);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+            if samples > 1 {
+                gl::RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER, samples as i32, internal_format,
+                    width as i32, height as i32);
+            } else {
+                gl::RenderbufferStorage(
+                    gl::RENDERBUFFER, internal_format, width as i32, height as i32);
+            }
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+        Renderbuffer { id }
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.id);
+        }
+    }
+}
+
+enum Attachment {
+    Texture(Texture),
+    Renderbuffer(Renderbuffer),
+}
+
+impl Attachment {
+    fn create(options: &AttachmentOptions, width: u32, height: u32, samples: u32) -> Self {
+        match options.storage {
+            Storage::Texture =>
+                Attachment::Texture(Texture::empty(width, height, &options.texture)),
+            Storage::Renderbuffer => Attachment::Renderbuffer(Renderbuffer::new(
+                options.texture.gl_internal_format(), width, height, samples)),
+        }
+    }
+
+    // Attaches to the framebuffer bound to GL_FRAMEBUFFER
+    unsafe fn attach(&self, point: gl::types::GLenum) {
+        match *self {
+            Attachment::Texture(ref texture) => {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER, point, gl::TEXTURE_2D, texture.id(), 0);
+            }
+            Attachment::Renderbuffer(ref renderbuffer) => {
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER, point, gl::RENDERBUFFER, renderbuffer.id);
+            }
+        }
+    }
+
+    fn texture(&self) -> Option<&Texture> {
+        match *self {
+            Attachment::Texture(ref texture) => Some(texture),
+            Attachment::Renderbuffer(_) => None,
+        }
+    }
+}
+
+pub struct Framebuffer {
+    id: u32,
+    width: u32,
+    height: u32,
+    options: FramebufferOptions,
+    colors: Vec<Attachment>,
+    depth: Option<Attachment>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32, options: &FramebufferOptions)
+        -> Result<Framebuffer, String> {
+        if width == 0 || height == 0 {
+            return Err(format!("framebuffer can't be {}x{}", width, height));
+        }
+        options.validate()?;
+
+        let samples = options.samples;
+        let colors: Vec<Attachment> = options.colors.iter()
+            .map(|c| Attachment::create(c, width, height, samples))
+            .collect();
+        let depth = options.depth.map(|d| Attachment::create(&d, width, height, samples));
+
+        let mut id: u32 = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut id);
+        }
+        // Built before the completeness check so Drop cleans up on failure
+        let framebuffer = Framebuffer {
+            id,
+            width,
+            height,
+            options: options.clone(),
+            colors,
+            depth,
+        };
+
+        let previous = bound_framebuffers();
+        let status = unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            for (i, color) in framebuffer.colors.iter().enumerate() {
+                color.attach(gl::COLOR_ATTACHMENT0 + i as u32);
+            }
+            if let Some(ref depth) = framebuffer.depth {
+                let point = if options.depth.is_some_and(|d| d.texture.format().has_stencil()) {
+                    gl::DEPTH_STENCIL_ATTACHMENT
+                } else {
+                    gl::DEPTH_ATTACHMENT
+                };
+                depth.attach(point);
+            }
+            framebuffer.set_draw_buffers();
+            gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+        };
+        restore_framebuffers(previous);
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "framebuffer {}x{} is incomplete: {}", width, height, status_name(status)));
+        }
+        Ok(framebuffer)
+    }
+
+    // Framebuffer::new with FramebufferOptions::standard
+    pub fn standard(width: u32, height: u32) -> Result<Framebuffer, String> {
+        Framebuffer::new(width, height, &FramebufferOptions::standard())
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.options.samples
+    }
+
+    pub fn options(&self) -> &FramebufferOptions {
+        &self.options
+    }
+
+    pub fn color_count(&self) -> usize {
+        self.colors.len()
+    }
+
+    // The nth color attachment, None if it's a renderbuffer or missing
+    pub fn texture(&self, index: usize) -> Option<&Texture> {
+        self.colors.get(index).and_then(|c| c.texture())
+    }
+
+    pub fn depth_texture(&self) -> Option<&Texture> {
+        self.depth.as_ref().and_then(|d| d.texture())
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        self.options.depth.is_some_and(|d| d.texture.format().has_stencil())
+    }
+
+    // Binds for drawing and reading. Prefer Renderer::set_render_target,
+    // which keeps the viewport in step.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    // Binds the window back
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // Recreates every attachment at the new size, their old contents are
+    // lost. The GL ids change, so bind it again if it was bound.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+        *self = Framebuffer::new(width, height, &self.options)?;
+        Ok(())
+    }
+
+    // Copies each color attachment into the one with the same index in
+    // target, and depth and stencil if both have them. This is how a
+    // multisampled framebuffer is resolved, in which case both must be the
+    // same size.
+    pub fn resolve_into(&self, target: &Framebuffer) -> Result<(), String> {
+        if self.samples() > 1 && (self.width, self.height) != (target.width, target.height) {
+            return Err(format!(
+                "can't resolve a {}x{} framebuffer into a {}x{} one",
+                self.width, self.height, target.width, target.height));
+        }
+        let previous = bound_framebuffers();
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.id);
+            for i in 0..self.colors.len().min(target.colors.len()) {
+                let point = gl::COLOR_ATTACHMENT0 + i as u32;
+                gl::ReadBuffer(point);
+                gl::DrawBuffers(1, &point);
+                self.blit(target.width, target.height, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+            }
+            let mut mask = 0;
+            if self.depth.is_some() && target.depth.is_some() {
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+            if self.has_stencil() && target.has_stencil() {
+                mask |= gl::STENCIL_BUFFER_BIT;
+            }
+            if mask != 0 {
+                self.blit(target.width, target.height, mask, gl::NEAREST);
+            }
+            // Put the draw buffers back the way new set them up
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.id);
+            target.set_draw_buffers();
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            self.set_draw_buffers();
+        }
+        restore_framebuffers(previous);
+        Ok(())
+    }
+
+    // Stretches the first color attachment over the window, which is
+    // width x height pixels. Resolves it first if multisampled, which
+    // then needs the sizes to match.
+    pub fn blit_to_window(&self, width: u32, height: u32) {
+        if self.colors.is_empty() {
+            return;
+        }
+        let previous = bound_framebuffers();
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            let filter = if self.samples() > 1 { gl::NEAREST } else { gl::LINEAR };
+            self.blit(width, height, gl::COLOR_BUFFER_BIT, filter);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            self.set_draw_buffers();
+        }
+        restore_framebuffers(previous);
+    }
+
+    // Blits all of the read framebuffer onto all of the draw framebuffer
+    unsafe fn blit(&self, width: u32, height: u32,
+                   mask: gl::types::GLbitfield, filter: gl::types::GLenum) {
+        gl::BlitFramebuffer(
+            0, 0, self.width as i32, self.height as i32,
+            0, 0, width as i32, height as i32,
+            mask, filter);
+    }
+
+    // Routes fragment outputs to every color attachment in order. Needs
+    // this framebuffer bound to GL_FRAMEBUFFER.
+    unsafe fn set_draw_buffers(&self) {
+        if self.colors.is_empty() {
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            return;
+        }
+        let points: Vec<gl::types::GLenum> = (0..self.colors.len())
+            .map(|i| gl::COLOR_ATTACHMENT0 + i as u32)
+            .collect();
+        gl::DrawBuffers(points.len() as i32, points.as_ptr());
+        gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+// (draw, read) framebuffers bound right now, so helpers can leave the
+// Renderer's render target alone
+fn bound_framebuffers() -> (u32, u32) {
+    let mut draw: gl::types::GLint = 0;
+    let mut read: gl::types::GLint = 0;
+    unsafe {
+        gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut draw);
+        gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read);
+    }
+    (draw as u32, read as u32)
+}
+
+fn restore_framebuffers((draw, read): (u32, u32)) {
+    unsafe {
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, draw);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, read);
+    }
+}
+
+fn status_name(status: gl::types::GLenum) -> String {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => String::from("undefined"),
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => String::from("incomplete attachment"),
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => String::from("missing attachment"),
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => String::from("incomplete draw buffer"),
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => String::from("incomplete read buffer"),
+        gl::FRAMEBUFFER_UNSUPPORTED => String::from("format combination unsupported"),
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => String::from("mismatched sample counts"),
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => String::from("incomplete layer targets"),
+        other => format!("status 0x{:x}", other),
+    }
+}
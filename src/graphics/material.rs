@@ -1,59 +1,147 @@
 #![allow(dead_code)]
 
 use graphics::{program, textures};
+use graphics::reflection::GlslType;
+use graphics::uniform::UniformValue;
 
 enum TextureContainer<'a> {
     OneTexture(&'a textures::Texture),
     NTextures(Vec<&'a textures::Texture>),
 }
 
+// One uniform of the material's program, generated from its reflection.
+// There's no location, hot reloading can relink the program and move it,
+// set values by name through the material or program.
+#[derive(Clone, Debug)]
+pub struct MaterialParam {
+    pub name: String,
+    pub ty: GlslType,
+    pub size: i32,
+    // Unit the sampler reads from, for sampler uniforms only
+    pub texture_unit: Option<u32>,
+}
+
+// Builds the parameter table for a program. Samplers get texture units in
+// location order, which is the order a material's textures are bound in.
+fn param_table(shader_program: &program::Program) -> Vec<MaterialParam> {
+    let info = shader_program.info();
+    let mut params: Vec<MaterialParam> = info.samplers().iter().enumerate()
+        .map(|(unit, u)| MaterialParam {
+            name: u.name.clone(),
+            ty: u.ty,
+            size: u.size,
+            texture_unit: Some(unit as u32),
+        })
+        .collect();
+    for u in info.uniforms.iter() {
+        if u.location >= 0 && !u.ty.is_sampler() {
+            params.push(MaterialParam {
+                name: u.name.clone(),
+                ty: u.ty,
+                size: u.size,
+                texture_unit: None,
+            });
+        }
+    }
+    params
+}
+
+// Textures plus the program that samples them. Textures are plain GPU
+// resources, so the same texture can be shared between materials.
 pub struct Material<'a> {
     tex_container: TextureContainer<'a>,
     shader_program: &'a program::Program,
+    params: Vec<MaterialParam>,
 }
 
 impl <'a> Material <'a> {
-    fn multiple_textures(
+    pub fn multiple_textures(
         textures: Vec<&'a textures::Texture>,
         shader_program: &'a program::Program
         ) -> Self {
+        let params = param_table(shader_program);
+        check_texture_count(&params, textures.len());
         Material { 
             tex_container: TextureContainer::NTextures(textures), 
-            shader_program 
+            shader_program,
+            params,
         }
     }
 
-    fn one_texture(
+    pub fn one_texture(
         texture: &'a textures::Texture,
         shader_program: &'a program::Program
         ) -> Self {
+        let params = param_table(shader_program);
+        check_texture_count(&params, 1);
         Material { 
             tex_container: TextureContainer::OneTexture(texture), 
-            shader_program 
+            shader_program,
+            params,
+        }
+    }
+
+    pub fn params(&self) -> &[MaterialParam] {
+        &self.params
+    }
+
+    pub fn param(&self, name: &str) -> Option<&MaterialParam> {
+        self.params.iter().find(|p| {
+            p.name == name
+                || (p.name.ends_with("[0]") && p.name[..p.name.len() - 3] == *name)
+        })
+    }
+
+    // Sets a non-sampler parameter, material must be in use
+    pub fn set_param<T>(&self, name: &str, value: &T) -> Result<(), String>
+        where T: UniformValue + ?Sized {
+        match self.param(name) {
+            Some(param) if param.texture_unit.is_some() => Err(format!(
+                "{} is a sampler, its unit comes from the material's textures", name)),
+            Some(param) => self.shader_program.set_uniform(&param.name, value),
+            None => Err(format!("material has no parameter {}", name)),
         }
     }
 
-    fn set_used(&self, used: bool) {
+    pub fn set_used(&self, used: bool) {
         self.shader_program.set_used(used);
+        if used {
+            for param in self.params.iter() {
+                if let Some(unit) = param.texture_unit {
+                    if let Err(e) = self.shader_program.set_sampler(&param.name, unit) {
+                        println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!());
+                    }
+                }
+            }
+        }
         match self.tex_container {
             TextureContainer::NTextures(ref textures) => {
                 if used {
-                    for i in 0..textures.len() {
-                        textures[i].bind(i as u32);
+                    for (i, texture) in textures.iter().enumerate() {
+                        texture.bind(i as u32);
                     }
                 } else {
-                    for texture in textures.iter() {
-                        texture.unbind();
-                    } 
+                    for i in 0..textures.len() {
+                        textures::Texture::unbind(i as u32);
+                    }
                 }
             }
-            TextureContainer::OneTexture(ref texture) => {
+            TextureContainer::OneTexture(texture) => {
                 if used {
                     texture.bind(0);
                 } else {
-                    texture.unbind();
+                    textures::Texture::unbind(0);
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn check_texture_count(params: &[MaterialParam], textures: usize) {
+    let samplers = params.iter().filter(|p| p.texture_unit.is_some()).count();
+    if samplers != textures {
+        println!(
+            "Warning: Material has {} textures but its program has {} samplers ({}:{}:{})",
+            textures, samplers, file!(), line!(), column!());
+    }
+}
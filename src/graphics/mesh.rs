@@ -0,0 +1,559 @@
+// Indexed meshes of any vertex type, and generators for common shapes.
+//
+// MeshData is the CPU side (vertices, indices, submeshes), Mesh is the same
+// uploaded to the GPU. A mesh doesn't own a program, use it with whatever
+// program or material matches its vertex layout.
+#![allow(dead_code)]
+
+extern crate gl;
+extern crate nalgebra as na;
+
+use std::f32::consts::PI;
+use std::mem;
+use self::na::Vector3;
+use graphics::buffer::{BufferTarget, BufferUsage, TypedBuffer};
+use graphics::vertex::Vertex;
+use graphics::vertex_array::VertexArrayObject;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Points,
+}
+
+impl Topology {
+    pub fn gl_mode(&self) -> gl::types::GLenum {
+        match *self {
+            Topology::Triangles => gl::TRIANGLES,
+            Topology::TriangleStrip => gl::TRIANGLE_STRIP,
+            Topology::TriangleFan => gl::TRIANGLE_FAN,
+            Topology::Lines => gl::LINES,
+            Topology::LineStrip => gl::LINE_STRIP,
+            Topology::LineLoop => gl::LINE_LOOP,
+            Topology::Points => gl::POINTS,
+        }
+    }
+}
+
+// A range of the index buffer (or of the vertices for meshes without
+// indices) drawn with one topology, e.g. one material's part of a model
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Submesh {
+    pub start: usize,
+    pub count: usize,
+    pub topology: Topology,
+}
+
+// Axis aligned bounding box in model space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Bounds {
+    // None for no points
+    pub fn from_points<I: Iterator<Item = Vector3<f32>>>(mut points: I) -> Option<Self> {
+        let first = points.next()?;
+        let mut bounds = Bounds { min: first, max: first };
+        for p in points {
+            for i in 0..3 {
+                bounds.min[i] = bounds.min[i].min(p[i]);
+                bounds.max[i] = bounds.max[i].max(p[i]);
+            }
+        }
+        Some(bounds)
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    // Radius of the sphere around center that contains the box
+    pub fn radius(&self) -> f32 {
+        self.size().norm() * 0.5
+    }
+}
+
+// Vertices that know where they are, so meshes can compute their bounds
+pub trait MeshVertex: Vertex {
+    fn position(&self) -> Vector3<f32>;
+}
+
+// Vertex used by the generators. Locations match standard.vert, with the
+// normal after them for shaders that want it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Vertex)]
+pub struct StandardVertex {
+    pub position: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+}
+
+impl StandardVertex {
+    pub fn new(position: [f32; 3], tex_coord: [f32; 2], normal: [f32; 3]) -> Self {
+        StandardVertex { position, tex_coord, normal }
+    }
+}
+
+impl MeshVertex for StandardVertex {
+    fn position(&self) -> Vector3<f32> {
+        Vector3::new(self.position[0], self.position[1], self.position[2])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MeshData<V: MeshVertex> {
+    pub vertices: Vec<V>,
+    // Empty to draw the vertices in order
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl<V: MeshVertex> MeshData<V> {
+    // One submesh covering everything
+    pub fn new(vertices: Vec<V>, indices: Vec<u32>, topology: Topology) -> Self {
+        let count = if indices.is_empty() { vertices.len() } else { indices.len() };
+        MeshData {
+            vertices,
+            indices,
+            submeshes: vec![Submesh { start: 0, count, topology }],
+        }
+    }
+
+    // Replaces the default submesh that covers everything
+    pub fn with_submeshes(mut self, submeshes: Vec<Submesh>) -> Self {
+        self.submeshes = submeshes;
+        self
+    }
+
+    pub fn bounds(&self) -> Option<Bounds> {
+        Bounds::from_points(self.vertices.iter().map(|v| v.position()))
+    }
+
+    // Checks indices and submeshes are in range
+    pub fn validate(&self) -> Result<(), String> {
+        let vertex_count = self.vertices.len();
+        if let Some(&bad) = self.indices.iter().find(|&&i| i as usize >= vertex_count) {
+            return Err(format!("index {} out of range for {} vertices", bad, vertex_count));
+        }
+        let limit = if self.indices.is_empty() { vertex_count } else { self.indices.len() };
+        check_submeshes(&self.submeshes, limit)
+    }
+}
+
+// Submeshes must fit in limit indices, or vertices without an index buffer
+fn check_submeshes(submeshes: &[Submesh], limit: usize) -> Result<(), String> {
+    for (i, submesh) in submeshes.iter().enumerate() {
+        if submesh.start + submesh.count > limit {
+            return Err(format!(
+                "submesh {} covers {}..{} but the mesh only has {}",
+                i, submesh.start, submesh.start + submesh.count, limit));
+        }
+    }
+    Ok(())
+}
+
+// Index buffer, u16 when every index fits to save memory and bandwidth
+enum IndexBuffer {
+    U16(TypedBuffer<u16>),
+    U32(TypedBuffer<u32>),
+}
+
+impl IndexBuffer {
+    fn bind(&self) {
+        match *self {
+            IndexBuffer::U16(ref b) => b.bind(),
+            IndexBuffer::U32(ref b) => b.bind(),
+        }
+    }
+
+    fn unbind(&self) {
+        match *self {
+            IndexBuffer::U16(ref b) => b.unbind(),
+            IndexBuffer::U32(ref b) => b.unbind(),
+        }
+    }
+
+    fn gl_type(&self) -> gl::types::GLenum {
+        match *self {
+            IndexBuffer::U16(_) => gl::UNSIGNED_SHORT,
+            IndexBuffer::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+
+    fn index_size(&self) -> usize {
+        match *self {
+            IndexBuffer::U16(_) => mem::size_of::<u16>(),
+            IndexBuffer::U32(_) => mem::size_of::<u32>(),
+        }
+    }
+}
+
+pub struct Mesh<V: MeshVertex> {
+    vbo: TypedBuffer<V>,
+    ebo: Option<IndexBuffer>,
+    vao: VertexArrayObject,
+    submeshes: Vec<Submesh>,
+    bounds: Option<Bounds>,
+    // Largest index, so set_vertices can check the indices still fit
+    max_index: Option<u32>,
+}
+
+impl<V: MeshVertex> Mesh<V> {
+    pub fn new(data: &MeshData<V>) -> Result<Self, String> {
+        Mesh::with_usage(data, BufferUsage::StaticDraw)
+    }
+
+    // DynamicDraw or StreamDraw for meshes updated with set_vertices
+    pub fn with_usage(data: &MeshData<V>, usage: BufferUsage) -> Result<Self, String> {
+        data.validate()?;
+        let vbo = TypedBuffer::from_slice(BufferTarget::ArrayBuffer, usage, &data.vertices);
+        let vao = VertexArrayObject::with_layout::<V>(vbo.buffer());
+
+        let ebo = if data.indices.is_empty() {
+            None
+        } else if data.vertices.len() <= u16::MAX as usize + 1 {
+            let indices: Vec<u16> = data.indices.iter().map(|&i| i as u16).collect();
+            Some(IndexBuffer::U16(TypedBuffer::from_slice(
+                BufferTarget::ElementArrayBuffer, BufferUsage::StaticDraw, &indices)))
+        } else {
+            Some(IndexBuffer::U32(TypedBuffer::from_slice(
+                BufferTarget::ElementArrayBuffer, BufferUsage::StaticDraw, &data.indices)))
+        };
+        // The element buffer binding is part of the VAO, so bind it while the
+        // VAO is bound and unbind it only after
+        if let Some(ref ebo) = ebo {
+            vao.bind();
+            ebo.bind();
+            vao.unbind();
+            ebo.unbind();
+        }
+
+        Ok(Mesh {
+            vbo,
+            ebo,
+            vao,
+            submeshes: data.submeshes.clone(),
+            bounds: data.bounds(),
+            max_index: data.indices.iter().cloned().max(),
+        })
+    }
+
+    pub fn vao(&self) -> &VertexArrayObject {
+        &self.vao
+    }
+
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    // None for a mesh without vertices
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vbo.len()
+    }
+
+    // Replaces the vertices, keeping indices and submeshes. Fails without
+    // changing anything if an index or submesh would be out of range.
+    pub fn set_vertices(&mut self, vertices: &[V]) -> Result<(), String> {
+        match self.max_index {
+            Some(max) if max as usize >= vertices.len() => {
+                return Err(format!(
+                    "index {} out of range for {} vertices", max, vertices.len()));
+            }
+            Some(_) => {}
+            None => check_submeshes(&self.submeshes, vertices.len())?,
+        }
+        self.vbo.set_data(vertices);
+        self.bounds = Bounds::from_points(vertices.iter().map(|v| v.position()));
+        Ok(())
+    }
+
+    // Draws every submesh with the program currently in use
+    pub fn draw(&self) {
+        self.vao.bind();
+        for submesh in self.submeshes.iter() {
+            self.draw_range(submesh, 1);
+        }
+        self.vao.unbind();
+    }
+
+    pub fn draw_submesh(&self, index: usize) {
+        match self.submeshes.get(index) {
+            Some(submesh) => {
+                self.vao.bind();
+                self.draw_range(submesh, 1);
+                self.vao.unbind();
+            }
+            None => {
+                println!(
+                    "Warning: mesh has no submesh {} ({}:{}:{})",
+                    index, file!(), line!(), column!());
+            }
+        }
+    }
+
+    // Draws every submesh instance_count times, for per-instance attributes
+    // added to vao() with a divisor
+    pub fn draw_instanced(&self, instance_count: usize) {
+        self.vao.bind();
+        for submesh in self.submeshes.iter() {
+            self.draw_range(submesh, instance_count);
+        }
+        self.vao.unbind();
+    }
+
+    fn draw_range(&self, submesh: &Submesh, instance_count: usize) {
+        let mode = submesh.topology.gl_mode();
+        unsafe {
+            match self.ebo {
+                Some(ref ebo) => {
+                    let offset = (submesh.start * ebo.index_size()) as *const _;
+                    if instance_count == 1 {
+                        gl::DrawElements(mode, submesh.count as i32, ebo.gl_type(), offset);
+                    } else {
+                        gl::DrawElementsInstanced(
+                            mode, submesh.count as i32, ebo.gl_type(), offset,
+                            instance_count as i32);
+                    }
+                }
+                None => {
+                    if instance_count == 1 {
+                        gl::DrawArrays(mode, submesh.start as i32, submesh.count as i32);
+                    } else {
+                        gl::DrawArraysInstanced(
+                            mode, submesh.start as i32, submesh.count as i32,
+                            instance_count as i32);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Generators. Everything is centered on the origin with counter-clockwise
+// front faces and texture coordinates from 0 to 1.
+
+// In the XY plane facing +Z
+pub fn rectangle(width: f32, height: f32) -> MeshData<StandardVertex> {
+    let (w, h) = (width * 0.5, height * 0.5);
+    let n = [0.0, 0.0, 1.0];
+    let vertices = vec![
+        StandardVertex::new([-w, -h, 0.0], [0.0, 0.0], n),
+        StandardVertex::new([w, -h, 0.0], [1.0, 0.0], n),
+        StandardVertex::new([w, h, 0.0], [1.0, 1.0], n),
+        StandardVertex::new([-w, h, 0.0], [0.0, 1.0], n),
+    ];
+    MeshData::new(vertices, vec![0, 1, 2, 2, 3, 0], Topology::Triangles)
+}
+
+// In the XY plane facing +Z, segments is clamped to at least 3
+pub fn circle(radius: f32, segments: u32) -> MeshData<StandardVertex> {
+    let segments = segments.max(3);
+    let n = [0.0, 0.0, 1.0];
+    let mut vertices = vec![StandardVertex::new([0.0, 0.0, 0.0], [0.5, 0.5], n)];
+    for i in 0..segments {
+        let angle = i as f32 / segments as f32 * 2.0 * PI;
+        let (sin, cos) = angle.sin_cos();
+        vertices.push(StandardVertex::new(
+            [cos * radius, sin * radius, 0.0], [0.5 + cos * 0.5, 0.5 + sin * 0.5], n));
+    }
+    let mut indices = Vec::with_capacity(segments as usize * 3);
+    for i in 0..segments {
+        indices.extend_from_slice(&[0, i + 1, (i + 1) % segments + 1]);
+    }
+    MeshData::new(vertices, indices, Topology::Triangles)
+}
+
+// In the XZ plane facing +Y, like a floor, split into columns x rows cells
+pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData<StandardVertex> {
+    let (columns, rows) = (columns.max(1), rows.max(1));
+    let n = [0.0, 1.0, 0.0];
+    let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+    for row in 0..rows + 1 {
+        for column in 0..columns + 1 {
+            let u = column as f32 / columns as f32;
+            let v = row as f32 / rows as f32;
+            vertices.push(StandardVertex::new(
+                [(u - 0.5) * width, 0.0, (0.5 - v) * depth], [u, v], n));
+        }
+    }
+    let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * (columns + 1) + column;
+            let b = a + 1;
+            let c = a + columns + 1;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, b, d, d, c, a]);
+        }
+    }
+    MeshData::new(vertices, indices, Topology::Triangles)
+}
+
+// Separate vertices per face so every face has flat normals and the whole
+// texture
+pub fn cube(size: f32) -> MeshData<StandardVertex> {
+    let s = size * 0.5;
+    // Normal, then the face's right and up directions
+    let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+    ];
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for &(n, r, u) in faces.iter() {
+        let base = vertices.len() as u32;
+        for &(x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+            let p = [
+                (n[0] + r[0] * x + u[0] * y) * s,
+                (n[1] + r[1] * x + u[1] * y) * s,
+                (n[2] + r[2] * x + u[2] * y) * s,
+            ];
+            vertices.push(StandardVertex::new(p, [(x + 1.0) * 0.5, (y + 1.0) * 0.5], n));
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+    MeshData::new(vertices, indices, Topology::Triangles)
+}
+
+// UV sphere, segments around the equator and rings from pole to pole
+pub fn sphere(radius: f32, segments: u32, rings: u32) -> MeshData<StandardVertex> {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut vertices = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+    for ring in 0..rings + 1 {
+        let v = ring as f32 / rings as f32;
+        // From the north pole down
+        let (ring_sin, ring_cos) = (v * PI).sin_cos();
+        for segment in 0..segments + 1 {
+            // The seam has two columns of vertices so u can reach 1
+            let u = segment as f32 / segments as f32;
+            let (seg_sin, seg_cos) = (u * 2.0 * PI).sin_cos();
+            let n = [ring_sin * seg_cos, ring_cos, -ring_sin * seg_sin];
+            vertices.push(StandardVertex::new(
+                [n[0] * radius, n[1] * radius, n[2] * radius], [u, 1.0 - v], n));
+        }
+    }
+    let mut indices = Vec::with_capacity((segments * rings * 6) as usize);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = ring * (segments + 1) + segment;
+            let b = a + segments + 1;
+            // Skip the triangles that collapse into the poles
+            if ring != 0 {
+                indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if ring != rings - 1 {
+                indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+    MeshData::new(vertices, indices, Topology::Triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_triangles(data: &MeshData<StandardVertex>, vertex_count: usize, index_count: usize) {
+        assert_eq!(data.vertices.len(), vertex_count);
+        assert_eq!(data.indices.len(), index_count);
+        assert_eq!(data.submeshes, vec![
+            Submesh { start: 0, count: index_count, topology: Topology::Triangles },
+        ]);
+        data.validate().unwrap();
+        // Counter-clockwise when looking at the front, against the normals
+        for triangle in data.indices.chunks(3) {
+            let [a, b, c] = [
+                &data.vertices[triangle[0] as usize],
+                &data.vertices[triangle[1] as usize],
+                &data.vertices[triangle[2] as usize],
+            ];
+            let face = (b.position() - a.position()).cross(&(c.position() - a.position()));
+            let normal = Vector3::from(a.normal) + Vector3::from(b.normal)
+                + Vector3::from(c.normal);
+            assert!(face.dot(&normal) > 0.0, "triangle {:?} faces backward", triangle);
+        }
+    }
+
+    fn check_bounds(data: &MeshData<StandardVertex>, min: [f32; 3], max: [f32; 3]) {
+        let bounds = data.bounds().unwrap();
+        assert!((bounds.min - Vector3::from(min)).norm() < 1e-5, "{:?}", bounds);
+        assert!((bounds.max - Vector3::from(max)).norm() < 1e-5, "{:?}", bounds);
+    }
+
+    #[test]
+    fn rectangle_counts() {
+        let data = rectangle(2.0, 4.0);
+        check_triangles(&data, 4, 6);
+        check_bounds(&data, [-1.0, -2.0, 0.0], [1.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn circle_counts() {
+        let data = circle(2.0, 16);
+        check_triangles(&data, 17, 48);
+        check_bounds(&data, [-2.0, -2.0, 0.0], [2.0, 2.0, 0.0]);
+        // Clamped to a triangle
+        check_triangles(&circle(1.0, 1), 4, 9);
+    }
+
+    #[test]
+    fn grid_counts() {
+        let data = grid(4.0, 2.0, 4, 3);
+        check_triangles(&data, 5 * 4, 4 * 3 * 6);
+        check_bounds(&data, [-2.0, 0.0, -1.0], [2.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn cube_counts() {
+        let data = cube(2.0);
+        check_triangles(&data, 24, 36);
+        check_bounds(&data, [-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn sphere_counts() {
+        let data = sphere(3.0, 8, 6);
+        // The poles only get one triangle per segment
+        check_triangles(&data, 9 * 7, 8 * (6 * 2 - 2) * 3);
+        check_bounds(&data, [-3.0, -3.0, -3.0], [3.0, 3.0, 3.0]);
+        let bounds = data.bounds().unwrap();
+        assert!(bounds.center().norm() < 1e-5);
+        assert!((bounds.radius() - 3.0 * 3.0f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn validate_catches_out_of_range() {
+        let mut data = rectangle(1.0, 1.0);
+        data.indices.push(4);
+        assert!(data.validate().is_err());
+
+        let data = rectangle(1.0, 1.0).with_submeshes(vec![
+            Submesh { start: 3, count: 6, topology: Topology::Triangles },
+        ]);
+        assert!(data.validate().is_err());
+
+        let points = MeshData::new(rectangle(1.0, 1.0).vertices, Vec::new(), Topology::Points);
+        assert_eq!(points.submeshes[0].count, 4);
+        points.validate().unwrap();
+        assert!(Bounds::from_points(Vec::new().into_iter()).is_none());
+    }
+}
@@ -1,18 +1,33 @@
 extern crate sdl2;
 extern crate gl;
 
-use std::{ptr, mem};
-
 pub mod renderer;
+pub mod color;
+pub mod camera;
 pub mod shaders;
 pub mod material;
 pub mod program;
+pub mod program_cache;
+pub mod uniform;
+pub mod reflection;
 pub mod textures;
+pub mod framebuffer;
+pub mod postprocess;
+pub mod capture;
 pub mod buffer;
+pub mod vertex;
 pub mod vertex_array;
+pub mod mesh;
+pub mod debug_draw;
+pub mod sprite;
+pub mod sprite_batch;
+pub mod atlas;
+pub mod animation;
+
+pub use self::color::Color;
 
 #[repr(u32)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum GLDataType {
     Byte = gl::BYTE, 
     UnsignedByte = gl::UNSIGNED_BYTE, 
@@ -30,73 +45,11 @@ pub enum GLDataType {
     //  -GL_UNSIGNED_INT_10F_11F_11F_REV
 }
 
-pub struct Color {
-    r: f32, g: f32, b: f32, a: f32,
-}
-
-pub struct Triangles {
-    vertices: Vec<f32>,
-    vao: u32,
-    program: program::Program,
-}
-
-impl Triangles {
-    pub fn new(vertices: Vec<f32>) -> Self {
-        let mut tri = Triangles { 
-            vertices, 
-            vao: 0,
-            program: program::Program::triangle().unwrap(),
-        };
-        tri.init();
-        tri
-    }
-
-    fn init(&mut self) {
-        let mut vbo: gl::types::GLuint = 0;
-        // Send buffer data
-        unsafe {
-            gl::GenBuffers(1, &mut vbo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER, // target
-                (self.vertices.len() * 4)  as gl::types::GLsizeiptr, // size in bytes
-                self.vertices.as_ptr() as *const gl::types::GLvoid, // pointer to data
-                gl::STATIC_DRAW, // usage
-            );
-        }
-
-        // Bind VAO information, then unbind VAO and VBO
-        unsafe {
-            gl::GenVertexArrays(1, &mut self.vao);
-            gl::BindVertexArray(self.vao);
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
-                0, // index of attrib (layout = 0)
-                3, // number of components per attrib
-                gl::FLOAT, // data type
-                gl::FALSE, // normalized
-                3 * mem::size_of::<f32>() as gl::types::GLint, // byte offset
-                ptr::null()
-            );
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
-        }
-    }
-
-    pub fn draw(&self) {
-        self.program.set_used(true);
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawArrays(
-                gl::TRIANGLES, // mode
-                0, // starting index
-                3 // number of indices to render
-            )
-        }
-        self.program.set_used(false);
+impl GLDataType {
+    // Types VertexAttribIPointer accepts
+    pub fn is_integer(&self) -> bool {
+        matches!(*self,
+            GLDataType::Byte | GLDataType::UnsignedByte | GLDataType::Short
+                | GLDataType::UnsignedShort | GLDataType::Int | GLDataType::UnsignedInt)
     }
 }
-
-pub struct Rectangle {
-    
-}
\ No newline at end of file
@@ -0,0 +1,345 @@
+// Full screen effects applied to the finished scene. The scene is drawn into
+// the chain's framebuffer between begin and end, then every enabled pass
+// draws a full screen quad with its fragment shader, reading the last
+// pass's output and writing into the other of two ping-pong framebuffers.
+// The last enabled pass draws straight to the window.
+//
+// Pass shaders can use these uniforms, all optional:
+//   inputTexture   output of the previous pass, the scene for the first one
+//   sceneTexture   the scene before any pass ran
+//   texelSize      size of one pixel in texture coordinates
+// plus the pass's own textures and parameters.
+#![allow(dead_code)]
+
+extern crate nalgebra as na;
+
+use self::na::Vector2;
+use graphics::framebuffer::{Framebuffer, FramebufferOptions};
+use graphics::mesh::{self, Mesh, StandardVertex};
+use graphics::program::Program;
+use graphics::renderer::{BlendMode, Renderer};
+use graphics::shaders::{self, ShaderFile};
+use graphics::textures::{Filter, Texture, TextureFormat, TextureOptions, Wrap};
+use graphics::uniform::{Sampler, UniformValue};
+
+// Names of the built-in passes. Bloom is two passes.
+pub const BLOOM_BLUR: &str = "bloom_blur";
+pub const BLOOM: &str = "bloom";
+pub const COLOR_GRADING: &str = "color_grading";
+pub const FXAA: &str = "fxaa";
+pub const VIGNETTE: &str = "vignette";
+pub const CRT: &str = "crt";
+
+// Units 0 and 1 hold inputTexture and sceneTexture
+const FIRST_PASS_TEXTURE_UNIT: u32 = 2;
+
+// One full screen draw
+pub struct PostPass {
+    name: String,
+    program: Program,
+    params: Vec<(String, Box<dyn UniformValue>)>,
+    textures: Vec<(String, Texture)>,
+    enabled: bool,
+}
+
+impl PostPass {
+    // A pass running fragment over the full screen quad
+    pub fn new(name: &str, fragment: ShaderFile) -> Result<Self, String> {
+        let program = Program::from_files(&[shaders::FULLSCREEN_VERTEX, fragment])?;
+        Ok(PostPass::from_program(name, program))
+    }
+
+    // For programs built some other way. The vertex shader gets the quad's
+    // clip space position at location 0 and texture coordinates at 1.
+    pub fn from_program(name: &str, program: Program) -> Self {
+        PostPass {
+            name: String::from(name),
+            program,
+            params: Vec::new(),
+            textures: Vec::new(),
+            enabled: true,
+        }
+    }
+
+    pub fn with_param<T: UniformValue + 'static>(mut self, name: &str, value: T) -> Self {
+        self.set_param(name, value);
+        self
+    }
+
+    // Extra texture the shader samples with the uniform called name
+    pub fn with_texture(mut self, name: &str, texture: Texture) -> Self {
+        self.textures.push((String::from(name), texture));
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // Uploaded every time the pass runs, replaces an earlier value
+    pub fn set_param<T: UniformValue + 'static>(&mut self, name: &str, value: T) {
+        let value: Box<dyn UniformValue> = Box::new(value);
+        if let Some(param) = self.params.iter_mut().find(|p| p.0 == name) {
+            param.1 = value;
+            return;
+        }
+        self.params.push((String::from(name), value));
+    }
+
+    // Replaces the texture for name, or adds it
+    pub fn set_texture(&mut self, name: &str, texture: Texture) {
+        if let Some(entry) = self.textures.iter_mut().find(|t| t.0 == name) {
+            entry.1 = texture;
+            return;
+        }
+        self.textures.push((String::from(name), texture));
+    }
+
+    fn draw(&self, input: &Texture, scene: &Texture, texel_size: Vector2<f32>,
+            quad: &Mesh<StandardVertex>) {
+        self.program.set_used(true);
+        input.bind(0);
+        scene.bind(1);
+        self.set_if_active("inputTexture", &Sampler(0));
+        self.set_if_active("sceneTexture", &Sampler(1));
+        self.set_if_active("texelSize", &texel_size);
+        for (i, (name, texture)) in self.textures.iter().enumerate() {
+            let unit = FIRST_PASS_TEXTURE_UNIT + i as u32;
+            texture.bind(unit);
+            self.set_if_active(name, &Sampler(unit));
+        }
+        for (name, value) in self.params.iter() {
+            if let Err(e) = self.program.set_uniform(name, &**value) {
+                println!(
+                    "Warning: post pass {}: {} ({}:{}:{})",
+                    self.name, e, file!(), line!(), column!());
+            }
+        }
+
+        quad.draw();
+
+        for i in 0..self.textures.len() {
+            Texture::unbind(FIRST_PASS_TEXTURE_UNIT + i as u32);
+        }
+        Texture::unbind(1);
+        Texture::unbind(0);
+        self.program.set_used(false);
+    }
+
+    // Built-in uniforms a shader doesn't use are skipped quietly
+    fn set_if_active<T: UniformValue + ?Sized>(&self, name: &str, value: &T) {
+        if self.program.info().uniform(name).is_none() {
+            return;
+        }
+        if let Err(e) = self.program.set_uniform(name, value) {
+            println!(
+                "Warning: post pass {}: {} ({}:{}:{})",
+                self.name, e, file!(), line!(), column!());
+        }
+    }
+}
+
+pub struct PostChain {
+    scene: Framebuffer,
+    ping_pong: [Framebuffer; 2],
+    quad: Mesh<StandardVertex>,
+    passes: Vec<PostPass>,
+}
+
+impl PostChain {
+    // A chain without passes, which just copies the scene to the window
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let target = FramebufferOptions::new().with_color(target_options());
+        Ok(PostChain {
+            scene: Framebuffer::standard(width, height)?,
+            ping_pong: [
+                Framebuffer::new(width, height, &target)?,
+                Framebuffer::new(width, height, &target)?,
+            ],
+            // 2x2 so it covers clip space
+            quad: Mesh::new(&mesh::rectangle(2.0, 2.0))?,
+            passes: Vec::new(),
+        })
+    }
+
+    // Bloom, color grading with an identity LUT, FXAA, vignette and CRT in
+    // that order, all disabled. Turn them on with set_enabled or PostEffects.
+    pub fn with_defaults(width: u32, height: u32) -> Result<Self, String> {
+        let mut chain = PostChain::new(width, height)?;
+        let lut_size = 16;
+        chain.push(PostPass::new(BLOOM_BLUR, shaders::BLOOM_BLUR_FRAG)?.with_enabled(false));
+        chain.push(PostPass::new(BLOOM, shaders::BLOOM_FRAG)?.with_enabled(false));
+        chain.push(PostPass::new(COLOR_GRADING, shaders::COLOR_GRADE_FRAG)?
+            .with_texture("lut", identity_lut(lut_size)?)
+            .with_param("lutSize", lut_size as f32)
+            .with_enabled(false));
+        chain.push(PostPass::new(FXAA, shaders::FXAA_FRAG)?.with_enabled(false));
+        chain.push(PostPass::new(VIGNETTE, shaders::VIGNETTE_FRAG)?.with_enabled(false));
+        chain.push(PostPass::new(CRT, shaders::CRT_FRAG)?.with_enabled(false));
+        Ok(chain)
+    }
+
+    // Runs after the passes already added
+    pub fn push(&mut self, pass: PostPass) {
+        self.passes.push(pass);
+    }
+
+    pub fn passes(&self) -> &[PostPass] {
+        &self.passes
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&PostPass> {
+        self.passes.iter().find(|p| p.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostPass> {
+        self.passes.iter_mut().find(|p| p.name == name)
+    }
+
+    // Returns false if there is no pass called name
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.pass_mut(name) {
+            Some(pass) => {
+                pass.set_enabled(enabled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_param<T>(&mut self, pass: &str, name: &str, value: T) -> Result<(), String>
+        where T: UniformValue + 'static {
+        match self.pass_mut(pass) {
+            Some(p) => {
+                p.set_param(name, value);
+                Ok(())
+            }
+            None => Err(format!("post chain has no pass {}", pass)),
+        }
+    }
+
+    // The framebuffer the scene is drawn into
+    pub fn scene(&self) -> &Framebuffer {
+        &self.scene
+    }
+
+    // Call with the window's drawable size when it changes
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.scene.resize(width, height)?;
+        self.ping_pong[0].resize(width, height)?;
+        self.ping_pong[1].resize(width, height)
+    }
+
+    // Makes the scene framebuffer the render target. Clear it with
+    // Renderer::begin_frame or Renderer::clear after this.
+    pub fn begin(&self, renderer: &mut Renderer) {
+        renderer.set_render_target(Some(&self.scene));
+    }
+
+    // Runs the enabled passes and leaves the window as the render target.
+    // Blending and depth testing are off during the passes and put back
+    // after.
+    pub fn end(&self, renderer: &mut Renderer) {
+        let enabled: Vec<&PostPass> = self.passes.iter().filter(|p| p.enabled).collect();
+        let scene = match self.scene.texture(0) {
+            Some(texture) if !enabled.is_empty() => texture,
+            _ => {
+                renderer.set_render_target(None);
+                let viewport = renderer.viewport();
+                self.scene.blit_to_window(viewport.width as u32, viewport.height as u32);
+                return;
+            }
+        };
+
+        let blend_mode = renderer.blend_mode();
+        let depth_test = renderer.depth_test();
+        renderer.set_blend_mode(BlendMode::None);
+        renderer.set_depth_test(false);
+
+        let texel_size = Vector2::new(
+            1.0 / self.scene.width() as f32, 1.0 / self.scene.height() as f32);
+        let mut input = scene;
+        for (i, pass) in enabled.iter().enumerate() {
+            let last = i + 1 == enabled.len();
+            let target = &self.ping_pong[i % 2];
+            renderer.set_render_target(if last { None } else { Some(target) });
+            pass.draw(input, scene, texel_size, &self.quad);
+            if let Some(output) = target.texture(0) {
+                input = output;
+            }
+        }
+
+        renderer.set_blend_mode(blend_mode);
+        renderer.set_depth_test(depth_test);
+    }
+}
+
+// Which built-in effects are on. Plain data so Game can own and toggle it
+// without touching GL, main applies it to the chain each frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PostEffects {
+    pub bloom: bool,
+    pub color_grading: bool,
+    pub fxaa: bool,
+    pub vignette: bool,
+    pub crt: bool,
+}
+
+impl PostEffects {
+    pub fn apply(&self, chain: &mut PostChain) {
+        chain.set_enabled(BLOOM_BLUR, self.bloom);
+        chain.set_enabled(BLOOM, self.bloom);
+        chain.set_enabled(COLOR_GRADING, self.color_grading);
+        chain.set_enabled(FXAA, self.fxaa);
+        chain.set_enabled(VIGNETTE, self.vignette);
+        chain.set_enabled(CRT, self.crt);
+    }
+}
+
+fn target_options() -> TextureOptions {
+    TextureOptions::new()
+        .with_format(TextureFormat::Rgba8)
+        .with_filter(Filter::Linear)
+        .with_wrap(Wrap::ClampToEdge)
+}
+
+// LUT that maps every color to itself, in the layout color_grade.frag reads.
+// Graded LUTs are made by running this through an image editor with the
+// same adjustments as a screenshot.
+pub fn identity_lut(size: u32) -> Result<Texture, String> {
+    let size = size.max(2);
+    let max = (size - 1) as f32;
+    let mut pixels = Vec::with_capacity((size * size * size * 3) as usize);
+    // Rows bottom first, green goes up
+    for g in 0..size {
+        for b in 0..size {
+            for r in 0..size {
+                for &c in [r, g, b].iter() {
+                    pixels.push((c as f32 / max * 255.0).round() as u8);
+                }
+            }
+        }
+    }
+    let options = TextureOptions::new()
+        .with_format(TextureFormat::Rgb8)
+        .with_filter(Filter::Linear)
+        .with_wrap(Wrap::ClampToEdge);
+    Texture::from_bytes(size * size, size, &pixels, &options)
+}
@@ -1,78 +1,367 @@
 extern crate gl;
 
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::ptr;
-use graphics::shaders;
+use std::time::{Duration, Instant, SystemTime};
+use graphics::program_cache;
+use graphics::shaders::{self, ShaderError};
+use graphics::reflection::ProgramInfo;
+use graphics::uniform::{Sampler, UniformValue};
+
+// How often a hot reloaded program checks its files
+const HOT_RELOAD_POLL_MS: u64 = 250;
+
+// Files a program was built from and when they were last modified
+struct HotReload {
+    files: Vec<shaders::ShaderFile>,
+    defines: shaders::Defines,
+    // Every file read while compiling, includes too
+    watched: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
 
 pub struct Program {
-    id: gl::types::GLuint,
+    // Changes when a hot reloaded program is relinked
+    id: Cell<gl::types::GLuint>,
+    // Uniform name to location, -1 for names that don't exist
+    uniform_locations: RefCell<HashMap<String, i32>>,
+    // Active attributes, uniforms and blocks, queried after linking
+    info: RefCell<ProgramInfo>,
+    hot_reload: Option<RefCell<HotReload>>,
 }
 
 impl Program {
-    pub fn from_shaders(shaders: &[shaders::Shader]) -> Result<Program, String> {
-        let program_id = unsafe { gl::CreateProgram() };
+    pub fn from_shaders(shaders: &[shaders::Shader]) -> Result<Program, ShaderError> {
+        let names = shaders.iter().map(|s| format!("shader {}", s.id())).collect();
+        Program::from_linked(link(shaders, names, false)?)
+    }
 
-        for shader in shaders {
-            unsafe { gl::AttachShader(program_id, shader.id()); }
-        }
+    fn from_linked(program_id: gl::types::GLuint) -> Result<Program, ShaderError> {
+        Ok(Program {
+            id: Cell::new(program_id),
+            uniform_locations: RefCell::new(HashMap::new()),
+            info: RefCell::new(ProgramInfo::query(program_id)),
+            hot_reload: None,
+        })
+    }
 
-        unsafe { gl::LinkProgram(program_id); }
-        
-        let mut success: gl::types::GLint = 1;
-        unsafe {
-            gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+    // Compiles and links shader files. With shaders::set_hot_reload on, the
+    // sources are read from disk and the program relinks itself when they
+    // change, see set_used.
+    pub fn from_files(files: &[shaders::ShaderFile]) -> Result<Program, ShaderError> {
+        Program::from_files_with_defines(files, &shaders::Defines::new())
+    }
+
+    // Compiles the files with #defines injected after their #version line
+    pub fn from_files_with_defines(files: &[shaders::ShaderFile], defines: &shaders::Defines)
+        -> Result<Program, ShaderError> {
+        let preprocessed = files.iter()
+            .map(|f| f.preprocess(defines))
+            .collect::<Result<Vec<_>, ShaderError>>()?;
+        let used_files: Vec<String> = preprocessed.iter()
+            .flat_map(|p| p.files.iter().cloned())
+            .collect();
+
+        // Use the binary from an earlier run if there is one
+        let stages: Vec<_> = files.iter().map(|f| f.kind).zip(preprocessed.iter()).collect();
+        let cache_entry = program_cache::Entry::for_sources(&stages);
+        let cached_id = cache_entry.as_ref().and_then(|entry| entry.load());
+        let program_id = match cached_id {
+            Some(program_id) => program_id,
+            None => {
+                let compiled = files.iter().zip(preprocessed.iter())
+                    .map(|(f, p)| f.compile_preprocessed(p))
+                    .collect::<Result<Vec<_>, ShaderError>>()?;
+                let program_id = link(&compiled, file_names(files), cache_entry.is_some())?;
+                if let Some(ref entry) = cache_entry {
+                    if let Err(e) = entry.store(program_id) {
+                        println!(
+                            "Warning: couldn't cache program: {} ({}:{}:{})",
+                            e, file!(), line!(), column!());
+                    }
+                }
+                program_id
+            }
+        };
+
+        let mut program = Program::from_linked(program_id)?;
+        if shaders::hot_reload_enabled() {
+            let watched = watched_paths(&used_files);
+            program.hot_reload = Some(RefCell::new(HotReload {
+                files: files.to_vec(),
+                defines: defines.clone(),
+                modified: watched.iter().map(|p| modified_time(p)).collect(),
+                watched,
+                last_poll: Instant::now(),
+            }));
         }
+        Ok(program)
+    }
+
+    pub fn standard() -> Result<Program, ShaderError> {
+        Program::from_files(&[shaders::STANDARD_VERTEX, shaders::STANDARD_FRAG])
+    }
+
+    pub fn sprite() -> Result<Program, ShaderError> {
+        Program::from_files(&[shaders::SPRITE_VERTEX, shaders::SPRITE_FRAG])
+    }
+
+    pub fn triangle() -> Result<Program, ShaderError> {
+        Program::from_files(&[shaders::TRIANGLE_VERTEX, shaders::TRIANGLE_FRAG])
+    }
+
+    pub fn debug() -> Result<Program, ShaderError> {
+        Program::from_files(&[shaders::DEBUG_VERTEX, shaders::DEBUG_FRAG])
+    }
 
-        if success == 0 {
-            return Err(get_program_error_log(program_id));
+    // Relinks from the files on disk if any of them changed since the last
+    // check. Runs from set_used(true), so a program is never swapped out
+    // between setting its uniforms and drawing. Uniforms go back to their
+    // defaults after a relink. If the new sources don't compile or link,
+    // the error is printed and the old program stays in use.
+    // Returns true if the program was relinked.
+    pub fn reload_if_changed(&self) -> bool {
+        let hot_reload = match self.hot_reload {
+            Some(ref h) => h,
+            None => { return false; }
+        };
+        let mut hot_reload = hot_reload.borrow_mut();
+        if hot_reload.last_poll.elapsed() < Duration::from_millis(HOT_RELOAD_POLL_MS) {
+            return false;
         }
+        hot_reload.last_poll = Instant::now();
 
-        for shader in shaders {
-            unsafe { gl::DetachShader(program_id, shader.id()); }
+        let modified: Vec<Option<SystemTime>> =
+            hot_reload.watched.iter().map(|p| modified_time(p)).collect();
+        if modified == hot_reload.modified {
+            return false;
         }
+        // Even on failure, so a broken file is only reported once per save
+        hot_reload.modified = modified;
 
-        Ok(Program { id: program_id })
+        match relink(&hot_reload.files, &hot_reload.defines) {
+            Ok((program_id, used_files)) => {
+                // Includes may have been added or removed
+                let watched = watched_paths(&used_files);
+                if watched != hot_reload.watched {
+                    hot_reload.modified = watched.iter().map(|p| modified_time(p)).collect();
+                    hot_reload.watched = watched;
+                }
+                unsafe {
+                    gl::DeleteProgram(self.id.get());
+                }
+                self.id.set(program_id);
+                self.uniform_locations.borrow_mut().clear();
+                *self.info.borrow_mut() = ProgramInfo::query(program_id);
+                println!("Reloaded shaders {}", file_names(&hot_reload.files).join(", "));
+                true
+            }
+            Err(e) => {
+                println!(
+                    "Warning: failed to reload shaders {}, keeping the old program: {} ({}:{}:{})",
+                    file_names(&hot_reload.files).join(", "), e, file!(), line!(), column!());
+                false
+            }
+        }
     }
 
-    pub fn standard() -> Result<Program, String> {
-        let standard_vert = shaders::Shader::from_vert_source(
-            &CString::new(shaders::STANDARD_VERTEX_SOURCE_STR).unwrap()
-        )?;
-        let standard_frag = shaders::Shader::from_frag_source(
-            &CString::new(shaders::STANDARD_FRAG_SOURCE_STR).unwrap()
-        )?;
-        Program::from_shaders(&[standard_vert, standard_frag])
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id.get()
     }
 
-    pub fn triangle() -> Result<Program, String> {
-        let tri_vert = shaders::Shader::from_vert_source(
-            &CString::new(shaders::TRIANGLE_VERTEX_SOURCE_STR).unwrap()
-        )?;
-        let tri_frag = shaders::Shader::from_frag_source(
-            &CString::new(shaders::TRIANGLE_FRAG_SOURCE_STR).unwrap()
-        )?;
-        Program::from_shaders(&[tri_vert, tri_frag])
+    pub fn info<'a>(&'a self) -> Ref<'a, ProgramInfo> {
+        self.info.borrow()
     }
 
-    pub fn id(&self) -> gl::types::GLuint {
-        self.id
+    // Location of an active vertex attribute, so VAO layouts don't have to
+    // hardcode the shader's layout indices
+    pub fn attribute_location(&self, name: &str) -> Result<u32, String> {
+        match self.info.borrow().attribute(name) {
+            Some(attribute) if attribute.location >= 0 => Ok(attribute.location as u32),
+            _ => Err(format!("program {} has no active attribute {}", self.id(), name)),
+        }
     }
 
     pub fn set_used(&self, used: bool) {
+        if used {
+            self.reload_if_changed();
+        }
         unsafe {
-            gl::UseProgram(if used { self.id} else { 0 });
+            gl::UseProgram(if used { self.id() } else { 0 });
         }
     }
+
+    // Location of a uniform, None if it doesn't exist or was optimized out.
+    // Looked up once per name, then cached.
+    pub fn uniform_location(&self, name: &str) -> Option<i32> {
+        if let Some(location) = self.uniform_locations.borrow().get(name) {
+            return if *location < 0 { None } else { Some(*location) };
+        }
+        let location = match CString::new(name) {
+            Ok(c_name) => unsafe { gl::GetUniformLocation(self.id(), c_name.as_ptr()) },
+            Err(_) => -1,
+        };
+        self.uniform_locations.borrow_mut().insert(name.to_owned(), location);
+        if location < 0 { None } else { Some(location) }
+    }
+
+    // Sets a uniform on this program, which must be in use.
+    // In debug builds setting a uniform that doesn't exist, or while another
+    // program is in use, is an error. Release builds skip the checks and
+    // ignore unknown names.
+    pub fn set_uniform<T>(&self, name: &str, value: &T) -> Result<(), String>
+        where T: UniformValue + ?Sized {
+        if cfg!(debug_assertions) {
+            let mut current: gl::types::GLint = 0;
+            unsafe {
+                gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut current);
+            }
+            if current as u32 != self.id() {
+                return Err(format!(
+                    "setting uniform {} on program {} while program {} is in use",
+                    name, self.id(), current));
+            }
+        }
+        match self.uniform_location(name) {
+            Some(location) => {
+                unsafe {
+                    value.upload(location);
+                }
+                Ok(())
+            }
+            None if cfg!(debug_assertions) => Err(format!(
+                "program {} has no active uniform {}", self.id(), name)),
+            None => Ok(()),
+        }
+    }
+
+    // Points a sampler uniform at a texture unit
+    pub fn set_sampler(&self, name: &str, slot: u32) -> Result<(), String> {
+        self.set_uniform(name, &Sampler(slot))
+    }
 }
 
 impl Drop for Program {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteProgram(self.id);
+            gl::DeleteProgram(self.id.get());
         }
     }
 }
 
+// Programs built from the same files with different define sets, each one
+// compiled the first time it's asked for
+pub struct ProgramVariants {
+    files: Vec<shaders::ShaderFile>,
+    variants: HashMap<String, Rc<Program>>,
+}
+
+impl ProgramVariants {
+    pub fn new(files: &[shaders::ShaderFile]) -> Self {
+        ProgramVariants { files: files.to_vec(), variants: HashMap::new() }
+    }
+
+    pub fn get(&mut self, defines: &shaders::Defines) -> Result<Rc<Program>, ShaderError> {
+        let key = defines.key();
+        if let Some(program) = self.variants.get(&key) {
+            return Ok(program.clone());
+        }
+        let program = Rc::new(Program::from_files_with_defines(&self.files, defines)?);
+        self.variants.insert(key, program.clone());
+        Ok(program)
+    }
+
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
+
+// Links the shaders, deleting the program object again if linking fails.
+// source_names are only used for errors. retrievable asks the driver to
+// keep the binary around for program_cache.
+fn link(shaders: &[shaders::Shader], source_names: Vec<String>, retrievable: bool)
+    -> Result<gl::types::GLuint, ShaderError> {
+    let program_id = unsafe { gl::CreateProgram() };
+    if program_id == 0 {
+        return Err(ShaderError::CreateFailed { source_name: source_names.join(" + ") });
+    }
+
+    for shader in shaders {
+        unsafe { gl::AttachShader(program_id, shader.id()); }
+    }
+
+    if retrievable {
+        unsafe {
+            gl::ProgramParameteri(
+                program_id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as gl::types::GLint);
+        }
+    }
+
+    unsafe { gl::LinkProgram(program_id); }
+
+    let mut success: gl::types::GLint = 1;
+    unsafe {
+        gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+    }
+
+    // Detached either way so the shaders can be deleted when dropped
+    for shader in shaders {
+        unsafe { gl::DetachShader(program_id, shader.id()); }
+    }
+
+    if success == 0 {
+        let error = ShaderError::link(source_names, &get_program_error_log(program_id));
+        unsafe { gl::DeleteProgram(program_id); }
+        return Err(error);
+    }
+
+    Ok(program_id)
+}
+
+// Compiles straight from disk, without falling back to the built-in sources
+fn relink(files: &[shaders::ShaderFile], defines: &shaders::Defines)
+    -> Result<(gl::types::GLuint, Vec<String>), ShaderError> {
+    let mut compiled = Vec::with_capacity(files.len());
+    let mut used_files = Vec::new();
+    for file in files {
+        let (shader, names) = file.compile_from_disk(defines)?;
+        compiled.push(shader);
+        used_files.extend(names);
+    }
+    Ok((link(&compiled, file_names(files), false)?, used_files))
+}
+
+fn watched_paths(file_names: &[String]) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = file_names.iter()
+        .map(|name| PathBuf::from(shaders::SHADER_DIR).join(name))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn file_names(files: &[shaders::ShaderFile]) -> Vec<String> {
+    files.iter().map(|f| f.file_name.to_owned()).collect()
+}
+
 fn get_program_error_log(program_id: gl::types::GLuint) -> String {
     let mut len: gl::types::GLint = 0;
     unsafe {
@@ -95,5 +384,5 @@ fn get_program_error_log(program_id: gl::types::GLuint) -> String {
         );
     }
 
-    return error.to_string_lossy().into_owned();
+    error.to_string_lossy().trim_end_matches('\0').to_owned()
 }
\ No newline at end of file
@@ -0,0 +1,276 @@
+// Linked program binaries saved to disk, so later launches skip compiling
+// and linking. Entries are keyed by a hash of the preprocessed sources and
+// the driver, and a binary the driver rejects (after an update, say) is
+// deleted and rebuilt from source.
+//
+// File layout, all integers little endian:
+//   magic     8 bytes  "RASPROG\0"
+//   format    u32      binary format from glGetProgramBinary
+//   checksum  u64      FNV-1a hash of the binary
+//   driver    u32 length then bytes, GL_VENDOR/GL_RENDERER/GL_VERSION
+//   binary             the rest of the file
+#![allow(dead_code)]
+
+extern crate gl;
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use graphics::shaders::{Preprocessed, ShaderKind};
+use util::{checksum, read_u32, read_u64, u32_bytes, u64_bytes, write_atomic};
+
+const MAGIC: &[u8; 8] = b"RASPROG\0";
+const EXTENSION: &str = "bin";
+
+thread_local! {
+    // GL objects belong to the thread with the context, so the cache does too
+    static CACHE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+// Turns caching on for programs created from shader files after this.
+// None turns it off.
+pub fn set_cache_dir<P: AsRef<Path>>(dir: Option<P>) {
+    let dir = dir.map(|d| d.as_ref().to_path_buf());
+    CACHE_DIR.with(|d| *d.borrow_mut() = dir);
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.with(|d| d.borrow().clone())
+}
+
+// Program binaries need GL 4.1 or ARB_get_program_binary, and a driver
+// that offers at least one binary format
+pub fn is_supported() -> bool {
+    if !gl::GetProgramBinary::is_loaded() || !gl::ProgramBinary::is_loaded()
+        || !gl::ProgramParameteri::is_loaded() {
+        return false;
+    }
+    let mut formats: gl::types::GLint = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+    }
+    formats > 0
+}
+
+// Deletes every cached binary
+pub fn clear() -> Result<(), String> {
+    let dir = match cache_dir() {
+        Some(dir) => dir,
+        None => { return Ok(()); }
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => { return Ok(()); }
+    };
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_some_and(|e| e == EXTENSION) {
+            fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// Where one program's binary is stored
+pub struct Entry {
+    path: PathBuf,
+    driver: String,
+}
+
+impl Entry {
+    // None if caching is off or not supported by the driver
+    pub fn for_sources(stages: &[(ShaderKind, &Preprocessed)]) -> Option<Entry> {
+        let dir = cache_dir()?;
+        if !is_supported() {
+            return None;
+        }
+        let driver = driver_string();
+        let mut key_bytes = driver.clone().into_bytes();
+        for &(kind, preprocessed) in stages.iter() {
+            key_bytes.push(0);
+            key_bytes.push(match kind {
+                ShaderKind::Vertex => b'v',
+                ShaderKind::Fragment => b'f',
+            });
+            key_bytes.extend_from_slice(preprocessed.source.as_bytes());
+        }
+        let path = dir.join(format!("{:016x}.{}", checksum(&key_bytes), EXTENSION));
+        Some(Entry { path, driver })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Creates a program from the cached binary. Returns None if there is no
+    // entry or the driver rejects it, in which case the file is removed.
+    pub fn load(&self) -> Option<gl::types::GLuint> {
+        let bytes = fs::read(&self.path).ok()?;
+        let (format, binary) = match decode(&bytes, &self.driver) {
+            Some(decoded) => decoded,
+            None => {
+                self.discard("unreadable or from another driver");
+                return None;
+            }
+        };
+
+        let program_id = unsafe { gl::CreateProgram() };
+        if program_id == 0 {
+            return None;
+        }
+        let mut success: gl::types::GLint = 0;
+        unsafe {
+            gl::ProgramBinary(
+                program_id, format, binary.as_ptr() as *const c_void,
+                binary.len() as gl::types::GLsizei);
+            gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+        }
+        if success == 0 {
+            unsafe { gl::DeleteProgram(program_id); }
+            self.discard("rejected by the driver");
+            return None;
+        }
+        Some(program_id)
+    }
+
+    // Saves a linked program, which must have been linked with
+    // PROGRAM_BINARY_RETRIEVABLE_HINT set
+    pub fn store(&self, program_id: gl::types::GLuint) -> Result<(), String> {
+        let mut length: gl::types::GLint = 0;
+        unsafe {
+            gl::GetProgramiv(program_id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+        if length <= 0 {
+            return Err(format!("program {} has no binary", program_id));
+        }
+        let mut binary = vec![0u8; length as usize];
+        let mut written: gl::types::GLsizei = 0;
+        let mut format: gl::types::GLenum = 0;
+        unsafe {
+            gl::GetProgramBinary(
+                program_id, length, &mut written, &mut format,
+                binary.as_mut_ptr() as *mut c_void);
+        }
+        binary.truncate(written.max(0) as usize);
+        if binary.is_empty() {
+            return Err(format!("program {} has no binary", program_id));
+        }
+
+        // A crash mid-write never leaves a truncated entry
+        let bytes = encode(format, &self.driver, &binary);
+        write_atomic(&self.path, &bytes).map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    fn discard(&self, reason: &str) {
+        println!(
+            "Warning: dropping cached program {}, {} ({}:{}:{})",
+            self.path.display(), reason, file!(), line!(), column!());
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn driver_string() -> String {
+    let get = |name| unsafe {
+        let ptr = gl::GetString(name);
+        if ptr.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(ptr as *const _).to_string_lossy().into_owned()
+        }
+    };
+    format!("{}|{}|{}", get(gl::VENDOR), get(gl::RENDERER), get(gl::VERSION))
+}
+
+fn encode(format: u32, driver: &str, binary: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(8 + 4 + 8 + 4 + driver.len() + binary.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&u32_bytes(format));
+    bytes.extend_from_slice(&u64_bytes(checksum(binary)));
+    bytes.extend_from_slice(&u32_bytes(driver.len() as u32));
+    bytes.extend_from_slice(driver.as_bytes());
+    bytes.extend_from_slice(binary);
+    bytes
+}
+
+// Returns the format and binary if the header is valid and the entry was
+// written by the same driver
+fn decode<'a>(bytes: &'a [u8], driver: &str) -> Option<(u32, &'a [u8])> {
+    if bytes.len() < 24 || &bytes[0..8] != MAGIC {
+        return None;
+    }
+    let format = read_u32(&bytes[8..12]);
+    let expected = read_u64(&bytes[12..20]);
+    let driver_len = read_u32(&bytes[20..24]) as usize;
+    let binary_start = 24 + driver_len;
+    if bytes.len() < binary_start || &bytes[24..binary_start] != driver.as_bytes() {
+        return None;
+    }
+    let binary = &bytes[binary_start..];
+    if binary.is_empty() || checksum(binary) != expected {
+        return None;
+    }
+    Some((format, binary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRIVER: &str = "Vendor|Renderer|4.6";
+
+    #[test]
+    fn header_layout() {
+        let bytes = encode(7, DRIVER, b"binary");
+        assert_eq!(&bytes[0..8], MAGIC);
+        assert_eq!(read_u32(&bytes[8..12]), 7);
+        assert_eq!(read_u64(&bytes[12..20]), checksum(b"binary"));
+        assert_eq!(read_u32(&bytes[20..24]) as usize, DRIVER.len());
+        assert_eq!(&bytes[24..24 + DRIVER.len()], DRIVER.as_bytes());
+        assert_eq!(&bytes[24 + DRIVER.len()..], b"binary");
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = encode(7, DRIVER, b"binary");
+        assert_eq!(decode(&bytes, DRIVER), Some((7, &b"binary"[..])));
+    }
+
+    #[test]
+    fn rejects_wrong_magic() {
+        let mut bytes = encode(7, DRIVER, b"binary");
+        bytes[0] = b'X';
+        assert_eq!(decode(&bytes, DRIVER), None);
+    }
+
+    #[test]
+    fn rejects_other_driver() {
+        let bytes = encode(7, DRIVER, b"binary");
+        assert_eq!(decode(&bytes, "Vendor|Renderer|4.5"), None);
+        assert_eq!(decode(&bytes, ""), None);
+    }
+
+    #[test]
+    fn rejects_truncated_entry() {
+        let bytes = encode(7, DRIVER, b"binary");
+        // Inside the header, inside the driver string and inside the binary
+        for &len in [0, 23, 30, bytes.len() - 1].iter() {
+            assert_eq!(decode(&bytes[..len], DRIVER), None, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let mut bytes = encode(7, DRIVER, b"binary");
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert_eq!(decode(&bytes, DRIVER), None);
+    }
+
+    #[test]
+    fn rejects_empty_binary() {
+        let bytes = encode(7, DRIVER, b"");
+        assert_eq!(decode(&bytes, DRIVER), None);
+    }
+}
@@ -0,0 +1,266 @@
+// Introspection of linked programs: what attributes, uniforms and uniform
+// blocks the driver kept after linking, with their GLSL types.
+#![allow(dead_code)]
+
+extern crate gl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GlslType {
+    Float, Vec2, Vec3, Vec4,
+    Int, IVec2, IVec3, IVec4,
+    UInt, UVec2, UVec3, UVec4,
+    Bool, BVec2, BVec3, BVec4,
+    Mat2, Mat3, Mat4,
+    Sampler2D, Sampler3D, SamplerCube, Sampler2DArray, Sampler2DShadow,
+    ISampler2D, USampler2D,
+    // Anything else, with the raw GL enum
+    Other(u32),
+}
+
+impl GlslType {
+    pub fn from_gl(ty: u32) -> Self {
+        match ty {
+            gl::FLOAT => GlslType::Float,
+            gl::FLOAT_VEC2 => GlslType::Vec2,
+            gl::FLOAT_VEC3 => GlslType::Vec3,
+            gl::FLOAT_VEC4 => GlslType::Vec4,
+            gl::INT => GlslType::Int,
+            gl::INT_VEC2 => GlslType::IVec2,
+            gl::INT_VEC3 => GlslType::IVec3,
+            gl::INT_VEC4 => GlslType::IVec4,
+            gl::UNSIGNED_INT => GlslType::UInt,
+            gl::UNSIGNED_INT_VEC2 => GlslType::UVec2,
+            gl::UNSIGNED_INT_VEC3 => GlslType::UVec3,
+            gl::UNSIGNED_INT_VEC4 => GlslType::UVec4,
+            gl::BOOL => GlslType::Bool,
+            gl::BOOL_VEC2 => GlslType::BVec2,
+            gl::BOOL_VEC3 => GlslType::BVec3,
+            gl::BOOL_VEC4 => GlslType::BVec4,
+            gl::FLOAT_MAT2 => GlslType::Mat2,
+            gl::FLOAT_MAT3 => GlslType::Mat3,
+            gl::FLOAT_MAT4 => GlslType::Mat4,
+            gl::SAMPLER_2D => GlslType::Sampler2D,
+            gl::SAMPLER_3D => GlslType::Sampler3D,
+            gl::SAMPLER_CUBE => GlslType::SamplerCube,
+            gl::SAMPLER_2D_ARRAY => GlslType::Sampler2DArray,
+            gl::SAMPLER_2D_SHADOW => GlslType::Sampler2DShadow,
+            gl::INT_SAMPLER_2D => GlslType::ISampler2D,
+            gl::UNSIGNED_INT_SAMPLER_2D => GlslType::USampler2D,
+            other => GlslType::Other(other),
+        }
+    }
+
+    // Components per attribute location, so 4 for a mat4 (which takes
+    // 4 locations, one per column). 0 for samplers and unknown types.
+    pub fn components(&self) -> i32 {
+        match *self {
+            GlslType::Float | GlslType::Int | GlslType::UInt | GlslType::Bool => 1,
+            GlslType::Vec2 | GlslType::IVec2 | GlslType::UVec2 | GlslType::BVec2
+                | GlslType::Mat2 => 2,
+            GlslType::Vec3 | GlslType::IVec3 | GlslType::UVec3 | GlslType::BVec3
+                | GlslType::Mat3 => 3,
+            GlslType::Vec4 | GlslType::IVec4 | GlslType::UVec4 | GlslType::BVec4
+                | GlslType::Mat4 => 4,
+            _ => 0,
+        }
+    }
+
+    // Attribute locations used by one element
+    pub fn locations(&self) -> u32 {
+        match *self {
+            GlslType::Mat2 => 2,
+            GlslType::Mat3 => 3,
+            GlslType::Mat4 => 4,
+            _ => 1,
+        }
+    }
+
+    // Integer attributes have to be fed with VertexAttribIPointer
+    pub fn is_integer(&self) -> bool {
+        matches!(*self,
+            GlslType::Int | GlslType::IVec2 | GlslType::IVec3 | GlslType::IVec4
+                | GlslType::UInt | GlslType::UVec2 | GlslType::UVec3 | GlslType::UVec4)
+    }
+
+    pub fn is_sampler(&self) -> bool {
+        matches!(*self,
+            GlslType::Sampler2D | GlslType::Sampler3D | GlslType::SamplerCube
+                | GlslType::Sampler2DArray | GlslType::Sampler2DShadow
+                | GlslType::ISampler2D | GlslType::USampler2D)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ActiveAttribute {
+    pub name: String,
+    pub ty: GlslType,
+    // Array length, 1 for non-arrays
+    pub size: i32,
+    // -1 for built-ins like gl_VertexID
+    pub location: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ActiveUniform {
+    // Arrays are reported as "name[0]"
+    pub name: String,
+    pub ty: GlslType,
+    pub size: i32,
+    // -1 for uniforms inside a block
+    pub location: i32,
+    pub block_index: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UniformBlock {
+    pub name: String,
+    pub index: u32,
+    pub binding: u32,
+    pub data_size: i32,
+    // Names of the uniforms inside the block
+    pub uniforms: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProgramInfo {
+    pub attributes: Vec<ActiveAttribute>,
+    pub uniforms: Vec<ActiveUniform>,
+    pub blocks: Vec<UniformBlock>,
+}
+
+impl ProgramInfo {
+    // Queries a successfully linked program
+    pub fn query(program_id: gl::types::GLuint) -> Self {
+        let attributes = query_attributes(program_id);
+        let uniforms = query_uniforms(program_id);
+        let blocks = query_blocks(program_id, &uniforms);
+        ProgramInfo { attributes, uniforms, blocks }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&ActiveAttribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    // Arrays can be found with or without the [0] suffix
+    pub fn uniform(&self, name: &str) -> Option<&ActiveUniform> {
+        self.uniforms.iter().find(|u| {
+            u.name == name
+                || (u.name.ends_with("[0]") && u.name[..u.name.len() - 3] == *name)
+        })
+    }
+
+    pub fn block(&self, name: &str) -> Option<&UniformBlock> {
+        self.blocks.iter().find(|b| b.name == name)
+    }
+
+    // Sampler uniforms outside of blocks, in location order
+    pub fn samplers(&self) -> Vec<&ActiveUniform> {
+        let mut samplers: Vec<&ActiveUniform> = self.uniforms.iter()
+            .filter(|u| u.ty.is_sampler() && u.location >= 0)
+            .collect();
+        samplers.sort_by_key(|u| u.location);
+        samplers
+    }
+}
+
+fn get_program_int(program_id: gl::types::GLuint, param: gl::types::GLenum) -> i32 {
+    let mut value: gl::types::GLint = 0;
+    unsafe {
+        gl::GetProgramiv(program_id, param, &mut value);
+    }
+    value
+}
+
+fn name_from_buffer(mut buffer: Vec<u8>, len: gl::types::GLsizei) -> String {
+    buffer.truncate(len.max(0) as usize);
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+fn query_attributes(program_id: gl::types::GLuint) -> Vec<ActiveAttribute> {
+    let count = get_program_int(program_id, gl::ACTIVE_ATTRIBUTES);
+    let max_len = get_program_int(program_id, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH).max(1);
+    let mut attributes = Vec::with_capacity(count.max(0) as usize);
+    for i in 0..count.max(0) as u32 {
+        let mut buffer = vec![0u8; max_len as usize];
+        let mut len = 0;
+        let mut size = 0;
+        let mut ty = 0;
+        let location = unsafe {
+            gl::GetActiveAttrib(
+                program_id, i, max_len, &mut len, &mut size, &mut ty,
+                buffer.as_mut_ptr() as *mut gl::types::GLchar);
+            gl::GetAttribLocation(program_id, buffer.as_ptr() as *const gl::types::GLchar)
+        };
+        attributes.push(ActiveAttribute {
+            name: name_from_buffer(buffer, len),
+            ty: GlslType::from_gl(ty),
+            size,
+            location,
+        });
+    }
+    attributes.sort_by_key(|a| a.location);
+    attributes
+}
+
+fn query_uniforms(program_id: gl::types::GLuint) -> Vec<ActiveUniform> {
+    let count = get_program_int(program_id, gl::ACTIVE_UNIFORMS);
+    let max_len = get_program_int(program_id, gl::ACTIVE_UNIFORM_MAX_LENGTH).max(1);
+    let mut uniforms = Vec::with_capacity(count.max(0) as usize);
+    for i in 0..count.max(0) as u32 {
+        let mut buffer = vec![0u8; max_len as usize];
+        let mut len = 0;
+        let mut size = 0;
+        let mut ty = 0;
+        let mut block_index: gl::types::GLint = -1;
+        let location = unsafe {
+            gl::GetActiveUniform(
+                program_id, i, max_len, &mut len, &mut size, &mut ty,
+                buffer.as_mut_ptr() as *mut gl::types::GLchar);
+            gl::GetActiveUniformsiv(
+                program_id, 1, &i, gl::UNIFORM_BLOCK_INDEX, &mut block_index);
+            gl::GetUniformLocation(program_id, buffer.as_ptr() as *const gl::types::GLchar)
+        };
+        uniforms.push(ActiveUniform {
+            name: name_from_buffer(buffer, len),
+            ty: GlslType::from_gl(ty),
+            size,
+            location,
+            block_index: if block_index < 0 { None } else { Some(block_index as u32) },
+        });
+    }
+    uniforms
+}
+
+fn query_blocks(program_id: gl::types::GLuint, uniforms: &[ActiveUniform])
+    -> Vec<UniformBlock> {
+    let count = get_program_int(program_id, gl::ACTIVE_UNIFORM_BLOCKS);
+    let max_len = get_program_int(
+        program_id, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH).max(1);
+    let mut blocks = Vec::with_capacity(count.max(0) as usize);
+    for index in 0..count.max(0) as u32 {
+        let mut buffer = vec![0u8; max_len as usize];
+        let mut len = 0;
+        let mut binding = 0;
+        let mut data_size = 0;
+        unsafe {
+            gl::GetActiveUniformBlockName(
+                program_id, index, max_len, &mut len,
+                buffer.as_mut_ptr() as *mut gl::types::GLchar);
+            gl::GetActiveUniformBlockiv(
+                program_id, index, gl::UNIFORM_BLOCK_BINDING, &mut binding);
+            gl::GetActiveUniformBlockiv(
+                program_id, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut data_size);
+        }
+        blocks.push(UniformBlock {
+            name: name_from_buffer(buffer, len),
+            index,
+            binding: binding as u32,
+            data_size,
+            uniforms: uniforms.iter()
+                .filter(|u| u.block_index == Some(index))
+                .map(|u| u.name.clone())
+                .collect(),
+        });
+    }
+    blocks
+}
@@ -1,42 +1,374 @@
 #![allow(dead_code)]
 
 extern crate gl;
+extern crate sdl2;
 
+use self::sdl2::video::Window;
 use graphics::Color;
+use graphics::framebuffer::Framebuffer;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendMode {
+    // Blending disabled, source replaces destination
+    None,
+    // Standard transparency, src * a + dst * (1 - a)
+    Alpha,
+    // For colors already multiplied by alpha
+    PremultipliedAlpha,
+    Additive,
+    Multiply,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DepthFunc {
+    Never = gl::NEVER,
+    Less = gl::LESS,
+    Equal = gl::EQUAL,
+    LessEqual = gl::LEQUAL,
+    Greater = gl::GREATER,
+    NotEqual = gl::NOTEQUAL,
+    GreaterEqual = gl::GEQUAL,
+    Always = gl::ALWAYS,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrontFace {
+    Clockwise = gl::CW,
+    CounterClockwise = gl::CCW,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+// Owns the GL pipeline state. All state changes should go through here so
+// redundant GL calls can be skipped and the state can be queried cheaply.
+// Must be created after the GL context and function pointers are loaded.
 pub struct Renderer {
-    
+    clear_color: Color,
+    clear_depth: f64,
+    clear_stencil: i32,
+    // Which buffers begin_frame clears
+    clear_mask: gl::types::GLbitfield,
+    blend_mode: BlendMode,
+    depth_test: bool,
+    depth_write: bool,
+    depth_func: DepthFunc,
+    cull_mode: CullMode,
+    front_face: FrontFace,
+    viewport: Viewport,
+    // Framebuffer being drawn to, 0 is the window
+    render_target: u32,
+    // Viewport to go back to when drawing to the window again
+    window_viewport: Viewport,
+    in_frame: bool,
+    frame_count: u64,
 }
 
 impl Renderer {
-    fn new() -> Self {
-        Renderer {}
+    pub fn new(width: i32, height: i32) -> Self {
+        let renderer = Renderer {
+            clear_color: Color::new(0.0, 0.0, 0.0, 1.0),
+            clear_depth: 1.0,
+            clear_stencil: 0,
+            clear_mask: gl::COLOR_BUFFER_BIT,
+            blend_mode: BlendMode::None,
+            depth_test: false,
+            depth_write: true,
+            depth_func: DepthFunc::Less,
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            viewport: Viewport { x: 0, y: 0, width, height },
+            render_target: 0,
+            window_viewport: Viewport { x: 0, y: 0, width, height },
+            in_frame: false,
+            frame_count: 0,
+        };
+        // Push everything to GL so the cache matches, whatever state
+        // the context started in
+        renderer.apply_all();
+        renderer
+    }
+
+    fn apply_all(&self) {
+        let c = self.clear_color;
+        let v = self.viewport;
+        unsafe {
+            gl::ClearColor(c.r, c.g, c.b, c.a);
+            gl::ClearDepth(self.clear_depth);
+            gl::ClearStencil(self.clear_stencil);
+            gl::DepthMask(if self.depth_write { gl::TRUE } else { gl::FALSE });
+            gl::DepthFunc(self.depth_func as u32);
+            gl::FrontFace(self.front_face as u32);
+            gl::Viewport(v.x, v.y, v.width, v.height);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.render_target);
+            set_capability(gl::DEPTH_TEST, self.depth_test);
+        }
+        apply_blend_mode(self.blend_mode);
+        apply_cull_mode(self.cull_mode);
+    }
+
+    // Clears the buffers selected with set_clear_buffers
+    pub fn begin_frame(&mut self) {
+        if self.in_frame {
+            println!(
+                "Warning: begin_frame called twice without end_frame ({}:{}:{})",
+                file!(), line!(), column!());
+        }
+        self.in_frame = true;
+        self.clear();
+    }
+
+    // Swaps what was rendered onto the screen.
+    // Remember that if vsync is enabled this is blocking.
+    pub fn end_frame(&mut self, window: &Window) {
+        if !self.in_frame {
+            println!(
+                "Warning: end_frame called without begin_frame ({}:{}:{})",
+                file!(), line!(), column!());
+        }
+        self.in_frame = false;
+        self.frame_count += 1;
+        window.gl_swap_window();
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn clear(&self) {
+        unsafe {
+            gl::Clear(self.clear_mask);
+        }
     }
 
-    fn set_clear_color(&self, color: &Color) {
+    pub fn set_clear_buffers(&mut self, color: bool, depth: bool, stencil: bool) {
+        self.clear_mask = 0;
+        if color { self.clear_mask |= gl::COLOR_BUFFER_BIT; }
+        if depth { self.clear_mask |= gl::DEPTH_BUFFER_BIT; }
+        if stencil { self.clear_mask |= gl::STENCIL_BUFFER_BIT; }
+    }
+
+    pub fn set_clear_color(&mut self, color: Color) {
+        if self.clear_color == color {
+            return;
+        }
+        self.clear_color = color;
         unsafe {
-            gl::ClearColor(color.a, color.b, color.g, color.r);
+            gl::ClearColor(color.r, color.g, color.b, color.a);
         }
     }
 
-    fn clear(&self) {
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    pub fn set_clear_depth(&mut self, depth: f64) {
+        if self.clear_depth == depth {
+            return;
+        }
+        self.clear_depth = depth;
         unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::ClearDepth(depth);
         }
     }
 
-    fn enable_blending() {
+    pub fn set_clear_stencil(&mut self, stencil: i32) {
+        if self.clear_stencil == stencil {
+            return;
+        }
+        self.clear_stencil = stencil;
         unsafe {
-            // Set a blend function so we can have transparency
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-            // Enable blending
-            gl::Enable(gl::BLEND);
+            gl::ClearStencil(stencil);
         }
     }
+
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        if self.blend_mode == mode {
+            return;
+        }
+        self.blend_mode = mode;
+        apply_blend_mode(mode);
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        if self.depth_test == enabled {
+            return;
+        }
+        self.depth_test = enabled;
+        unsafe {
+            set_capability(gl::DEPTH_TEST, enabled);
+        }
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    pub fn set_depth_write(&mut self, enabled: bool) {
+        if self.depth_write == enabled {
+            return;
+        }
+        self.depth_write = enabled;
+        unsafe {
+            gl::DepthMask(if enabled { gl::TRUE } else { gl::FALSE });
+        }
+    }
+
+    pub fn set_depth_func(&mut self, func: DepthFunc) {
+        if self.depth_func == func {
+            return;
+        }
+        self.depth_func = func;
+        unsafe {
+            gl::DepthFunc(func as u32);
+        }
+    }
+
+    pub fn set_cull_mode(&mut self, mode: CullMode) {
+        if self.cull_mode == mode {
+            return;
+        }
+        self.cull_mode = mode;
+        apply_cull_mode(mode);
+    }
+
+    pub fn cull_mode(&self) -> CullMode {
+        self.cull_mode
+    }
+
+    pub fn set_front_face(&mut self, front_face: FrontFace) {
+        if self.front_face == front_face {
+            return;
+        }
+        self.front_face = front_face;
+        unsafe {
+            gl::FrontFace(front_face as u32);
+        }
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        if self.render_target == 0 {
+            self.window_viewport = viewport;
+        }
+        if self.viewport == viewport {
+            return;
+        }
+        self.viewport = viewport;
+        unsafe {
+            gl::Viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+        }
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    // Call when the window is resized, covers the whole window. While a
+    // framebuffer is the render target the window viewport is only updated
+    // for when drawing goes back to the window.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        let viewport = Viewport { x: 0, y: 0, width, height };
+        if self.render_target == 0 {
+            self.set_viewport(viewport);
+        } else {
+            self.window_viewport = viewport;
+        }
+    }
+
+    // Draws into target from now on, None for the window. The viewport is
+    // set to cover all of the target.
+    pub fn set_render_target(&mut self, target: Option<&Framebuffer>) {
+        let (id, viewport) = match target {
+            Some(framebuffer) => (framebuffer.id(), Viewport {
+                x: 0,
+                y: 0,
+                width: framebuffer.width() as i32,
+                height: framebuffer.height() as i32,
+            }),
+            None => (0, self.window_viewport),
+        };
+        if self.render_target != id {
+            self.render_target = id;
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            }
+        }
+        self.set_viewport(viewport);
+    }
+
+    // Id of the framebuffer being drawn to, 0 for the window
+    pub fn render_target(&self) -> u32 {
+        self.render_target
+    }
+
+    // Width divided by height of the viewport, for cameras
+    pub fn aspect_ratio(&self) -> f32 {
+        if self.viewport.height == 0 {
+            return 1.0;
+        }
+        self.viewport.width as f32 / self.viewport.height as f32
+    }
 }
 
-impl Drop for Renderer {
-    fn drop(&mut self) {
+unsafe fn set_capability(capability: gl::types::GLenum, enabled: bool) {
+    if enabled {
+        gl::Enable(capability);
+    } else {
+        gl::Disable(capability);
+    }
+}
 
+fn apply_blend_mode(mode: BlendMode) {
+    unsafe {
+        match mode {
+            BlendMode::None => {
+                gl::Disable(gl::BLEND);
+                return;
+            }
+            BlendMode::Alpha =>
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+            BlendMode::PremultipliedAlpha =>
+                gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive =>
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE),
+            BlendMode::Multiply =>
+                gl::BlendFunc(gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA),
+        }
+        gl::Enable(gl::BLEND);
     }
-}
\ No newline at end of file
+}
+
+fn apply_cull_mode(mode: CullMode) {
+    unsafe {
+        let face = match mode {
+            CullMode::None => {
+                gl::Disable(gl::CULL_FACE);
+                return;
+            }
+            CullMode::Front => gl::FRONT,
+            CullMode::Back => gl::BACK,
+            CullMode::FrontAndBack => gl::FRONT_AND_BACK,
+        };
+        gl::CullFace(face);
+        gl::Enable(gl::CULL_FACE);
+    }
+}
@@ -0,0 +1,26 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D inputTexture;
+uniform sampler2D sceneTexture;
+uniform vec2 texelSize;
+uniform float intensity = 1.0;
+// Texels between blur taps, should match bloom_blur.frag
+uniform float spread = 1.5;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// Second half of bloom: blurs the glow vertically and adds it to the scene
+void main()
+{
+    vec2 offset = vec2(0.0, texelSize.y * spread);
+    vec3 glow = texture(inputTexture, TexCoord).rgb * weights[0];
+    for (int i = 1; i < 5; i++) {
+        glow += texture(inputTexture, TexCoord + offset * float(i)).rgb * weights[i];
+        glow += texture(inputTexture, TexCoord - offset * float(i)).rgb * weights[i];
+    }
+    vec4 scene = texture(sceneTexture, TexCoord);
+    FragColor = vec4(scene.rgb + glow * intensity, scene.a);
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D inputTexture;
+uniform vec2 texelSize;
+// Luminance where pixels start to glow
+uniform float threshold = 0.7;
+// Texels between blur taps
+uniform float spread = 1.5;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+vec3 bright(vec2 uv)
+{
+    vec3 color = texture(inputTexture, uv).rgb;
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    return color * max(luma - threshold, 0.0) / max(luma, 0.0001);
+}
+
+// First half of bloom: keeps the bright parts and blurs them horizontally
+void main()
+{
+    vec2 offset = vec2(texelSize.x * spread, 0.0);
+    vec3 sum = bright(TexCoord) * weights[0];
+    for (int i = 1; i < 5; i++) {
+        sum += bright(TexCoord + offset * float(i)) * weights[i];
+        sum += bright(TexCoord - offset * float(i)) * weights[i];
+    }
+    FragColor = vec4(sum, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+// View and projection set by Camera::upload and SpriteBatch
+uniform mat4 view = mat4(1.0);
+uniform mat4 projection = mat4(1.0);
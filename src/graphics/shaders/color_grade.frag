@@ -0,0 +1,32 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D inputTexture;
+// lutSize slices side by side, red across each slice, green up,
+// blue from slice to slice
+uniform sampler2D lut;
+uniform float lutSize = 16.0;
+uniform float strength = 1.0;
+
+vec3 grade(vec3 color)
+{
+    color = clamp(color, 0.0, 1.0);
+    float blue = color.b * (lutSize - 1.0);
+    float slice0 = floor(blue);
+    float slice1 = min(slice0 + 1.0, lutSize - 1.0);
+    // Half texel insets keep filtering inside one slice
+    vec2 uv = vec2(
+        (color.r * (lutSize - 1.0) + 0.5) / (lutSize * lutSize),
+        (color.g * (lutSize - 1.0) + 0.5) / lutSize);
+    vec3 a = texture(lut, uv + vec2(slice0 / lutSize, 0.0)).rgb;
+    vec3 b = texture(lut, uv + vec2(slice1 / lutSize, 0.0)).rgb;
+    return mix(a, b, blue - slice0);
+}
+
+void main()
+{
+    vec4 color = texture(inputTexture, TexCoord);
+    FragColor = vec4(mix(color.rgb, grade(color.rgb), strength), color.a);
+}
\ No newline at end of file
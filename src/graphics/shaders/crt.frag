@@ -0,0 +1,32 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D inputTexture;
+uniform vec2 texelSize;
+// Barrel distortion, 0 is a flat screen
+uniform float curvature = 0.08;
+uniform float scanlineIntensity = 0.25;
+// Strength of the RGB phosphor mask
+uniform float maskIntensity = 0.15;
+
+void main()
+{
+    vec2 uv = TexCoord * 2.0 - 1.0;
+    uv += uv * dot(uv, uv) * curvature;
+    uv = uv * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec4 scene = texture(inputTexture, uv);
+    // Darkens every other row of source pixels
+    float line = sin(uv.y / texelSize.y * 3.14159265) * 0.5 + 0.5;
+    vec3 color = scene.rgb * (1.0 - scanlineIntensity * (1.0 - line));
+
+    vec3 mask = vec3(1.0 - maskIntensity);
+    mask[int(mod(gl_FragCoord.x, 3.0))] = 1.0;
+    FragColor = vec4(color * mask, scene.a);
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+#version 330 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in vec4 aColor;
+
+out vec4 VertexColor;
+
+#include "camera.glsl"
+
+void main()
+{
+    gl_Position = projection * view * vec4(aPos, 0.0, 1.0);
+    VertexColor = aColor;
+}
\ No newline at end of file
@@ -0,0 +1,314 @@
+// Errors from loading, preprocessing, compiling and linking shaders, with
+// the driver's info log split into per-line diagnostics.
+#![allow(dead_code)]
+
+use std::fmt;
+use std::io;
+use graphics::shaders::ShaderKind;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// One line of a driver info log
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // File name (after #line remapping) or source string number
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}:", file)?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, "{}:", column)?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if self.file.is_some() || self.line.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    // A shader file couldn't be read
+    Io(String, io::Error),
+    // Bad #include or #version, or an include couldn't be found
+    Preprocess { source_name: String, message: String },
+    // Source has a nul byte so can't be passed to GL
+    InvalidSource { source_name: String },
+    // glCreateShader or glCreateProgram returned 0, usually no GL context
+    CreateFailed { source_name: String },
+    Compile {
+        stage: ShaderKind,
+        source_name: String,
+        diagnostics: Vec<Diagnostic>,
+        // Full info log, with source string numbers replaced by file names
+        log: String,
+    },
+    Link {
+        source_names: Vec<String>,
+        diagnostics: Vec<Diagnostic>,
+        log: String,
+    },
+}
+
+impl ShaderError {
+    pub fn compile(stage: ShaderKind, source_name: &str, log: &str) -> Self {
+        let log = trim_log(log);
+        ShaderError::Compile {
+            stage,
+            source_name: source_name.to_owned(),
+            diagnostics: parse_log(log),
+            log: log.to_owned(),
+        }
+    }
+
+    pub fn link(source_names: Vec<String>, log: &str) -> Self {
+        let log = trim_log(log);
+        ShaderError::Link {
+            source_names,
+            diagnostics: parse_log(log),
+            log: log.to_owned(),
+        }
+    }
+
+    // Empty for errors that didn't come from the driver
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match *self {
+            ShaderError::Compile { ref diagnostics, .. }
+                | ShaderError::Link { ref diagnostics, .. } => diagnostics,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderError::Io(ref path, ref e) => write!(f, "{}: {}", path, e),
+            ShaderError::Preprocess { ref source_name, ref message } =>
+                write!(f, "preprocessing {} failed: {}", source_name, message),
+            ShaderError::InvalidSource { ref source_name } =>
+                write!(f, "{} contains a nul byte", source_name),
+            ShaderError::CreateFailed { ref source_name } =>
+                write!(f, "couldn't create a GL object for {}", source_name),
+            ShaderError::Compile { stage, ref source_name, ref diagnostics, ref log } => {
+                let stage = match stage {
+                    ShaderKind::Vertex => "vertex",
+                    ShaderKind::Fragment => "fragment",
+                };
+                write!(f, "{} shader {} failed to compile", stage, source_name)?;
+                write_log(f, diagnostics, log)
+            }
+            ShaderError::Link { ref source_names, ref diagnostics, ref log } => {
+                write!(f, "program {} failed to link", source_names.join(" + "))?;
+                write_log(f, diagnostics, log)
+            }
+        }
+    }
+}
+
+// Drivers include the log's nul terminator in its length
+fn trim_log(log: &str) -> &str {
+    log.trim_matches(|c: char| c == '\0' || c.is_whitespace())
+}
+
+fn write_log(f: &mut fmt::Formatter, diagnostics: &[Diagnostic], log: &str)
+    -> fmt::Result {
+    if diagnostics.is_empty() {
+        if !log.is_empty() {
+            write!(f, ":\n{}", log)?;
+        }
+        return Ok(());
+    }
+    write!(f, ":")?;
+    for diagnostic in diagnostics.iter() {
+        write!(f, "\n  {}", diagnostic)?;
+    }
+    Ok(())
+}
+
+// So code that returns Result<_, String> can still use ? on shader results
+impl From<ShaderError> for String {
+    fn from(e: ShaderError) -> Self {
+        e.to_string()
+    }
+}
+
+// Splits an info log into diagnostics. Understands
+//   0:12(5): error: ...       Mesa
+//   0(12) : error C0000: ...  NVIDIA
+//   ERROR: 0:12: ...          AMD, Intel
+// where 0 may already be a file name. Lines in other formats are kept as
+// diagnostics without a location.
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    log.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(parse_line)
+        .collect()
+}
+
+fn parse_line(line: &str) -> Diagnostic {
+    let mut severity = None;
+    let mut rest = line;
+    for &(prefix, s) in [("ERROR: ", Severity::Error), ("WARNING: ", Severity::Warning)].iter() {
+        if rest.starts_with(prefix) {
+            severity = Some(s);
+            rest = &rest[prefix.len()..];
+        }
+    }
+
+    let (file, line_number, column, message) = match parse_location(rest) {
+        Some(parsed) => parsed,
+        None => (None, None, None, rest),
+    };
+
+    // "error: msg", "error C0000: msg", "warning: msg"
+    let mut message = message.trim_start();
+    let lower = message.to_lowercase();
+    for &(word, s) in [("error", Severity::Error), ("warning", Severity::Warning)].iter() {
+        if !lower.starts_with(word) {
+            continue;
+        }
+        if let Some(colon) = message.find(':') {
+            if message[word.len()..colon].trim().chars().all(|c| c.is_alphanumeric()) {
+                severity = severity.or(Some(s));
+                message = message[colon + 1..].trim_start();
+            }
+        }
+        break;
+    }
+
+    Diagnostic {
+        severity: severity.unwrap_or(Severity::Error),
+        file,
+        line: line_number,
+        column,
+        message: message.trim().to_owned(),
+    }
+}
+
+// (file, line, column, rest of the text)
+type Location<'a> = (Option<String>, Option<u32>, Option<u32>, &'a str);
+
+// file:line(column): or file:line: or file(line) : followed by the message
+fn parse_location(text: &str) -> Option<Location<'_>> {
+    let file_end = text.find([':', '('])?;
+    let file = &text[..file_end];
+    if file.is_empty() || file.contains(' ') {
+        return None;
+    }
+    let after_file = &text[file_end..];
+    let closing = if after_file.starts_with('(') { Some(')') } else { None };
+    let (line_number, mut rest) = take_number(&after_file[1..])?;
+    if let Some(c) = closing {
+        if !rest.starts_with(c) {
+            return None;
+        }
+        rest = &rest[1..];
+    }
+    let mut column = None;
+    if closing.is_none() && rest.starts_with('(') {
+        let (c, after) = take_number(&rest[1..])?;
+        if !after.starts_with(')') {
+            return None;
+        }
+        column = Some(c);
+        rest = &after[1..];
+    }
+    let rest = rest.trim_start();
+    if !rest.starts_with(':') {
+        return None;
+    }
+    Some((Some(file.to_owned()), Some(line_number), column, &rest[1..]))
+}
+
+fn take_number(text: &str) -> Option<(u32, &str)> {
+    let digits = text.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let number = text[..digits].parse().ok()?;
+    Some((number, &text[digits..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(line: &str, severity: Severity, file: &str, line_number: u32,
+             column: Option<u32>, message: &str) {
+        let d = parse_line(line);
+        assert_eq!(d.severity, severity, "{}", line);
+        assert_eq!(d.file.as_deref(), Some(file), "{}", line);
+        assert_eq!(d.line, Some(line_number), "{}", line);
+        assert_eq!(d.column, column, "{}", line);
+        assert_eq!(d.message, message, "{}", line);
+    }
+
+    #[test]
+    fn mesa() {
+        check("0:12(5): error: `color' undeclared",
+              Severity::Error, "0", 12, Some(5), "`color' undeclared");
+        check("sprite.frag:3(10): warning: `unused' declared but not used",
+              Severity::Warning, "sprite.frag", 3, Some(10), "`unused' declared but not used");
+    }
+
+    #[test]
+    fn nvidia() {
+        check("0(12) : error C1008: undefined variable \"color\"",
+              Severity::Error, "0", 12, None, "undefined variable \"color\"");
+        check("camera.glsl(4) : warning C7022: unrecognized profile specifier",
+              Severity::Warning, "camera.glsl", 4, None, "unrecognized profile specifier");
+    }
+
+    #[test]
+    fn amd_and_intel() {
+        check("ERROR: 0:12: 'color' : undeclared identifier",
+              Severity::Error, "0", 12, None, "'color' : undeclared identifier");
+        check("WARNING: debug.vert:7: 'x' : unused",
+              Severity::Warning, "debug.vert", 7, None, "'x' : unused");
+    }
+
+    #[test]
+    fn unknown_lines_keep_the_text() {
+        let d = parse_line("error: linking with uncompiled shader");
+        assert_eq!(d.severity, Severity::Error);
+        assert_eq!(d.file, None);
+        assert_eq!(d.line, None);
+        assert_eq!(d.message, "linking with uncompiled shader");
+
+        let d = parse_line("Vertex info");
+        assert_eq!(d.file, None);
+        assert_eq!(d.message, "Vertex info");
+    }
+
+    #[test]
+    fn parse_log_skips_blank_lines_and_nul() {
+        let error = ShaderError::compile(
+            ShaderKind::Fragment, "sprite.frag",
+            "0:1(1): error: a\n\n0:2(1): warning: b\n\0");
+        let diagnostics = error.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].to_string(), "0:2:1: warning: b");
+    }
+}
@@ -0,0 +1,46 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D inputTexture;
+uniform vec2 texelSize;
+// Longest edge search in texels
+uniform float spanMax = 8.0;
+uniform float reduceMul = 0.125;
+uniform float reduceMin = 0.0078125;
+
+// Console FXAA by Timothy Lottes, blurs along edges found from luma
+void main()
+{
+    vec3 rgbNW = texture(inputTexture, TexCoord + vec2(-1.0, -1.0) * texelSize).rgb;
+    vec3 rgbNE = texture(inputTexture, TexCoord + vec2(1.0, -1.0) * texelSize).rgb;
+    vec3 rgbSW = texture(inputTexture, TexCoord + vec2(-1.0, 1.0) * texelSize).rgb;
+    vec3 rgbSE = texture(inputTexture, TexCoord + vec2(1.0, 1.0) * texelSize).rgb;
+    vec4 rgbaM = texture(inputTexture, TexCoord);
+
+    vec3 toLuma = vec3(0.299, 0.587, 0.114);
+    float lumaNW = dot(rgbNW, toLuma);
+    float lumaNE = dot(rgbNE, toLuma);
+    float lumaSW = dot(rgbSW, toLuma);
+    float lumaSE = dot(rgbSE, toLuma);
+    float lumaM = dot(rgbaM.rgb, toLuma);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * reduceMul, reduceMin);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-spanMax), vec2(spanMax)) * texelSize;
+
+    vec3 rgbA = 0.5 * (
+        texture(inputTexture, TexCoord + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(inputTexture, TexCoord + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(inputTexture, TexCoord - dir * 0.5).rgb +
+        texture(inputTexture, TexCoord + dir * 0.5).rgb);
+    float lumaB = dot(rgbB, toLuma);
+    FragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, rgbaM.a);
+}
\ No newline at end of file
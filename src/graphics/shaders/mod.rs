@@ -1,7 +1,32 @@
 extern crate gl;
 
 use std::ffi::{CString, CStr};
+use std::fs;
+use std::path::PathBuf;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub mod preprocess;
+pub mod error;
+
+pub use self::preprocess::{Defines, Preprocessed};
+pub use self::error::ShaderError;
+
+// Where the .glsl files live in the source tree, used for hot reloading
+pub static SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/graphics/shaders");
+
+static HOT_RELOAD: AtomicBool = AtomicBool::new(false);
+
+// Load shaders from SHADER_DIR instead of the copies built into the binary,
+// and relink programs when the files change. Only has an effect in debug
+// builds, and only on programs created after it's turned on.
+pub fn set_hot_reload(enabled: bool) {
+    HOT_RELOAD.store(enabled && cfg!(debug_assertions), Ordering::SeqCst);
+}
+
+pub fn hot_reload_enabled() -> bool {
+    HOT_RELOAD.load(Ordering::SeqCst)
+}
 
 pub static STANDARD_VERTEX_SOURCE_STR: &str = 
     include_str!("standard.vert");
@@ -9,24 +34,278 @@ pub static STANDARD_VERTEX_SOURCE_STR: &str =
 pub static STANDARD_FRAG_SOURCE_STR: &str = 
     include_str!("standard.frag");
 
+pub static SPRITE_VERTEX_SOURCE_STR: &str = 
+    include_str!("sprite.vert");
+
+pub static SPRITE_FRAG_SOURCE_STR: &str = 
+    include_str!("sprite.frag");
+
 pub static TRIANGLE_VERTEX_SOURCE_STR: &str = 
     include_str!("triangle.vert");
 
 pub static TRIANGLE_FRAG_SOURCE_STR: &str = 
     include_str!("triangle.frag");
 
+pub static DEBUG_VERTEX_SOURCE_STR: &str = 
+    include_str!("debug.vert");
+
+pub static DEBUG_FRAG_SOURCE_STR: &str = 
+    include_str!("debug.frag");
+
+pub static FULLSCREEN_VERTEX_SOURCE_STR: &str = 
+    include_str!("fullscreen.vert");
+
+pub static BLOOM_BLUR_FRAG_SOURCE_STR: &str = 
+    include_str!("bloom_blur.frag");
+
+pub static BLOOM_FRAG_SOURCE_STR: &str = 
+    include_str!("bloom.frag");
+
+pub static COLOR_GRADE_FRAG_SOURCE_STR: &str = 
+    include_str!("color_grade.frag");
+
+pub static VIGNETTE_FRAG_SOURCE_STR: &str = 
+    include_str!("vignette.frag");
+
+pub static CRT_FRAG_SOURCE_STR: &str = 
+    include_str!("crt.frag");
+
+pub static FXAA_FRAG_SOURCE_STR: &str = 
+    include_str!("fxaa.frag");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShaderKind {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderKind {
+    pub fn gl_kind(&self) -> gl::types::GLuint {
+        match *self {
+            ShaderKind::Vertex => gl::VERTEX_SHADER,
+            ShaderKind::Fragment => gl::FRAGMENT_SHADER,
+        }
+    }
+}
+
+// A shader file under SHADER_DIR along with the copy built into the binary
+#[derive(Clone, Copy, Debug)]
+pub struct ShaderFile {
+    pub kind: ShaderKind,
+    pub file_name: &'static str,
+    pub embedded: &'static str,
+}
+
+pub static STANDARD_VERTEX: ShaderFile = ShaderFile {
+    kind: ShaderKind::Vertex,
+    file_name: "standard.vert",
+    embedded: STANDARD_VERTEX_SOURCE_STR,
+};
+
+pub static STANDARD_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "standard.frag",
+    embedded: STANDARD_FRAG_SOURCE_STR,
+};
+
+pub static SPRITE_VERTEX: ShaderFile = ShaderFile {
+    kind: ShaderKind::Vertex,
+    file_name: "sprite.vert",
+    embedded: SPRITE_VERTEX_SOURCE_STR,
+};
+
+pub static SPRITE_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "sprite.frag",
+    embedded: SPRITE_FRAG_SOURCE_STR,
+};
+
+pub static TRIANGLE_VERTEX: ShaderFile = ShaderFile {
+    kind: ShaderKind::Vertex,
+    file_name: "triangle.vert",
+    embedded: TRIANGLE_VERTEX_SOURCE_STR,
+};
+
+pub static TRIANGLE_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "triangle.frag",
+    embedded: TRIANGLE_FRAG_SOURCE_STR,
+};
+
+pub static DEBUG_VERTEX: ShaderFile = ShaderFile {
+    kind: ShaderKind::Vertex,
+    file_name: "debug.vert",
+    embedded: DEBUG_VERTEX_SOURCE_STR,
+};
+
+pub static DEBUG_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "debug.frag",
+    embedded: DEBUG_FRAG_SOURCE_STR,
+};
+
+pub static FULLSCREEN_VERTEX: ShaderFile = ShaderFile {
+    kind: ShaderKind::Vertex,
+    file_name: "fullscreen.vert",
+    embedded: FULLSCREEN_VERTEX_SOURCE_STR,
+};
+
+pub static BLOOM_BLUR_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "bloom_blur.frag",
+    embedded: BLOOM_BLUR_FRAG_SOURCE_STR,
+};
+
+pub static BLOOM_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "bloom.frag",
+    embedded: BLOOM_FRAG_SOURCE_STR,
+};
+
+pub static COLOR_GRADE_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "color_grade.frag",
+    embedded: COLOR_GRADE_FRAG_SOURCE_STR,
+};
+
+pub static VIGNETTE_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "vignette.frag",
+    embedded: VIGNETTE_FRAG_SOURCE_STR,
+};
+
+pub static CRT_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "crt.frag",
+    embedded: CRT_FRAG_SOURCE_STR,
+};
+
+pub static FXAA_FRAG: ShaderFile = ShaderFile {
+    kind: ShaderKind::Fragment,
+    file_name: "fxaa.frag",
+    embedded: FXAA_FRAG_SOURCE_STR,
+};
+
+impl ShaderFile {
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(SHADER_DIR).join(self.file_name)
+    }
+
+    // Reads the file on disk
+    pub fn read(&self) -> Result<String, ShaderError> {
+        fs::read_to_string(self.path())
+            .map_err(|e| ShaderError::Io(self.path().display().to_string(), e))
+    }
+
+    // The file on disk with hot reload on, otherwise the embedded copy.
+    // Falls back to the embedded copy if the file can't be read.
+    pub fn source(&self) -> String {
+        if !hot_reload_enabled() {
+            return self.embedded.to_owned();
+        }
+        match self.read() {
+            Ok(source) => source,
+            Err(e) => {
+                println!(
+                    "Warning: {}, using built-in shader ({}:{}:{})",
+                    e, file!(), line!(), column!());
+                self.embedded.to_owned()
+            }
+        }
+    }
+
+    // Compiles with the given defines, reading files as source() does.
+    // Returns the shader and the names of every file it was built from,
+    // including itself and its includes.
+    pub fn compile(&self, defines: &Defines) -> Result<(Shader, Vec<String>), ShaderError> {
+        self.compile_source(&self.source(), defines, false)
+    }
+
+    // Like compile but only reads from disk, for hot reloading
+    pub fn compile_from_disk(&self, defines: &Defines)
+        -> Result<(Shader, Vec<String>), ShaderError> {
+        self.compile_source(&self.read()?, defines, true)
+    }
+
+    fn compile_source(&self, source: &str, defines: &Defines, from_disk: bool)
+        -> Result<(Shader, Vec<String>), ShaderError> {
+        let preprocessed = self.preprocess_source(source, defines, from_disk)?;
+        let shader = self.compile_preprocessed(&preprocessed)?;
+        Ok((shader, preprocessed.files))
+    }
+
+    // Expands includes and defines without compiling, reading files as
+    // source() does
+    pub fn preprocess(&self, defines: &Defines) -> Result<Preprocessed, ShaderError> {
+        self.preprocess_source(&self.source(), defines, false)
+    }
+
+    fn preprocess_source(&self, source: &str, defines: &Defines, from_disk: bool)
+        -> Result<Preprocessed, ShaderError> {
+        preprocess::preprocess(
+            self.file_name, source, defines, |name| include_source(name, from_disk))
+            .map_err(|message| ShaderError::Preprocess {
+                source_name: self.file_name.to_owned(),
+                message,
+            })
+    }
+
+    pub fn compile_preprocessed(&self, preprocessed: &Preprocessed)
+        -> Result<Shader, ShaderError> {
+        let c_source = CString::new(preprocessed.source.as_str())
+            .map_err(|_| ShaderError::InvalidSource { source_name: self.file_name.to_owned() })?;
+        Shader::compile(&c_source, self.kind, self.file_name)
+            .map_err(|e| match e {
+                ShaderError::Compile { stage, source_name, log, .. } =>
+                    ShaderError::compile(stage, &source_name, &preprocessed.remap_log(&log)),
+                e => e,
+            })
+    }
+}
+
+// Snippets that can be #included, by file name
+pub static INCLUDES: &[(&str, &str)] = &[
+    ("camera.glsl", include_str!("camera.glsl")),
+];
+
+// Source of an #include. Read from SHADER_DIR with hot reload on, falling
+// back to the built-in copy unless from_disk is set.
+fn include_source(name: &str, from_disk: bool) -> Result<String, String> {
+    if hot_reload_enabled() || from_disk {
+        let path = PathBuf::from(SHADER_DIR).join(name);
+        match fs::read_to_string(&path) {
+            Ok(source) => { return Ok(source); }
+            Err(e) => {
+                if from_disk {
+                    return Err(format!("{}: {}", path.display(), e));
+                }
+                println!(
+                    "Warning: {}: {}, using built-in include ({}:{}:{})",
+                    path.display(), e, file!(), line!(), column!());
+            }
+        }
+    }
+    INCLUDES.iter()
+        .find(|&&(include, _)| include == name)
+        .map(|&(_, source)| source.to_owned())
+        .ok_or_else(|| format!("unknown include {}", name))
+}
+
 pub struct Shader {
     id: gl::types::GLuint,
 }
 
 impl Shader {
-    // Compile shader from string source
-    fn from_source(
-        source: &CStr,
-        kind: gl::types::GLuint
-    ) -> Result<Shader, String> {
-        
-        let id = unsafe { gl::CreateShader(kind) };
+    // Compile shader from string source. The shader object is deleted if
+    // compiling fails.
+    pub fn compile(source: &CStr, stage: ShaderKind, source_name: &str)
+        -> Result<Shader, ShaderError> {
+        let id = unsafe { gl::CreateShader(stage.gl_kind()) };
+        if id == 0 {
+            return Err(ShaderError::CreateFailed { source_name: source_name.to_owned() });
+        }
+        // From here on Drop cleans up
+        let shader = Shader { id };
         let mut success : gl::types::GLint = 1;
         unsafe {
             gl::ShaderSource(id, 1, &source.as_ptr(), ptr::null());
@@ -36,19 +315,19 @@ impl Shader {
         }
         // Compilation failed
         if success == 0 {
-            return Err(get_shader_error_log(id));
+            return Err(ShaderError::compile(stage, source_name, &get_shader_error_log(id)));
         }
 
         // Compilation succeeded
-        Ok(Shader { id })
+        Ok(shader)
     }
 
-    pub fn from_vert_source(source: &CStr) -> Result<Shader, String> {
-        Shader::from_source(source, gl::VERTEX_SHADER)
+    pub fn from_vert_source(source: &CStr) -> Result<Shader, ShaderError> {
+        Shader::compile(source, ShaderKind::Vertex, "vertex source")
     }
 
-    pub fn from_frag_source(source: &CStr) -> Result<Shader, String> {
-        Shader::from_source(source, gl::FRAGMENT_SHADER)
+    pub fn from_frag_source(source: &CStr) -> Result<Shader, ShaderError> {
+        Shader::compile(source, ShaderKind::Fragment, "fragment source")
     }
 
     pub fn id(&self) -> gl::types::GLuint {
@@ -87,6 +366,7 @@ fn get_shader_error_log(id: gl::types::GLuint) -> String {
                 error.as_ptr() as *mut gl::types::GLchar // Error buffer
             );
         }
-        error.to_string_lossy().into_owned()
+        // The log's own nul terminator ends up in the string
+        error.to_string_lossy().trim_end_matches('\0').to_owned()
 }
 
@@ -0,0 +1,322 @@
+// Resolves #include, injects #defines and keeps track of which file every
+// line came from, so compile errors point at the right file and line.
+//
+// Every file gets a GLSL source string number (the root is 0) and #line
+// directives are emitted around each include. remap_log turns those
+// numbers back into file names.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+// A set of #defines for one shader variant, e.g. TEXTURED and VERTEX_COLOR.
+// Kept sorted so the same set always gives the same key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Defines {
+    values: BTreeMap<String, String>,
+}
+
+impl Defines {
+    pub fn new() -> Self {
+        Defines { values: BTreeMap::new() }
+    }
+
+    // #define name 1
+    pub fn with(self, name: &str) -> Self {
+        self.with_value(name, "1")
+    }
+
+    pub fn with_value(mut self, name: &str, value: &str) -> Self {
+        self.values.insert(name.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    // e.g. "TEXTURED=1;VERTEX_COLOR=1", empty for no defines
+    pub fn key(&self) -> String {
+        self.values.iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
+
+pub struct Preprocessed {
+    pub source: String,
+    // File names by GLSL source string number, the root file first
+    pub files: Vec<String>,
+}
+
+impl Preprocessed {
+    // Replaces source string numbers in a compiler log with file names.
+    // Handles the usual driver formats: "0(12) : error ..." (NVIDIA),
+    // "0:12(5): error ..." (Mesa) and "ERROR: 0:12: ..." (AMD, Intel).
+    pub fn remap_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.remap_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn remap_line(&self, line: &str) -> String {
+        let mut start = 0;
+        for prefix in ["ERROR: ", "WARNING: "].iter() {
+            if line.starts_with(prefix) {
+                start = prefix.len();
+            }
+        }
+        let rest = &line[start..];
+        let digits = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return line.to_owned();
+        }
+        let after = &rest[digits..];
+        let line_digits = match after.bytes().next() {
+            Some(b':') | Some(b'(') =>
+                after[1..].bytes().take_while(|b| b.is_ascii_digit()).count(),
+            _ => 0,
+        };
+        if line_digits == 0 {
+            return line.to_owned();
+        }
+        match rest[..digits].parse::<usize>().ok().and_then(|i| self.files.get(i)) {
+            Some(name) => format!("{}{}{}", &line[..start], name, after),
+            None => line.to_owned(),
+        }
+    }
+}
+
+// Expands a shader. resolve returns the source of an included file by the
+// name used in the #include. The #version line is moved to the top with the
+// defines after it. Files included more than once are only expanded the
+// first time, like #pragma once.
+pub fn preprocess<F>(name: &str, source: &str, defines: &Defines, mut resolve: F)
+    -> Result<Preprocessed, String>
+    where F: FnMut(&str) -> Result<String, String> {
+    let mut out = String::with_capacity(source.len());
+    let mut files = vec![name.to_owned()];
+
+    let lines: Vec<&str> = source.lines().collect();
+    let version = lines.iter().position(|l| l.trim_start().starts_with("#version"));
+    let body_start = match version {
+        Some(i) => {
+            out.push_str(lines[i].trim());
+            out.push('\n');
+            i + 1
+        }
+        None => 0,
+    };
+    for (define, value) in defines.values.iter() {
+        out.push_str(&format!("#define {} {}\n", define, value));
+    }
+    // Lines before #version can only be comments, so they're dropped
+    emit_line_directive(&mut out, body_start + 1, 0);
+
+    let mut stack = vec![name.to_owned()];
+    expand(&lines[body_start..], body_start, 0, &mut stack, &mut files, &mut out, &mut resolve)?;
+    Ok(Preprocessed { source: out, files })
+}
+
+// Makes the next line report as line_number in source string file_index.
+// GLSL 3.30 numbers the line after "#line n" as n + 1, unlike C.
+fn emit_line_directive(out: &mut String, line_number: usize, file_index: usize) {
+    out.push_str(&format!("#line {} {}\n", line_number - 1, file_index));
+}
+
+// lines starts at line first_line + 1 of the file
+fn expand<F>(
+    lines: &[&str],
+    first_line: usize,
+    file_index: usize,
+    stack: &mut Vec<String>,
+    files: &mut Vec<String>,
+    out: &mut String,
+    resolve: &mut F,
+) -> Result<(), String>
+    where F: FnMut(&str) -> Result<String, String> {
+    for (i, line) in lines.iter().enumerate() {
+        let line_number = first_line + i + 1;
+        let here = || format!("{}:{}", stack[stack.len() - 1], line_number);
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("#version") {
+            return Err(format!("{}: #version is only allowed at the top of a shader", here()));
+        }
+        if !trimmed.starts_with("#include") {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+
+        let include = parse_include(trimmed)
+            .ok_or_else(|| format!("{}: expected #include \"file\"", here()))?;
+        if stack.contains(&include) {
+            return Err(format!(
+                "{}: include cycle {} -> {}", here(), stack.join(" -> "), include));
+        }
+        if files.contains(&include) {
+            // Already expanded, keep the line count the same
+            out.push('\n');
+            continue;
+        }
+
+        let source = resolve(&include).map_err(|e| format!("{}: {}", here(), e))?;
+        let index = files.len();
+        files.push(include.clone());
+        stack.push(include);
+        emit_line_directive(out, 1, index);
+        let included: Vec<&str> = source.lines().collect();
+        expand(&included, 0, index, stack, files, out, resolve)?;
+        stack.pop();
+        emit_line_directive(out, line_number + 1, file_index);
+    }
+    Ok(())
+}
+
+// #include "name" or #include <name>
+fn parse_include(line: &str) -> Option<String> {
+    let rest = line["#include".len()..].trim();
+    let close = match rest.chars().next() {
+        Some('"') => '"',
+        Some('<') => '>',
+        _ => { return None; }
+    };
+    let end = rest[1..].find(close)?;
+    let name = &rest[1..end + 1];
+    if name.is_empty() { None } else { Some(name.to_owned()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn run(root: &str, files: &[(&str, &str)], defines: &Defines) -> Result<Preprocessed, String> {
+        let files: HashMap<String, String> = files.iter()
+            .map(|&(name, source)| (name.to_owned(), source.to_owned()))
+            .collect();
+        preprocess("root.glsl", root, defines, |name| {
+            files.get(name).cloned().ok_or_else(|| format!("no file {}", name))
+        })
+    }
+
+    // Where the compiler would say each line of the output is, as
+    // (text, source string, line), following #line like GLSL 3.30 does
+    fn compiler_positions(source: &str) -> Vec<(String, usize, usize)> {
+        let mut positions = Vec::new();
+        let (mut file, mut line) = (0, 1);
+        for text in source.lines() {
+            if let Some(rest) = text.strip_prefix("#line ") {
+                let parts: Vec<usize> = rest.split(' ').map(|p| p.parse().unwrap()).collect();
+                line = parts[0] + 1;
+                file = parts[1];
+                continue;
+            }
+            positions.push((text.to_owned(), file, line));
+            line += 1;
+        }
+        positions
+    }
+
+    fn position_of(output: &Preprocessed, text: &str) -> (String, usize) {
+        let positions = compiler_positions(&output.source);
+        let &(_, file, line) = positions.iter().find(|p| p.0 == text)
+            .unwrap_or_else(|| panic!("{} not in output", text));
+        (output.files[file].clone(), line)
+    }
+
+    #[test]
+    fn line_numbers_follow_includes() {
+        let root = "// header comment\n#version 330 core\nfloat root_a;\n#include \"a.glsl\"\nfloat root_b;\n#include \"b.glsl\"\nfloat root_c;";
+        let a = "float a_1;\n#include \"b.glsl\"\nfloat a_3;";
+        let b = "\nfloat b_2;";
+        let output = run(root, &[("a.glsl", a), ("b.glsl", b)], &Defines::new()).unwrap();
+
+        assert!(output.source.starts_with("#version 330 core\n"));
+        assert_eq!(output.files, vec!["root.glsl", "a.glsl", "b.glsl"]);
+        assert_eq!(position_of(&output, "float root_a;"), ("root.glsl".to_owned(), 3));
+        assert_eq!(position_of(&output, "float a_1;"), ("a.glsl".to_owned(), 1));
+        assert_eq!(position_of(&output, "float b_2;"), ("b.glsl".to_owned(), 2));
+        assert_eq!(position_of(&output, "float a_3;"), ("a.glsl".to_owned(), 3));
+        assert_eq!(position_of(&output, "float root_b;"), ("root.glsl".to_owned(), 5));
+        assert_eq!(position_of(&output, "float root_c;"), ("root.glsl".to_owned(), 7));
+    }
+
+    #[test]
+    fn included_once() {
+        let root = "#version 330 core\n#include \"a.glsl\"\n#include \"a.glsl\"\nfloat end;";
+        let output = run(root, &[("a.glsl", "float a;")], &Defines::new()).unwrap();
+        assert_eq!(output.source.matches("float a;").count(), 1);
+        assert_eq!(position_of(&output, "float end;"), ("root.glsl".to_owned(), 4));
+    }
+
+    #[test]
+    fn include_cycle_is_an_error() {
+        let root = "#version 330 core\n#include \"a.glsl\"";
+        let a = "#include \"b.glsl\"";
+        let b = "\n#include <a.glsl>";
+        let error = run(root, &[("a.glsl", a), ("b.glsl", b)], &Defines::new())
+            .err().unwrap();
+        assert_eq!(error, "b.glsl:2: include cycle root.glsl -> a.glsl -> b.glsl -> a.glsl");
+
+        let error = run("#include \"root.glsl\"", &[], &Defines::new()).err().unwrap();
+        assert!(error.contains("include cycle"), "{}", error);
+    }
+
+    #[test]
+    fn include_errors_name_the_line() {
+        let root = "#version 330 core\n\n#include \"missing.glsl\"";
+        assert_eq!(run(root, &[], &Defines::new()).err().unwrap(),
+                   "root.glsl:3: no file missing.glsl");
+        assert_eq!(run("#include missing", &[], &Defines::new()).err().unwrap(),
+                   "root.glsl:1: expected #include \"file\"");
+        assert!(run("float a;\n#version 330 core\n#version 330 core", &[], &Defines::new())
+            .err().unwrap().starts_with("root.glsl:3: #version"));
+    }
+
+    #[test]
+    fn defines_after_version() {
+        let defines = Defines::new().with("TEXTURED").with_value("LIGHTS", "4");
+        let output = run("#version 330 core\nfloat a;", &[], &defines).unwrap();
+        let lines: Vec<&str> = output.source.lines().collect();
+        assert_eq!(&lines[..3], &["#version 330 core", "#define LIGHTS 4", "#define TEXTURED 1"]);
+        assert_eq!(position_of(&output, "float a;"), ("root.glsl".to_owned(), 2));
+    }
+
+    #[test]
+    fn defines_key_is_sorted() {
+        let a = Defines::new().with("B").with_value("A", "2");
+        let b = Defines::new().with_value("A", "2").with("B");
+        assert_eq!(a.key(), "A=2;B=1");
+        assert_eq!(a, b);
+        assert_eq!(Defines::new().key(), "");
+        assert!(a.contains("B") && !a.contains("C"));
+    }
+
+    #[test]
+    fn remap_log_formats() {
+        let output = Preprocessed {
+            source: String::new(),
+            files: vec!["root.glsl".to_owned(), "lib.glsl".to_owned()],
+        };
+        let log = "0(12) : error C0000: syntax error\n\
+                   1:7(3): error: `x' undeclared\n\
+                   ERROR: 1:4: 'y' : undeclared identifier\n\
+                   WARNING: 0:2: unused\n\
+                   5:3(1): error: unknown file\n\
+                   error: no position";
+        assert_eq!(output.remap_log(log),
+                   "root.glsl(12) : error C0000: syntax error\n\
+                    lib.glsl:7(3): error: `x' undeclared\n\
+                    ERROR: lib.glsl:4: 'y' : undeclared identifier\n\
+                    WARNING: root.glsl:2: unused\n\
+                    5:3(1): error: unknown file\n\
+                    error: no position");
+    }
+}
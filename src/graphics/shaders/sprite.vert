@@ -0,0 +1,16 @@
+#version 330 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in vec2 aTexCoord;
+layout (location = 2) in vec4 aColor;
+
+out vec2 TexCoord;
+out vec4 Tint;
+
+#include "camera.glsl"
+
+void main()
+{
+    gl_Position = projection * view * vec4(aPos, 0.0, 1.0);
+    TexCoord = aTexCoord;
+    Tint = aColor;
+}
\ No newline at end of file
@@ -2,7 +2,9 @@
 
 layout (location = 0) in vec3 Position;
 
+#include "camera.glsl"
+
 void main()
 {
-    gl_Position = vec4(Position, 1.0);
+    gl_Position = projection * view * vec4(Position, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,19 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D inputTexture;
+uniform float intensity = 0.5;
+// Where darkening starts, 0 is the center and 1 the corners
+uniform float radius = 0.75;
+uniform float softness = 0.45;
+uniform vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+
+void main()
+{
+    vec4 scene = texture(inputTexture, TexCoord);
+    float dist = distance(TexCoord, vec2(0.5)) * 1.41421356;
+    float amount = smoothstep(radius - softness, radius, dist) * intensity * color.a;
+    FragColor = vec4(mix(scene.rgb, color.rgb, amount), scene.a);
+}
\ No newline at end of file
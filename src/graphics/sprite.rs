@@ -0,0 +1,106 @@
+#![allow(dead_code)]
+
+extern crate gl;
+
+use std::ptr;
+use graphics::{program, buffer, textures, vertex_array};
+use graphics::vertex::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct ImageVertex {
+    #[vertex(location = 0)]
+    pos: [f32; 3],
+    #[vertex(location = 1)]
+    tex_coord: [f32; 2],
+}
+
+// Unit quad centered on the origin, with the standard shader program.
+// One Quad can draw any number of textures.
+pub struct Quad {
+    vbo: buffer::Buffer,
+    vao: vertex_array::VertexArrayObject,
+    ebo: buffer::Buffer,
+    program: program::Program,
+}
+
+impl Quad {
+    pub fn new() -> Result<Quad, String> {
+        let vertices: Vec<ImageVertex> = vec![
+            ImageVertex { pos: [0.5, 0.5, 0.0], tex_coord: [1.0, 1.0] }, // top right
+            ImageVertex { pos: [0.5, -0.5, 0.0], tex_coord: [1.0, 0.0] }, // bottom right
+            ImageVertex { pos: [-0.5, -0.5, 0.0], tex_coord: [0.0, 0.0] }, // bottom left
+            ImageVertex { pos: [-0.5, 0.5, 0.0], tex_coord: [0.0, 1.0] }, // top left
+        ];
+
+        let indices: Vec<u16> = vec![
+            0, 1, 3, // first triangle
+            1, 2, 3  // second triangle
+        ];
+
+        let vbo = buffer::Buffer::new(
+            buffer::BufferTarget::ArrayBuffer,
+            buffer::BufferUsage::StaticDraw,
+            vertices
+        );
+
+        let ebo = buffer::Buffer::new(
+            buffer::BufferTarget::ElementArrayBuffer,
+            buffer::BufferUsage::StaticDraw,
+            indices
+        );
+
+        let program = program::Program::standard()?;
+        program.set_used(true);
+        program.set_sampler("ourTexture", 0)?;
+        program.set_used(false);
+
+        let vao = vertex_array::VertexArrayObject::with_layout::<ImageVertex>(&vbo);
+        vao.validate(&program)?;
+
+        Ok(Quad { vbo, vao, ebo, program })
+    }
+
+    pub fn program(&self) -> &program::Program {
+        &self.program
+    }
+
+    pub fn draw(&self, texture: &textures::Texture) {
+        texture.bind(0);
+        self.program.set_used(true);
+        self.vao.bind();
+        self.ebo.bind();
+        unsafe {
+            gl::DrawElements(
+                gl::TRIANGLES, // Mode
+                6, // number of vertices to draw
+                gl::UNSIGNED_SHORT, // type
+                ptr::null() // buffer offset
+            );
+        }
+        self.vao.unbind();
+        self.ebo.unbind();
+        textures::Texture::unbind(0);
+        self.program.set_used(false);
+    }
+}
+
+// A texture drawn with a shared Quad
+pub struct Sprite<'a> {
+    texture: &'a textures::Texture,
+    quad: &'a Quad,
+}
+
+impl<'a> Sprite<'a> {
+    pub fn new(texture: &'a textures::Texture, quad: &'a Quad) -> Self {
+        Sprite { texture, quad }
+    }
+
+    pub fn texture(&self) -> &textures::Texture {
+        self.texture
+    }
+
+    pub fn draw(&self) {
+        self.quad.draw(self.texture);
+    }
+}
@@ -0,0 +1,378 @@
+#![allow(dead_code)]
+
+extern crate gl;
+extern crate nalgebra as na;
+
+use std::mem;
+use self::na::{Matrix4, Orthographic3, Vector2};
+use graphics::{buffer, program, textures, vertex_array, Color};
+use graphics::vertex::Vertex;
+
+// Indices are u16, so one flush can't address more than 65536 vertices
+pub const MAX_SPRITES_PER_FLUSH: usize = 65536 / 4;
+
+// Part of a texture in normalized coordinates, (0, 0) is bottom left
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub u0: f32, pub v0: f32,
+    pub u1: f32, pub v1: f32,
+}
+
+impl UvRect {
+    pub fn full() -> Self {
+        UvRect { u0: 0.0, v0: 0.0, u1: 1.0, v1: 1.0 }
+    }
+}
+
+// Parameters for one quad
+#[derive(Clone, Copy, Debug)]
+pub struct SpriteParams {
+    // Where the origin ends up in world space
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    // Radians, counter-clockwise around the origin
+    pub rotation: f32,
+    // Pivot as a fraction of size, (0.5, 0.5) is the center
+    pub origin: Vector2<f32>,
+    pub uv: UvRect,
+    // Multiplied with the texture color
+    pub tint: Color,
+    // Higher layers are drawn on top
+    pub layer: i32,
+}
+
+impl SpriteParams {
+    pub fn new(position: Vector2<f32>, size: Vector2<f32>) -> Self {
+        SpriteParams {
+            position,
+            size,
+            rotation: 0.0,
+            origin: Vector2::new(0.5, 0.5),
+            uv: UvRect::full(),
+            tint: Color::WHITE,
+            layer: 0,
+        }
+    }
+}
+
+// Locations match sprite.vert
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct SpriteVertex {
+    pos: [f32; 2],
+    tex_coord: [f32; 2],
+    color: Color,
+}
+
+// What the queue is sorted by, field order is sort priority
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    layer: i32,
+    texture_id: u32,
+    // Submission order, keeps sorting stable
+    order: usize,
+}
+
+struct QueuedSprite<'a> {
+    // Borrowed so the texture can't be dropped while it's queued
+    texture: &'a textures::Texture,
+    params: SpriteParams,
+    key: SortKey,
+}
+
+// Collects textured quads between begin and end and draws them with as few
+// draw calls as possible. Sprites are sorted by layer, then by texture, so
+// within one layer sprites with different textures may draw in any order.
+// Queued textures are borrowed for the batch's lifetime 'a.
+pub struct SpriteBatch<'a> {
+    vbo: buffer::TypedBuffer<SpriteVertex>,
+    ebo: buffer::Buffer,
+    vao: vertex_array::VertexArrayObject,
+    program: program::Program,
+    queue: Vec<QueuedSprite<'a>>,
+    vertices: Vec<SpriteVertex>,
+    view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    drawing: bool,
+    draw_calls: u32,
+}
+
+impl<'a> SpriteBatch<'a> {
+    pub fn new() -> Result<Self, String> {
+        let vbo = buffer::TypedBuffer::<SpriteVertex>::with_capacity(
+            buffer::BufferTarget::ArrayBuffer,
+            buffer::BufferUsage::StreamDraw,
+            MAX_SPRITES_PER_FLUSH * 4
+        );
+
+        // Index pattern for every quad is fixed, so build it once
+        let mut indices: Vec<u16> = Vec::with_capacity(MAX_SPRITES_PER_FLUSH * 6);
+        for i in 0..MAX_SPRITES_PER_FLUSH {
+            let base = (i * 4) as u16;
+            indices.extend_from_slice(
+                &[base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
+        let ebo = buffer::Buffer::new(
+            buffer::BufferTarget::ElementArrayBuffer,
+            buffer::BufferUsage::StaticDraw,
+            indices
+        );
+
+        let program = program::Program::sprite()?;
+        program.set_used(true);
+        program.set_sampler("spriteTexture", 0)?;
+        program.set_used(false);
+
+        let vao = vertex_array::VertexArrayObject::with_layout::<SpriteVertex>(vbo.buffer());
+        vao.validate(&program)?;
+
+        Ok(SpriteBatch {
+            vbo,
+            ebo,
+            vao,
+            program,
+            queue: Vec::new(),
+            vertices: Vec::new(),
+            view: Matrix4::identity(),
+            projection: Matrix4::identity(),
+            drawing: false,
+            draw_calls: 0,
+        })
+    }
+
+    // Projection with one unit per pixel and (0, 0) at the bottom left
+    pub fn pixel_projection(width: f32, height: f32) -> Matrix4<f32> {
+        Orthographic3::new(0.0, width, 0.0, height, -1.0, 1.0).to_homogeneous()
+    }
+
+    // Sprites are drawn with the same view and projection uniforms as
+    // Camera::upload, pass identity as view for screen space sprites
+    pub fn begin(&mut self, view: &Matrix4<f32>, projection: &Matrix4<f32>) {
+        if self.drawing {
+            println!(
+                "Warning: SpriteBatch::begin called twice without end ({}:{}:{})",
+                file!(), line!(), column!());
+        }
+        self.drawing = true;
+        self.view = *view;
+        self.projection = *projection;
+        self.queue.clear();
+        self.draw_calls = 0;
+    }
+
+    pub fn draw(&mut self, texture: &'a textures::Texture, params: SpriteParams) {
+        let key = SortKey { layer: params.layer, texture_id: texture.id(), order: self.queue.len() };
+        self.queue.push(QueuedSprite { texture, params, key });
+    }
+
+    // Sorts and draws everything queued since begin
+    pub fn end(&mut self) {
+        if !self.drawing {
+            println!(
+                "Warning: SpriteBatch::end called without begin ({}:{}:{})",
+                file!(), line!(), column!());
+            return;
+        }
+        self.drawing = false;
+        if self.queue.is_empty() {
+            return;
+        }
+
+        self.queue.sort_by_key(|s| s.key);
+
+        self.program.set_used(true);
+        let uniforms = self.program.set_uniform("view", &self.view)
+            .and_then(|_| self.program.set_uniform("projection", &self.projection));
+        if let Err(e) = uniforms {
+            println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!());
+        }
+        self.vao.bind();
+        self.ebo.bind();
+
+        let mut start = 0;
+        while start < self.queue.len() {
+            let end = (start + MAX_SPRITES_PER_FLUSH).min(self.queue.len());
+            self.flush(start, end);
+            start = end;
+        }
+
+        self.vao.unbind();
+        self.ebo.unbind();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        self.program.set_used(false);
+    }
+
+    // Uploads queue[start..end] and draws each run of one texture
+    fn flush(&mut self, start: usize, end: usize) {
+        self.vertices.clear();
+        for sprite in self.queue[start..end].iter() {
+            push_quad(&mut self.vertices, &sprite.params);
+        }
+        // Orphaning lets the driver hand out fresh storage instead of
+        // waiting on draws still using the last flush
+        self.vbo.stream_data(&self.vertices);
+
+        let mut run_start = start;
+        while run_start < end {
+            let texture = self.queue[run_start].texture;
+            let mut run_end = run_start + 1;
+            while run_end < end && self.queue[run_end].key.texture_id == texture.id() {
+                run_end += 1;
+            }
+            texture.bind(0);
+            unsafe {
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    ((run_end - run_start) * 6) as i32,
+                    gl::UNSIGNED_SHORT,
+                    // Byte offset of the run's first index
+                    ((run_start - start) * 6 * mem::size_of::<u16>()) as *const _
+                );
+            }
+            self.draw_calls += 1;
+            run_start = run_end;
+        }
+    }
+
+    // Draw calls issued by the last end
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls
+    }
+
+    // Sprites queued since begin
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+fn push_quad(vertices: &mut Vec<SpriteVertex>, params: &SpriteParams) {
+    let (sin, cos) = params.rotation.sin_cos();
+    let offset = Vector2::new(
+        params.origin.x * params.size.x, params.origin.y * params.size.y);
+    let uv = params.uv;
+    // Counter-clockwise from bottom left
+    let corners = [
+        (Vector2::new(0.0, 0.0), [uv.u0, uv.v0]),
+        (Vector2::new(params.size.x, 0.0), [uv.u1, uv.v0]),
+        (Vector2::new(params.size.x, params.size.y), [uv.u1, uv.v1]),
+        (Vector2::new(0.0, params.size.y), [uv.u0, uv.v1]),
+    ];
+    for &(corner, tex_coord) in corners.iter() {
+        let local = corner - offset;
+        let world = Vector2::new(
+            local.x * cos - local.y * sin,
+            local.x * sin + local.y * cos) + params.position;
+        vertices.push(SpriteVertex {
+            pos: [world.x, world.y],
+            tex_coord,
+            color: params.tint,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn quad(params: &SpriteParams) -> Vec<SpriteVertex> {
+        let mut vertices = Vec::new();
+        push_quad(&mut vertices, params);
+        vertices
+    }
+
+    fn assert_pos(vertex: &SpriteVertex, x: f32, y: f32) {
+        assert!((vertex.pos[0] - x).abs() < 1e-5 && (vertex.pos[1] - y).abs() < 1e-5,
+            "expected ({}, {}), got {:?}", x, y, vertex.pos);
+    }
+
+    #[test]
+    fn centered_quad_surrounds_position() {
+        let params = SpriteParams::new(Vector2::new(10.0, 20.0), Vector2::new(4.0, 2.0));
+        let v = quad(&params);
+        assert_eq!(v.len(), 4);
+        assert_pos(&v[0], 8.0, 19.0);
+        assert_pos(&v[1], 12.0, 19.0);
+        assert_pos(&v[2], 12.0, 21.0);
+        assert_pos(&v[3], 8.0, 21.0);
+    }
+
+    #[test]
+    fn origin_is_the_pivot() {
+        let mut params = SpriteParams::new(Vector2::new(0.0, 0.0), Vector2::new(2.0, 2.0));
+        params.origin = Vector2::new(0.0, 0.0);
+        let v = quad(&params);
+        assert_pos(&v[0], 0.0, 0.0);
+        assert_pos(&v[2], 2.0, 2.0);
+
+        params.origin = Vector2::new(1.0, 1.0);
+        let v = quad(&params);
+        assert_pos(&v[0], -2.0, -2.0);
+        assert_pos(&v[2], 0.0, 0.0);
+    }
+
+    #[test]
+    fn rotation_turns_around_origin() {
+        let mut params = SpriteParams::new(Vector2::new(5.0, 5.0), Vector2::new(2.0, 1.0));
+        params.origin = Vector2::new(0.0, 0.0);
+        params.rotation = FRAC_PI_2;
+        let v = quad(&params);
+        // Origin stays put, the x axis now points up
+        assert_pos(&v[0], 5.0, 5.0);
+        assert_pos(&v[1], 5.0, 7.0);
+        assert_pos(&v[2], 4.0, 7.0);
+        assert_pos(&v[3], 4.0, 5.0);
+    }
+
+    #[test]
+    fn uvs_and_tint_follow_corners() {
+        let mut params = SpriteParams::new(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0));
+        params.uv = UvRect { u0: 0.25, v0: 0.5, u1: 0.75, v1: 1.0 };
+        params.tint = Color::RED;
+        let v = quad(&params);
+        assert_eq!(v[0].tex_coord, [0.25, 0.5]);
+        assert_eq!(v[1].tex_coord, [0.75, 0.5]);
+        assert_eq!(v[2].tex_coord, [0.75, 1.0]);
+        assert_eq!(v[3].tex_coord, [0.25, 1.0]);
+        assert!(v.iter().all(|vertex| vertex.color == Color::RED));
+    }
+
+    #[test]
+    fn swapped_uvs_flip_the_sprite() {
+        let mut params = SpriteParams::new(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0));
+        params.uv = UvRect { u0: 1.0, v0: 1.0, u1: 0.0, v1: 0.0 };
+        let v = quad(&params);
+        // Positions don't change, only the texture is mirrored
+        assert_pos(&v[0], -0.5, -0.5);
+        assert_eq!(v[0].tex_coord, [1.0, 1.0]);
+        assert_eq!(v[2].tex_coord, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn sorts_by_layer_then_texture_then_order() {
+        let key = |layer, texture_id, order| SortKey { layer, texture_id, order };
+        let mut keys = vec![
+            key(1, 1, 0),
+            key(0, 2, 1),
+            key(0, 1, 2),
+            key(-1, 3, 3),
+            key(0, 2, 4),
+            key(0, 1, 5),
+        ];
+        keys.sort();
+        assert_eq!(keys, vec![
+            key(-1, 3, 3),
+            key(0, 1, 2),
+            key(0, 1, 5),
+            key(0, 2, 1),
+            key(0, 2, 4),
+            key(1, 1, 0),
+        ]);
+    }
+}
@@ -2,148 +2,386 @@
 
 extern crate image;
 extern crate gl;
-extern crate nalgebra as na;
 
-use std::{path, ptr, mem};
-use graphics::textures::image::GenericImage;
+use std::{path, mem};
+use graphics::textures::image::{DynamicImage, GenericImage};
 use std::os::raw::c_void;
-use graphics::{program, buffer, vertex_array, GLDataType};
 
-pub struct Texture {
-    tex_id: u32,
-    vbo: buffer::Buffer,
-    vao: vertex_array::VertexArrayObject,
-    ebo: buffer::Buffer,
-    program: program::Program,
+// From EXT_texture_filter_anisotropic, core only since GL 4.6
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Rgba8,
+    // Float formats take f32 pixel data, the 16 bit ones are stored as halfs
+    R16F,
+    Rgba16F,
+    R32F,
+    Rgba32F,
+    // Depth formats are for framebuffer attachments and can only be
+    // created empty. Depth24Stencil8 is also the stencil attachment.
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
 }
 
+impl TextureFormat {
+    pub fn channels(self) -> usize {
+        match self {
+            TextureFormat::R8 | TextureFormat::R16F | TextureFormat::R32F => 1,
+            TextureFormat::Depth24 | TextureFormat::Depth32F
+                | TextureFormat::Depth24Stencil8 => 1,
+            TextureFormat::Rg8 => 2,
+            TextureFormat::Rgb8 => 3,
+            TextureFormat::Rgba8 | TextureFormat::Rgba16F | TextureFormat::Rgba32F => 4,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self,
+            TextureFormat::R16F | TextureFormat::Rgba16F |
+            TextureFormat::R32F | TextureFormat::Rgba32F)
+    }
+
+    pub fn is_depth(self) -> bool {
+        matches!(self,
+            TextureFormat::Depth24 | TextureFormat::Depth32F | TextureFormat::Depth24Stencil8)
+    }
+
+    pub fn has_stencil(self) -> bool {
+        self == TextureFormat::Depth24Stencil8
+    }
+
+    // (internal format, pixel format, data type) for glTexImage2D
+    fn gl_formats(self, srgb: bool) -> (u32, u32, u32) {
+        match self {
+            TextureFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            TextureFormat::Rg8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgb8 =>
+                (if srgb { gl::SRGB8 } else { gl::RGB8 }, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba8 =>
+                (if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 }, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::R16F => (gl::R16F, gl::RED, gl::FLOAT),
+            TextureFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            TextureFormat::R32F => (gl::R32F, gl::RED, gl::FLOAT),
+            TextureFormat::Rgba32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+            TextureFormat::Depth24 =>
+                (gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+            TextureFormat::Depth32F =>
+                (gl::DEPTH_COMPONENT32F, gl::DEPTH_COMPONENT, gl::FLOAT),
+            TextureFormat::Depth24Stencil8 =>
+                (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+        }
+    }
+}
 
-struct Pos3D {
-    x: f32, y: f32, z: f32,
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    // Blocky, good for pixel art
+    Nearest,
+    Linear,
+    // Linear between mipmap levels too, turns on mipmaps
+    Trilinear,
 }
 
-struct TexCoord {
-    u: u16, v: u16,
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    Repeat = gl::REPEAT,
+    MirroredRepeat = gl::MIRRORED_REPEAT,
+    ClampToEdge = gl::CLAMP_TO_EDGE,
+    ClampToBorder = gl::CLAMP_TO_BORDER,
 }
 
-struct ImageVertex {
-    pos: Pos3D, tex_coord: TexCoord,
+// How a texture is created, defaults match what Texture::load always did
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureOptions {
+    filter: Filter,
+    wrap_s: Wrap,
+    wrap_t: Wrap,
+    mipmaps: bool,
+    anisotropy: f32,
+    srgb: bool,
+    format: TextureFormat,
+    flip_vertically: bool,
 }
 
-static IMAGE_VERTEX_STRIDE: isize = mem::size_of::<ImageVertex>() as isize;
+impl TextureOptions {
+    pub fn new() -> Self {
+        TextureOptions {
+            filter: Filter::Linear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            mipmaps: false,
+            anisotropy: 1.0,
+            srgb: false,
+            format: TextureFormat::Rgba8,
+            flip_vertically: true,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    // Sets both directions
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self
+    }
+
+    pub fn with_wrap_st(mut self, wrap_s: Wrap, wrap_t: Wrap) -> Self {
+        self.wrap_s = wrap_s;
+        self.wrap_t = wrap_t;
+        self
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    // Clamped to what the driver supports, 1.0 turns it off
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy.max(1.0);
+        self
+    }
+
+    // Only Rgb8 and Rgba8 have sRGB versions
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn with_format(mut self, format: TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    // Image files are stored top row first, GL expects the bottom row first.
+    // Only used when loading images, raw pixels are uploaded as given.
+    pub fn with_flip_vertically(mut self, flip: bool) -> Self {
+        self.flip_vertically = flip;
+        self
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    // Sized internal format, with sRGB applied where the format has it
+    pub fn gl_internal_format(&self) -> u32 {
+        self.gl_formats().0
+    }
+
+    fn gl_formats(&self) -> (u32, u32, u32) {
+        let srgb = self.srgb
+            && matches!(self.format, TextureFormat::Rgb8 | TextureFormat::Rgba8);
+        self.format.gl_formats(srgb)
+    }
+
+    fn uses_mipmaps(&self) -> bool {
+        self.mipmaps || self.filter == Filter::Trilinear
+    }
+
+    fn min_filter(&self) -> u32 {
+        match (self.filter, self.uses_mipmaps()) {
+            (Filter::Nearest, false) => gl::NEAREST,
+            (Filter::Nearest, true) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, false) => gl::LINEAR,
+            (Filter::Linear, true) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Trilinear, _) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn mag_filter(&self) -> u32 {
+        match self.filter {
+            Filter::Nearest => gl::NEAREST,
+            _ => gl::LINEAR,
+        }
+    }
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        TextureOptions::new()
+    }
+}
+
+// A GL texture object, with no geometry attached.
+// Use graphics::sprite or graphics::sprite_batch to draw it.
+pub struct Texture {
+    tex_id: u32,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
 
 impl Texture {
     pub fn load<P>(path: P) -> Result<Texture, String>
         where P: AsRef<path::Path>  {
-        let img = match image::open(path) {
-            Ok(i) => i.flipv(), // Flip for GL
-            Err(e) => { return Err(e.to_string()); }
+        Texture::load_with_options(path, &TextureOptions::new())
+    }
+
+    pub fn load_with_options<P>(path: P, options: &TextureOptions)
+        -> Result<Texture, String>
+        where P: AsRef<path::Path>  {
+        match image::open(path) {
+            Ok(img) => Texture::from_image(&img, options),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // Decodes an image file already in memory, e.g. from include_bytes!
+    pub fn load_from_memory(bytes: &[u8], options: &TextureOptions)
+        -> Result<Texture, String> {
+        match image::load_from_memory(bytes) {
+            Ok(img) => Texture::from_image(&img, options),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // Converts the image to the format in options
+    pub fn from_image(img: &DynamicImage, options: &TextureOptions)
+        -> Result<Texture, String> {
+        let flipped;
+        let img = if options.flip_vertically {
+            flipped = img.flipv(); // Flip for GL
+            &flipped
+        } else {
+            img
         };
+        let (width, height) = img.dimensions();
+        let format = options.format;
+        if format.is_float() {
+            // Widen 8 bit channels to 0.0..1.0
+            let bytes = image_bytes(img, format.channels());
+            let floats: Vec<f32> = bytes.iter().map(|b| *b as f32 / 255.0).collect();
+            Texture::from_floats(width, height, &floats, options)
+        } else {
+            Texture::from_bytes(width, height, &image_bytes(img, format.channels()), options)
+        }
+    }
+
+    // Uploads 8 bit pixel data, rows bottom first, tightly packed with
+    // as many channels as options' format has
+    pub fn from_bytes(width: u32, height: u32, pixels: &[u8], options: &TextureOptions)
+        -> Result<Texture, String> {
+        check_not_depth(options.format)?;
+        if options.format.is_float() {
+            return Err(format!(
+                "{:?} needs float pixel data, use Texture::from_floats", options.format));
+        }
+        check_len(width, height, pixels.len(), options.format)?;
+        Ok(Texture::create(width, height, pixels.as_ptr() as *const c_void, options))
+    }
+
+    // Uploads float pixel data for the float formats
+    pub fn from_floats(width: u32, height: u32, pixels: &[f32], options: &TextureOptions)
+        -> Result<Texture, String> {
+        check_not_depth(options.format)?;
+        if !options.format.is_float() {
+            return Err(format!(
+                "{:?} needs 8 bit pixel data, use Texture::from_bytes", options.format));
+        }
+        check_len(width, height, pixels.len(), options.format)?;
+        Ok(Texture::create(width, height, pixels.as_ptr() as *const c_void, options))
+    }
+
+    // Allocates a texture without initializing its pixels
+    pub fn empty(width: u32, height: u32, options: &TextureOptions) -> Texture {
+        Texture::create(width, height, ::std::ptr::null(), options)
+    }
+
+    fn create(width: u32, height: u32, pixels: *const c_void, options: &TextureOptions)
+        -> Texture {
+        let (internal_format, pixel_format, data_type) = options.gl_formats();
+
         let mut tex_id: u32 = 0;
         unsafe {
             gl::GenTextures(1, &mut tex_id);
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, tex_id);
             gl::TexParameteri(
-                gl::TEXTURE_2D, 
-                gl::TEXTURE_WRAP_S, 
-                gl::REPEAT as i32
-            );	
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                options.wrap_s as i32
+            );
             gl::TexParameteri(
-                gl::TEXTURE_2D, 
-                gl::TEXTURE_WRAP_T, 
-                gl::REPEAT as i32
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                options.wrap_t as i32
             );
             gl::TexParameteri(
-                gl::TEXTURE_2D, 
-                gl::TEXTURE_MIN_FILTER, 
-                gl::LINEAR as i32
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                options.min_filter() as i32
             );
             gl::TexParameteri(
-                gl::TEXTURE_2D, 
-                gl::TEXTURE_MAG_FILTER, 
-                gl::LINEAR as i32
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                options.mag_filter() as i32
             );
+            if options.anisotropy > 1.0 {
+                let mut max: f32 = 0.0;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+                // max stays 0 if the extension isn't there
+                if max >= 1.0 {
+                    gl::TexParameterf(
+                        gl::TEXTURE_2D,
+                        TEXTURE_MAX_ANISOTROPY,
+                        options.anisotropy.min(max)
+                    );
+                }
+            }
+            // Rows of 1 and 3 channel formats aren't always 4 byte aligned
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0, // Mipmap level
-                gl::RGBA8 as i32, // Format
-                img.dimensions().0 as i32, // Width
-                img.dimensions().1 as i32, // Height
+                internal_format as i32, // Format
+                width as i32, // Width
+                height as i32, // Height
                 0, // Legacy
-                gl::RGBA, // Format
-                gl::UNSIGNED_BYTE, // Datatype
-                img.raw_pixels().as_ptr() as *const c_void
+                pixel_format, // Format
+                data_type, // Datatype
+                pixels
             );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if options.uses_mipmaps() {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
-        
-        let vertices: Vec<ImageVertex> = vec![
-            ImageVertex { // top right
-                pos: Pos3D { x: 0.5, y: 0.5, z: 0.0 },
-                tex_coord: TexCoord { u: 1, v: 1}
-            },
-            ImageVertex { // bottom right
-                pos: Pos3D { x: 0.5, y: -0.5, z: 0.0 },
-                tex_coord: TexCoord { u: 1, v: 0}
-            },
-            ImageVertex { // bottom left
-                pos: Pos3D { x: -0.5, y: -0.5, z: 0.0 },
-                tex_coord: TexCoord { u: 0, v: 0}
-            },
-            ImageVertex { // top left
-                pos: Pos3D { x: -0.5, y: 0.5, z: 0.0 },
-                tex_coord: TexCoord { u: 0, v: 1}
-            }
-        ];
-
-        let indices: Vec<u16> = vec![
-            0, 1, 3, // first triangle
-            1, 2, 3  // second triangle 
-        ];
-
-        let vbo = buffer::Buffer::new(
-            buffer::BufferTarget::ArrayBuffer,
-            buffer::BufferUsage::StaticDraw,
-            vertices
-        );
-
-        let ebo = buffer::Buffer::new(
-            buffer::BufferTarget::ElementArrayBuffer,
-            buffer::BufferUsage::StaticDraw,
-            indices
-        );
-
-        let vao = vertex_array::VertexArrayObject::new(&vbo);
-        vao.set_attribute(
-            &vbo,
-            0, // index of attrib (layout = 0)
-            3, // number of components per attrib
-            GLDataType::Float, // data type
-            false, // normalized
-            IMAGE_VERTEX_STRIDE, // stride
-            0
-        );
-        vao.set_attribute(
-            &vbo,
-            1, // index of attrib (layout = 0)
-            2, // number of components per attrib
-            GLDataType::UnsignedShort, // data type
-            false, // normalized
-            IMAGE_VERTEX_STRIDE, // stride
-            mem::size_of::<Pos3D>()
-        );
-
-        let program = program::Program::standard().unwrap();
-
-        Ok(Texture { 
+        Texture {
             tex_id,
-            vbo,
-            vao,
-            ebo,
-            program
-        })
+            width,
+            height,
+            format: options.format,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.tex_id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
     }
 
     pub fn bind(&self, slot: u32) {
@@ -153,29 +391,59 @@ impl Texture {
         }
     }
 
-    pub fn unbind(&self) {
+    // Unbinds whatever texture is in the slot
+    pub fn unbind(slot: u32) {
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, self.tex_id);
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
         }
     }
+}
 
-    pub fn draw(&self) {
-        self.bind(0);
-        self.program.set_used(true);
-        self.vao.bind();
-        self.ebo.bind();
+impl Drop for Texture {
+    fn drop(&mut self) {
         unsafe {
-            gl::DrawElements(
-                gl::TRIANGLES, // Mode
-                6, // number of vertices to draw
-                gl::UNSIGNED_SHORT, // type
-                ptr::null() // buffer offset
-            );
+            gl::DeleteTextures(1, &self.tex_id);
         }
-        self.vao.unbind();
-        self.ebo.unbind();
-        self.unbind();
-        self.program.set_used(false);
+    }
+}
+
+fn check_len(width: u32, height: u32, len: usize, format: TextureFormat)
+    -> Result<(), String> {
+    let expected = width as usize * height as usize * format.channels();
+    if len != expected {
+        return Err(format!(
+            "{}x{} {:?} texture needs {} values, got {}",
+            width, height, format, expected, len));
+    }
+    Ok(())
+}
+
+fn check_not_depth(format: TextureFormat) -> Result<(), String> {
+    if format.is_depth() {
+        return Err(format!(
+            "{:?} textures can't be uploaded to, create them with Texture::empty", format));
+    }
+    Ok(())
+}
+
+fn image_bytes(img: &DynamicImage, channels: usize) -> Vec<u8> {
+    match channels {
+        1 => img.to_luma().into_raw(),
+        2 => img.to_luma_alpha().into_raw(),
+        3 => img.to_rgb().into_raw(),
+        _ => img.to_rgba().into_raw(),
+    }
+}
+
+// Bytes per pixel for a format, useful when sizing pixel buffers
+pub fn bytes_per_pixel(format: TextureFormat) -> usize {
+    if format.is_depth() {
+        4
+    } else if format.is_float() {
+        format.channels() * mem::size_of::<f32>()
+    } else {
+        format.channels()
     }
 }
 
@@ -192,4 +460,4 @@ impl Texture {
     gl::UNSIGNED_BYTE, // datatype
     self.img.raw_pixels().as_ptr() as *const c_void
 );
-*/
\ No newline at end of file
+*/
@@ -0,0 +1,147 @@
+extern crate gl;
+extern crate nalgebra as na;
+
+use self::na::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+// Types that can be uploaded with Program::set_uniform.
+// nalgebra matrices are column-major like GL, so none are transposed.
+pub trait UniformValue {
+    // Uploads to a location of the program currently in use. Unsafe because
+    // it calls GL, which needs a current context on this thread.
+    #[allow(clippy::missing_safety_doc)]
+    unsafe fn upload(&self, location: i32);
+}
+
+// Texture unit for a sampler uniform, e.g. Sampler(0) for GL_TEXTURE0
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampler(pub u32);
+
+impl UniformValue for Sampler {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1i(location, self.0 as i32);
+    }
+}
+
+impl UniformValue for f32 {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1f(location, *self);
+    }
+}
+
+impl UniformValue for i32 {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1i(location, *self);
+    }
+}
+
+impl UniformValue for u32 {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1ui(location, *self);
+    }
+}
+
+impl UniformValue for bool {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1i(location, *self as i32);
+    }
+}
+
+impl UniformValue for Vector2<f32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform2fv(location, 1, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Vector3<f32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform3fv(location, 1, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Vector4<f32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform4fv(location, 1, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Vector2<i32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform2iv(location, 1, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Vector3<i32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform3iv(location, 1, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Vector4<i32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform4iv(location, 1, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Matrix2<f32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::UniformMatrix2fv(location, 1, gl::FALSE, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Matrix3<f32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::UniformMatrix3fv(location, 1, gl::FALSE, self.as_slice().as_ptr());
+    }
+}
+
+impl UniformValue for Matrix4<f32> {
+    unsafe fn upload(&self, location: i32) {
+        gl::UniformMatrix4fv(location, 1, gl::FALSE, self.as_slice().as_ptr());
+    }
+}
+
+// Arrays, set with the array's name or the name of its first element
+
+impl UniformValue for [f32] {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1fv(location, self.len() as i32, self.as_ptr());
+    }
+}
+
+impl UniformValue for [i32] {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform1iv(location, self.len() as i32, self.as_ptr());
+    }
+}
+
+impl UniformValue for [Vector2<f32>] {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform2fv(location, self.len() as i32, self.as_ptr() as *const f32);
+    }
+}
+
+impl UniformValue for [Vector3<f32>] {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform3fv(location, self.len() as i32, self.as_ptr() as *const f32);
+    }
+}
+
+impl UniformValue for [Vector4<f32>] {
+    unsafe fn upload(&self, location: i32) {
+        gl::Uniform4fv(location, self.len() as i32, self.as_ptr() as *const f32);
+    }
+}
+
+impl UniformValue for [Matrix4<f32>] {
+    unsafe fn upload(&self, location: i32) {
+        gl::UniformMatrix4fv(
+            location, self.len() as i32, gl::FALSE, self.as_ptr() as *const f32);
+    }
+}
+
+impl UniformValue for [Sampler] {
+    unsafe fn upload(&self, location: i32) {
+        let slots: Vec<i32> = self.iter().map(|s| s.0 as i32).collect();
+        gl::Uniform1iv(location, slots.len() as i32, slots.as_ptr());
+    }
+}
@@ -0,0 +1,123 @@
+// Vertex layouts described by the vertex type itself, so VAOs don't have to
+// hand-compute strides and offsets. Usually derived, see ras_derive.
+#![allow(dead_code)]
+
+extern crate nalgebra as na;
+
+use std::mem;
+use self::na::{Vector2, Vector3, Vector4};
+use graphics::GLDataType;
+
+pub use ras_derive::Vertex;
+
+// Rust types that can be a vertex attribute
+pub trait AttributeType {
+    const COMPONENTS: i32;
+    const DATA_TYPE: GLDataType;
+}
+
+macro_rules! attribute_types {
+    ($($ty:ty => $data_type:expr),*) => {
+        $(
+            impl AttributeType for $ty {
+                const COMPONENTS: i32 = 1;
+                const DATA_TYPE: GLDataType = $data_type;
+            }
+            impl AttributeType for [$ty; 1] {
+                const COMPONENTS: i32 = 1;
+                const DATA_TYPE: GLDataType = $data_type;
+            }
+            impl AttributeType for [$ty; 2] {
+                const COMPONENTS: i32 = 2;
+                const DATA_TYPE: GLDataType = $data_type;
+            }
+            impl AttributeType for [$ty; 3] {
+                const COMPONENTS: i32 = 3;
+                const DATA_TYPE: GLDataType = $data_type;
+            }
+            impl AttributeType for [$ty; 4] {
+                const COMPONENTS: i32 = 4;
+                const DATA_TYPE: GLDataType = $data_type;
+            }
+        )*
+    }
+}
+
+attribute_types! {
+    f32 => GLDataType::Float,
+    f64 => GLDataType::Double,
+    i8 => GLDataType::Byte,
+    u8 => GLDataType::UnsignedByte,
+    i16 => GLDataType::Short,
+    u16 => GLDataType::UnsignedShort,
+    i32 => GLDataType::Int,
+    u32 => GLDataType::UnsignedInt
+}
+
+impl AttributeType for Vector2<f32> {
+    const COMPONENTS: i32 = 2;
+    const DATA_TYPE: GLDataType = GLDataType::Float;
+}
+
+impl AttributeType for Vector3<f32> {
+    const COMPONENTS: i32 = 3;
+    const DATA_TYPE: GLDataType = GLDataType::Float;
+}
+
+impl AttributeType for Vector4<f32> {
+    const COMPONENTS: i32 = 4;
+    const DATA_TYPE: GLDataType = GLDataType::Float;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub components: i32,
+    pub data_type: GLDataType,
+    pub normalized: bool,
+    // Set with VertexAttribIPointer, read as int/uint in the shader
+    pub integer: bool,
+    // Byte offset within the vertex
+    pub offset: usize,
+    // 0 advances per vertex, n once per n instances
+    pub divisor: u32,
+}
+
+impl VertexAttribute {
+    pub fn of<T: AttributeType>(location: u32, offset: usize) -> Self {
+        VertexAttribute {
+            location,
+            components: T::COMPONENTS,
+            data_type: T::DATA_TYPE,
+            normalized: false,
+            integer: false,
+            offset,
+            divisor: 0,
+        }
+    }
+
+    pub fn with_normalized(mut self, normalized: bool) -> Self {
+        self.normalized = normalized;
+        self
+    }
+
+    pub fn with_integer(mut self, integer: bool) -> Self {
+        self.integer = integer;
+        self
+    }
+
+    pub fn with_divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+}
+
+// A vertex type whose attributes VertexArrayObject::with_layout can set up.
+// Implementors should be repr(C).
+pub trait Vertex: Copy {
+    fn attributes() -> Vec<VertexAttribute>;
+
+    fn stride() -> usize {
+        mem::size_of::<Self>()
+    }
+}
@@ -1,11 +1,24 @@
 extern crate gl;
 
+use std::cell::RefCell;
 use std::os::raw::c_void;
-use graphics::{buffer, GLDataType};
+use graphics::{buffer, program, GLDataType};
+use graphics::vertex::{Vertex, VertexAttribute};
 
+// What was set for one attribute index, kept to check against programs
+#[derive(Clone, Copy, Debug)]
+pub struct AttributeLayout {
+    pub index: u32,
+    pub num_components: i32,
+    pub normalized: bool,
+    // Set with VertexAttribIPointer
+    pub integer: bool,
+    pub divisor: u32,
+}
 
 pub struct VertexArrayObject {
     id: u32,
+    layout: RefCell<Vec<AttributeLayout>>,
 }
 
 impl VertexArrayObject {
@@ -24,7 +37,49 @@ impl VertexArrayObject {
             gl::GenVertexArrays(1, &mut id);
         }
         vbo.unbind();
-        VertexArrayObject { id }
+        VertexArrayObject { id, layout: RefCell::new(Vec::new()) }
+    }
+
+    // VAO with every attribute of V read from vbo
+    pub fn with_layout<V: Vertex>(vbo: &buffer::Buffer) -> Self {
+        let vao = VertexArrayObject::new(vbo);
+        vao.add_layout::<V>(vbo);
+        vao
+    }
+
+    // Adds V's attributes from another buffer, e.g. per-instance data next
+    // to per-vertex data
+    pub fn add_layout<V: Vertex>(&self, vbo: &buffer::Buffer) {
+        let stride = V::stride() as isize;
+        for attribute in V::attributes() {
+            self.set_vertex_attribute(vbo, &attribute, stride);
+        }
+    }
+
+    pub fn set_vertex_attribute(&self, vbo: &buffer::Buffer,
+        attribute: &VertexAttribute, stride: isize) {
+        if attribute.integer {
+            if attribute.data_type.is_integer() {
+                self.set_integer_attribute(
+                    vbo, attribute.location, attribute.components, attribute.data_type,
+                    stride, attribute.offset);
+            } else {
+                println!(
+                    "Warning: attribute {} is marked integer but isn't integer data, \
+                    converting to float ({}:{}:{})",
+                    attribute.location, file!(), line!(), column!());
+                self.set_attribute(
+                    vbo, attribute.location, attribute.components, attribute.data_type,
+                    attribute.normalized, stride, attribute.offset);
+            }
+        } else {
+            self.set_attribute(
+                vbo, attribute.location, attribute.components, attribute.data_type,
+                attribute.normalized, stride, attribute.offset);
+        }
+        if attribute.divisor != 0 {
+            self.set_divisor(attribute.location, attribute.divisor);
+        }
     }
 
     pub fn bind(&self) {
@@ -39,6 +94,8 @@ impl VertexArrayObject {
         }
     }
 
+    // Mirrors glVertexAttribPointer, so it takes all of its arguments
+    #[allow(clippy::too_many_arguments)]
     pub fn set_attribute(&self, vbo: &buffer::Buffer,
         layout_index: u32, num_components: i32, data_type: GLDataType,
         normalized: bool, stride: isize, byte_offset: usize) {
@@ -65,6 +122,110 @@ impl VertexArrayObject {
         }
         self.unbind();
         vbo.unbind();
+
+        self.record(AttributeLayout {
+            index: layout_index,
+            num_components,
+            normalized,
+            integer: false,
+            divisor: 0,
+        });
+    }
+
+    // For attributes the shader reads as int, ivec, uint or uvec. The data
+    // reaches the shader unconverted.
+    pub fn set_integer_attribute(&self, vbo: &buffer::Buffer,
+        layout_index: u32, num_components: i32, data_type: GLDataType,
+        stride: isize, byte_offset: usize) {
+        vbo.bind();
+        self.bind();
+        unsafe {
+            gl::EnableVertexAttribArray(layout_index);
+            gl::VertexAttribIPointer(
+                layout_index,
+                num_components,
+                data_type as u32,
+                stride as gl::types::GLint,
+                byte_offset as *const c_void
+            );
+        }
+        self.unbind();
+        vbo.unbind();
+
+        self.record(AttributeLayout {
+            index: layout_index,
+            num_components,
+            normalized: false,
+            integer: true,
+            divisor: 0,
+        });
+    }
+
+    // Advance the attribute once per divisor instances, 0 for per vertex
+    pub fn set_divisor(&self, layout_index: u32, divisor: u32) {
+        self.bind();
+        unsafe {
+            gl::VertexAttribDivisor(layout_index, divisor);
+        }
+        self.unbind();
+        if let Some(a) = self.layout.borrow_mut().iter_mut().find(|a| a.index == layout_index) {
+            a.divisor = divisor;
+        }
+    }
+
+    fn record(&self, attribute: AttributeLayout) {
+        let mut layout = self.layout.borrow_mut();
+        layout.retain(|a| a.index != attribute.index);
+        layout.push(attribute);
+    }
+
+    pub fn layout(&self) -> Vec<AttributeLayout> {
+        self.layout.borrow().clone()
+    }
+
+    // Checks that every attribute the program reads is enabled here without
+    // more components than the shader's type has. Fewer is fine, GL fills in
+    // the missing ones from (0, 0, 0, 1). Attributes set here that the
+    // program doesn't use are fine too, the driver may have optimized them
+    // out.
+    pub fn validate(&self, program: &program::Program) -> Result<(), String> {
+        let layout = self.layout.borrow();
+        let mut errors = Vec::new();
+        for attribute in program.info().attributes.iter() {
+            // Built-ins like gl_VertexID have no location
+            if attribute.location < 0 {
+                continue;
+            }
+            let count = attribute.ty.locations() * attribute.size.max(1) as u32;
+            for i in 0..count {
+                let index = attribute.location as u32 + i;
+                match layout.iter().find(|a| a.index == index) {
+                    None => errors.push(format!(
+                        "{} (location {}) is not enabled", attribute.name, index)),
+                    Some(a) if a.integer != attribute.ty.is_integer() => {
+                        errors.push(format!(
+                            "{} (location {}) is {} in the shader but set as {}",
+                            attribute.name, index,
+                            if attribute.ty.is_integer() { "integer" } else { "float" },
+                            if a.integer { "integer" } else { "float" }));
+                    }
+                    Some(a) if a.num_components > attribute.ty.components() => {
+                        errors.push(format!(
+                            "{} (location {}) has {} components, layout has {}",
+                            attribute.name, index, attribute.ty.components(),
+                            a.num_components));
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "VAO {} doesn't match program {}: {}",
+                self.id, program.id(), errors.join(", ")))
+        }
     }
 }
 
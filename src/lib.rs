@@ -1,4 +1,16 @@
+#[macro_use]
+extern crate serde_derive;
+extern crate ras_derive;
+
+// Lets code generated by ras_derive name this crate as ::ras from inside it
+extern crate self as ras;
+
 pub mod events;
 pub mod game;
 pub mod controller;
-pub mod graphics;
\ No newline at end of file
+pub mod graphics;
+pub mod physics;
+pub mod tween;
+pub mod clock;
+pub mod save;
+pub mod util;
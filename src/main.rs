@@ -1,13 +1,14 @@
 extern crate sdl2;
 extern crate sdl2_sys;
 extern crate gl;
-extern crate nalgebra as na;
 
 // Associated library
 extern crate ras;
 
 // For graphics
-use ras::graphics::textures;
+use ras::graphics::{atlas, capture, debug_draw, mesh, postprocess, program, sprite, sprite_batch, textures};
+use ras::graphics::Color;
+use ras::graphics::renderer::{Renderer, BlendMode};
 
 // For exiting process
 use std::process;
@@ -16,7 +17,7 @@ use std::process;
 use std::time::{Duration, Instant};
 
 // For SDL2 interaction
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 use sdl2::video::GLProfile;
 
@@ -64,30 +65,46 @@ fn main() {
         enable_vsync(true);
     }
 
+    // Shaders reload from src/graphics/shaders when edited, debug builds only
+    ras::graphics::shaders::set_hot_reload(true);
+    // Linked shaders are kept between runs when the driver supports it
+    ras::graphics::program_cache::set_cache_dir(Some("cache/shaders"));
+
     //*** Render Test Code
-    let tri = ras::graphics::Triangles::new(
+    let triangle_program = program::Program::triangle().unwrap();
+    let tri = mesh::Mesh::new(&mesh::MeshData::new(
         vec![
-            -0.5, -0.5, 0.0,
-            0.5, -0.5, 0.0,
-            0.0, 0.5, 0.0
-        ]);
-
-    let smiley = textures::Texture::load("res/awesomeface.png")
+            mesh::StandardVertex::new([-0.5, -0.5, 0.0], [0.0, 0.0], [0.0, 0.0, 1.0]),
+            mesh::StandardVertex::new([0.5, -0.5, 0.0], [1.0, 0.0], [0.0, 0.0, 1.0]),
+            mesh::StandardVertex::new([0.0, 0.5, 0.0], [0.5, 1.0], [0.0, 0.0, 1.0]),
+        ],
+        Vec::new(),
+        mesh::Topology::Triangles)).unwrap();
+
+    let smiley_texture = textures::Texture::load("res/awesomeface.png")
                     .unwrap();
+    let quad = sprite::Quad::new().unwrap();
+    let smiley = sprite::Sprite::new(&smiley_texture, &quad);
+    let mut debug_renderer = debug_draw::DebugRenderer::new().unwrap();
+    let sprite_atlas = example_atlas().unwrap();
+    let mut sprites = sprite_batch::SpriteBatch::new().unwrap();
     //***
 
     // Set flag for when to stop program
     let mut is_running = true;
 
     // GL initializations
-    unsafe {
-        // Set clear color
-        gl::ClearColor(0.24, 0.4, 0.27, 1.0);
-        // Set a blend function so we can have transparency
-        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-        // Enable blending
-        gl::Enable(gl::BLEND);
-    }
+    let (width, height) = window.drawable_size();
+    let mut renderer = Renderer::new(width as i32, height as i32);
+    renderer.set_clear_color(Color::new(0.24, 0.4, 0.27, 1.0));
+    game.set_aspect(renderer.aspect_ratio());
+    // Blend so we can have transparency
+    renderer.set_blend_mode(BlendMode::Alpha);
+    // Scene is drawn offscreen so effects can be applied on the way to the window
+    let mut post_chain = postprocess::PostChain::with_defaults(width, height).unwrap();
+    // F12 saves a screenshot, F11 starts and stops recording frames
+    let mut take_screenshot = false;
+    let mut recorder = capture::FrameRecorder::new("recordings");
 
     // Start frame timer
     let mut now = Instant::now();
@@ -98,6 +115,24 @@ fn main() {
                 Event::Quit {..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
                     is_running = false;
                 }
+                Event::Window {win_event: WindowEvent::SizeChanged(..), ..} => {
+                    let (width, height) = window.drawable_size();
+                    renderer.resize(width as i32, height as i32);
+                    game.set_aspect(renderer.aspect_ratio());
+                    if let Err(e) = post_chain.resize(width, height) {
+                        println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!());
+                    }
+                }
+                Event::KeyDown {keycode: Some(Keycode::F12), repeat: false, ..} => {
+                    take_screenshot = true;
+                }
+                Event::KeyDown {keycode: Some(Keycode::F11), repeat: false, ..} => {
+                    match recorder.toggle() {
+                        Ok(true) => println!("Recording frames"),
+                        Ok(false) => println!("Stopped recording"),
+                        Err(e) => println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!()),
+                    }
+                }
                 _ => {}
             }
         }
@@ -108,23 +143,53 @@ fn main() {
 
         game.update(delta_time_ms);
 
+        game.post_effects().apply(&mut post_chain);
+
         // Clear screen
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
-        }
+        post_chain.begin(&mut renderer);
+        renderer.begin_frame();
 
+        triangle_program.set_used(true);
         tri.draw();
+        triangle_program.set_used(false);
         smiley.draw();
+        game.draw_sprites(&mut sprites, &sprite_atlas);
+        post_chain.end(&mut renderer);
+        // Shapes queued during the update, on top of everything else
+        debug_renderer.render(&mut renderer, &game.debug_view_projection());
+
+        // Read back before the swap, the back buffer is undefined after it
+        let (width, height) = window.drawable_size();
+        if take_screenshot {
+            take_screenshot = false;
+            match capture::screenshot(width, height, "screenshots") {
+                Ok(path) => println!("Saved {}", path.display()),
+                Err(e) => println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!()),
+            }
+        }
+        recorder.capture_frame(width, height);
 
         // Swap what we just rendered onto screen
-        // Remember that if vsync is enabled this is blocking
-        window.gl_swap_window();
+        renderer.end_frame(&window);
+    }
+
+    // exit skips destructors, so finish writing any recording first
+    if let Err(e) = recorder.stop() {
+        println!("Warning: {} ({}:{}:{})", e, file!(), line!(), column!());
     }
 
     // Exit program
     process::exit(0);
 }
 
+// Packs the images Game's example animator flips between
+fn example_atlas() -> Result<atlas::Atlas, String> {
+    let mut builder = atlas::AtlasBuilder::new(2048, 1024);
+    builder.add_file("res/awesomeface.png")?;
+    builder.add_file("res/cat.png")?;
+    builder.build()?.upload(&textures::TextureOptions::default())
+}
+
 // Wrapper for enabling SDL vsync
 unsafe fn enable_vsync(flag : bool) {
     if flag {
@@ -0,0 +1,103 @@
+use physics::collider::Aabb;
+
+// Sweep-and-prune along the x axis. Keeps its sort order between frames,
+// so the insertion sort is close to linear when bodies move a little.
+pub struct SweepAndPrune {
+    // Indices into the bounds slice passed to find_pairs
+    order: Vec<usize>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        SweepAndPrune { order: Vec::new() }
+    }
+
+    // Returns every pair (a, b) with a < b whose bounds overlap
+    pub fn find_pairs(&mut self, bounds: &[Aabb]) -> Vec<(usize, usize)> {
+        // Rebuild the order if bodies were added or removed
+        if self.order.len() != bounds.len() ||
+            self.order.iter().any(|&i| i >= bounds.len()) {
+            self.order = (0..bounds.len()).collect();
+        }
+
+        // Insertion sort by min x
+        for i in 1..self.order.len() {
+            let mut j = i;
+            while j > 0 && bounds[self.order[j - 1]].min.x > bounds[self.order[j]].min.x {
+                self.order.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for i in 0..self.order.len() {
+            let a = self.order[i];
+            for j in (i + 1)..self.order.len() {
+                let b = self.order[j];
+                // Everything after this starts past a's right edge
+                if bounds[b].min.x > bounds[a].max.x {
+                    break;
+                }
+                if bounds[a].overlaps(&bounds[b]) {
+                    pairs.push(if a < b { (a, b) } else { (b, a) });
+                }
+            }
+        }
+        pairs
+    }
+}
+
+impl Default for SweepAndPrune {
+    fn default() -> Self {
+        SweepAndPrune::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::collider::Aabb;
+    use physics::na::Vector2;
+
+    fn aabb(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Aabb {
+        Aabb::new(Vector2::new(min_x, min_y), Vector2::new(max_x, max_y))
+    }
+
+    fn sorted(mut pairs: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn finds_overlapping_pairs() {
+        let mut sap = SweepAndPrune::new();
+        let bounds = [
+            aabb(5.0, 0.0, 6.0, 1.0),
+            aabb(0.0, 0.0, 2.0, 2.0),
+            aabb(1.0, 1.0, 3.0, 3.0),
+            // Overlaps 1 on x only
+            aabb(1.5, 5.0, 2.5, 6.0),
+            aabb(5.5, 0.5, 7.0, 1.0),
+        ];
+        assert_eq!(sorted(sap.find_pairs(&bounds)), vec![(0, 4), (1, 2)]);
+    }
+
+    #[test]
+    fn follows_moving_bounds() {
+        let mut sap = SweepAndPrune::new();
+        let mut bounds = vec![aabb(0.0, 0.0, 1.0, 1.0), aabb(3.0, 0.0, 4.0, 1.0)];
+        assert!(sap.find_pairs(&bounds).is_empty());
+
+        // Swap their order along x
+        bounds[0] = aabb(3.5, 0.5, 4.5, 1.5);
+        assert_eq!(sap.find_pairs(&bounds), vec![(0, 1)]);
+
+        // A body added
+        bounds.push(aabb(4.0, 1.0, 5.0, 2.0));
+        assert_eq!(sorted(sap.find_pairs(&bounds)), vec![(0, 1), (0, 2), (1, 2)]);
+
+        // And removed
+        bounds.remove(0);
+        assert_eq!(sap.find_pairs(&bounds), vec![(0, 1)]);
+    }
+}
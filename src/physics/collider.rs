@@ -0,0 +1,328 @@
+extern crate nalgebra as na;
+
+use self::na::Vector2;
+
+// Edges shorter than this are treated as a single point
+pub const EDGE_EPSILON: f32 = 1e-5;
+
+// Axis-aligned bounding box in world space
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector2<f32>, max: Vector2<f32>) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_center(center: Vector2<f32>, half_extents: Vector2<f32>) -> Self {
+        Aabb { min: center - half_extents, max: center + half_extents }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+        self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+
+    pub fn contains(&self, point: &Vector2<f32>) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+        point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector2<f32> {
+        (self.max - self.min) * 0.5
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    // Stays axis aligned regardless of the body's rotation
+    Aabb { half_extents: Vector2<f32> },
+    Circle { radius: f32 },
+    // Convex, counter-clockwise winding, relative to the body's position
+    Polygon { vertices: Vec<Vector2<f32>> },
+}
+
+#[derive(Clone, Debug)]
+pub struct Collider {
+    pub shape: Shape,
+    // Offset of the shape from the body's position
+    pub offset: Vector2<f32>,
+}
+
+impl Collider {
+    pub fn aabb(width: f32, height: f32) -> Self {
+        Collider::new(Shape::Aabb {
+            half_extents: Vector2::new(width / 2.0, height / 2.0)
+        })
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Collider::new(Shape::Circle { radius })
+    }
+
+    // Returns None if the polygon has fewer than 3 distinct corners or isn't
+    // convex. Repeated vertices and ones in the middle of a straight edge are
+    // dropped, zero length edges have no normal. Clockwise input is reversed
+    // to counter-clockwise.
+    pub fn polygon(vertices: Vec<Vector2<f32>>) -> Option<Self> {
+        let mut vertices = remove_degenerate(vertices);
+        if vertices.len() < 3 {
+            return None;
+        }
+        if signed_area(&vertices) < 0.0 {
+            vertices.reverse();
+        }
+        let n = vertices.len();
+        for i in 0..n {
+            let a = vertices[i];
+            let b = vertices[(i + 1) % n];
+            let c = vertices[(i + 2) % n];
+            if cross(&(b - a), &(c - b)) < 0.0 {
+                return None;
+            }
+        }
+        Some(Collider::new(Shape::Polygon { vertices }))
+    }
+
+    fn new(shape: Shape) -> Self {
+        Collider { shape, offset: Vector2::new(0.0, 0.0) }
+    }
+
+    pub fn with_offset(mut self, offset: Vector2<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // Area of the shape, used for mass and inertia
+    pub fn area(&self) -> f32 {
+        match self.shape {
+            Shape::Aabb { half_extents } => 4.0 * half_extents.x * half_extents.y,
+            Shape::Circle { radius } => ::std::f32::consts::PI * radius * radius,
+            Shape::Polygon { ref vertices } => signed_area(vertices).abs(),
+        }
+    }
+
+    // Center of the shape relative to the body's position, offset included
+    pub fn centroid(&self) -> Vector2<f32> {
+        match self.shape {
+            Shape::Polygon { ref vertices } => polygon_centroid(vertices) + self.offset,
+            _ => self.offset,
+        }
+    }
+
+    // Moment of inertia around the body's position for the given mass, the
+    // inertia around the centroid plus the parallel axis term
+    pub fn inertia(&self, mass: f32) -> f32 {
+        let around_centroid = match self.shape {
+            Shape::Aabb { half_extents } => {
+                let (w, h) = (half_extents.x * 2.0, half_extents.y * 2.0);
+                mass * (w * w + h * h) / 12.0
+            }
+            Shape::Circle { radius } => 0.5 * mass * radius * radius,
+            Shape::Polygon { ref vertices } => {
+                // Fan of triangles from the centroid. The polygon is
+                // counter-clockwise so every weight is positive, but keep
+                // the sign so a bad polygon doesn't add up to nonsense.
+                let c = polygon_centroid(vertices);
+                let mut numerator = 0.0;
+                let mut denominator = 0.0;
+                for i in 0..vertices.len() {
+                    let a = vertices[i] - c;
+                    let b = vertices[(i + 1) % vertices.len()] - c;
+                    let w = cross(&a, &b);
+                    numerator += w * (a.dot(&a) + a.dot(&b) + b.dot(&b));
+                    denominator += w;
+                }
+                if denominator > 0.0 { mass * numerator / (6.0 * denominator) } else { 0.0 }
+            }
+        };
+        around_centroid + mass * self.centroid().norm_squared()
+    }
+
+    // Bounding box in world space for a body at position with rotation
+    pub fn bounds(&self, position: &Vector2<f32>, rotation: f32) -> Aabb {
+        let center = position + rotate(&self.offset, rotation);
+        match self.shape {
+            Shape::Aabb { half_extents } => Aabb::from_center(center, half_extents),
+            Shape::Circle { radius } =>
+                Aabb::from_center(center, Vector2::new(radius, radius)),
+            Shape::Polygon { .. } => {
+                let vertices = self.world_vertices(position, rotation);
+                let mut min = vertices[0];
+                let mut max = vertices[0];
+                for v in vertices.iter().skip(1) {
+                    min = Vector2::new(min.x.min(v.x), min.y.min(v.y));
+                    max = Vector2::new(max.x.max(v.x), max.y.max(v.y));
+                }
+                Aabb::new(min, max)
+            }
+        }
+    }
+
+    // Corners of box and polygon shapes in world space, counter-clockwise.
+    // Empty for circles.
+    pub fn world_vertices(&self, position: &Vector2<f32>, rotation: f32)
+        -> Vec<Vector2<f32>> {
+        match self.shape {
+            Shape::Aabb { half_extents } => {
+                let c = position + rotate(&self.offset, rotation);
+                let (hx, hy) = (half_extents.x, half_extents.y);
+                vec![
+                    c + Vector2::new(-hx, -hy),
+                    c + Vector2::new(hx, -hy),
+                    c + Vector2::new(hx, hy),
+                    c + Vector2::new(-hx, hy),
+                ]
+            }
+            Shape::Circle { .. } => Vec::new(),
+            Shape::Polygon { ref vertices } => vertices.iter()
+                .map(|v| position + rotate(&(v + self.offset), rotation))
+                .collect(),
+        }
+    }
+
+    // World space center of the shape
+    pub fn world_center(&self, position: &Vector2<f32>, rotation: f32) -> Vector2<f32> {
+        position + rotate(&self.offset, rotation)
+    }
+}
+
+// 2D cross product (z component of the 3D cross product)
+pub fn cross(a: &Vector2<f32>, b: &Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Cross product of a scalar angular velocity with a vector
+pub fn cross_scalar(s: f32, v: &Vector2<f32>) -> Vector2<f32> {
+    Vector2::new(-s * v.y, s * v.x)
+}
+
+pub fn rotate(v: &Vector2<f32>, angle: f32) -> Vector2<f32> {
+    if angle == 0.0 {
+        return *v;
+    }
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+fn signed_area(vertices: &[Vector2<f32>]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..vertices.len() {
+        area += cross(&vertices[i], &vertices[(i + 1) % vertices.len()]);
+    }
+    area / 2.0
+}
+
+// Area weighted center of a counter-clockwise polygon
+fn polygon_centroid(vertices: &[Vector2<f32>]) -> Vector2<f32> {
+    let mut sum = Vector2::new(0.0, 0.0);
+    let mut area = 0.0;
+    for i in 0..vertices.len() {
+        let a = vertices[i];
+        let b = vertices[(i + 1) % vertices.len()];
+        let w = cross(&a, &b);
+        sum += (a + b) * w;
+        area += w;
+    }
+    if area.abs() <= f32::EPSILON {
+        // No area, fall back to the average of the vertices
+        let n = vertices.len().max(1) as f32;
+        return vertices.iter().fold(Vector2::new(0.0, 0.0), |s, v| s + v) / n;
+    }
+    sum / (3.0 * area)
+}
+
+// Drops vertices equal to the one before and ones on a straight line
+// between their neighbours, until nothing changes
+fn remove_degenerate(mut vertices: Vec<Vector2<f32>>) -> Vec<Vector2<f32>> {
+    loop {
+        let n = vertices.len();
+        if n < 3 {
+            return vertices;
+        }
+        let degenerate = (0..n).find(|&i| {
+            let prev = vertices[(i + n - 1) % n];
+            let next = vertices[(i + 1) % n];
+            let (to, from) = (vertices[i] - prev, next - vertices[i]);
+            to.norm() <= EDGE_EPSILON ||
+                cross(&to, &from).abs() <= EDGE_EPSILON * (to.norm() + from.norm())
+        });
+        match degenerate {
+            Some(i) => { vertices.remove(i); }
+            None => return vertices,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(x, y)
+    }
+
+    fn polygon_vertices(collider: &Collider) -> Vec<Vector2<f32>> {
+        match collider.shape {
+            Shape::Polygon { ref vertices } => vertices.clone(),
+            _ => panic!("not a polygon"),
+        }
+    }
+
+    #[test]
+    fn polygon_drops_repeated_and_collinear_vertices() {
+        let collider = Collider::polygon(vec![
+            v(0.0, 0.0), v(0.5, 0.0), v(1.0, 0.0), v(1.0, 0.0), v(1.0, 1.0), v(0.0, 1.0),
+        ]).unwrap();
+        assert_eq!(polygon_vertices(&collider),
+                   vec![v(0.0, 0.0), v(1.0, 0.0), v(1.0, 1.0), v(0.0, 1.0)]);
+    }
+
+    #[test]
+    fn polygon_rejects_degenerate_and_concave() {
+        assert!(Collider::polygon(vec![v(0.0, 0.0), v(1.0, 0.0)]).is_none());
+        assert!(Collider::polygon(vec![v(0.0, 0.0), v(1.0, 0.0), v(1.0, 0.0)]).is_none());
+        assert!(Collider::polygon(vec![v(0.0, 0.0), v(1.0, 0.0), v(2.0, 0.0)]).is_none());
+        assert!(Collider::polygon(vec![
+            v(0.0, 0.0), v(2.0, 0.0), v(1.0, 0.5), v(2.0, 2.0), v(0.0, 2.0),
+        ]).is_none());
+    }
+
+    #[test]
+    fn polygon_reverses_clockwise() {
+        let collider = Collider::polygon(vec![v(0.0, 0.0), v(0.0, 1.0), v(1.0, 0.0)]).unwrap();
+        assert!(signed_area(&polygon_vertices(&collider)) > 0.0);
+    }
+
+    #[test]
+    fn inertia_around_body_position() {
+        // Unit square centered on the body, m * (w^2 + h^2) / 12
+        let centered = Collider::polygon(vec![
+            v(-0.5, -0.5), v(0.5, -0.5), v(0.5, 0.5), v(-0.5, 0.5),
+        ]).unwrap();
+        assert!((centered.inertia(1.0) - 1.0 / 6.0).abs() < 1e-5);
+
+        // Same square with the origin outside it adds m * d^2
+        let outside = Collider::polygon(vec![
+            v(2.0, 0.0), v(3.0, 0.0), v(3.0, 1.0), v(2.0, 1.0),
+        ]).unwrap();
+        assert!((outside.centroid() - v(2.5, 0.5)).norm() < 1e-5);
+        assert!((outside.inertia(1.0) - (1.0 / 6.0 + 6.5)).abs() < 1e-4);
+
+        let offset = centered.clone().with_offset(v(2.5, 0.5));
+        assert!((offset.inertia(1.0) - outside.inertia(1.0)).abs() < 1e-4);
+
+        let box_offset = Collider::aabb(1.0, 1.0).with_offset(v(2.5, 0.5));
+        assert!((box_offset.inertia(1.0) - outside.inertia(1.0)).abs() < 1e-4);
+
+        let circle = Collider::circle(2.0).with_offset(v(0.0, 1.0));
+        assert!((circle.inertia(2.0) - (4.0 + 2.0)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,453 @@
+// CPU-side 2D collision detection and rigid body physics.
+// Nothing in here touches GL, so it can be used and tested headless.
+#![allow(dead_code)]
+
+extern crate nalgebra as na;
+
+pub mod collider;
+pub mod broadphase;
+pub mod narrowphase;
+
+use std::collections::HashSet;
+use self::na::Vector2;
+use events::{Event, EventType, EventPool};
+use physics::collider::{Aabb, Collider, cross, cross_scalar};
+use physics::broadphase::SweepAndPrune;
+use physics::narrowphase::Contact;
+
+// Default fixed step, 60 updates per second
+pub const DEFAULT_STEP_MS: f32 = 1000.0 / 60.0;
+
+// Caps the steps taken in one update so a long frame can't spiral
+const MAX_STEPS_PER_UPDATE: u32 = 8;
+
+// Fraction of penetration corrected each step, and how much is ignored
+const CORRECTION_PERCENT: f32 = 0.4;
+const CORRECTION_SLOP: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BodyKind {
+    // Never moves, infinite mass
+    Static,
+    // Moved only by setting its velocity, infinite mass
+    Kinematic,
+    // Moved by forces, gravity and collisions
+    Dynamic,
+}
+
+pub struct Body {
+    id: u32,
+    pub kind: BodyKind,
+    pub collider: Collider,
+    pub position: Vector2<f32>,
+    // Radians, counter-clockwise
+    pub rotation: f32,
+    pub velocity: Vector2<f32>,
+    pub angular_velocity: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub gravity_scale: f32,
+    // Triggers report overlaps through events instead of colliding
+    pub is_trigger: bool,
+    force: Vector2<f32>,
+    torque: f32,
+    inv_mass: f32,
+    inv_inertia: f32,
+}
+
+impl Body {
+    pub fn new(kind: BodyKind, collider: Collider, position: Vector2<f32>) -> Self {
+        let mut body = Body {
+            id: 0,
+            kind,
+            collider,
+            position,
+            rotation: 0.0,
+            velocity: Vector2::new(0.0, 0.0),
+            angular_velocity: 0.0,
+            restitution: 0.2,
+            friction: 0.4,
+            gravity_scale: 1.0,
+            is_trigger: false,
+            force: Vector2::new(0.0, 0.0),
+            torque: 0.0,
+            inv_mass: 0.0,
+            inv_inertia: 0.0,
+        };
+        body.set_density(1.0);
+        body
+    }
+
+    pub fn trigger(collider: Collider, position: Vector2<f32>) -> Self {
+        let mut body = Body::new(BodyKind::Static, collider, position);
+        body.is_trigger = true;
+        body
+    }
+
+    // Id assigned by the World, used as trigger_id/other_id in events
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // Recomputes mass and inertia from the collider's area
+    pub fn set_density(&mut self, density: f32) {
+        let mass = self.collider.area() * density;
+        self.set_mass(mass);
+    }
+
+    pub fn set_mass(&mut self, mass: f32) {
+        if self.kind != BodyKind::Dynamic || mass <= 0.0 {
+            self.inv_mass = 0.0;
+            self.inv_inertia = 0.0;
+            return;
+        }
+        self.inv_mass = 1.0 / mass;
+        let inertia = self.collider.inertia(mass);
+        // Axis aligned boxes can't rotate
+        self.inv_inertia = match self.collider.shape {
+            collider::Shape::Aabb { .. } => 0.0,
+            _ => if inertia > 0.0 { 1.0 / inertia } else { 0.0 },
+        };
+    }
+
+    pub fn mass(&self) -> f32 {
+        if self.inv_mass > 0.0 { 1.0 / self.inv_mass } else { 0.0 }
+    }
+
+    // Forces are cleared after every step
+    pub fn apply_force(&mut self, force: Vector2<f32>) {
+        self.force += force;
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.torque += torque;
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vector2<f32>, contact_offset: Vector2<f32>) {
+        self.velocity += impulse * self.inv_mass;
+        self.angular_velocity += cross(&contact_offset, &impulse) * self.inv_inertia;
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.collider.bounds(&self.position, self.rotation)
+    }
+}
+
+pub struct World {
+    bodies: Vec<Body>,
+    pub gravity: Vector2<f32>,
+    step_ms: f32,
+    accumulator_ms: f32,
+    next_id: u32,
+    broadphase: SweepAndPrune,
+    // (trigger id, other id) pairs overlapping as of the last step
+    trigger_pairs: HashSet<(u32, u32)>,
+}
+
+impl World {
+    pub fn new(gravity: Vector2<f32>) -> Self {
+        World::with_step(gravity, DEFAULT_STEP_MS)
+    }
+
+    // step_ms is the fixed step length and must be above 0
+    pub fn with_step(gravity: Vector2<f32>, step_ms: f32) -> Self {
+        assert!(step_ms > 0.0, "physics step must be above 0ms, got {}", step_ms);
+        World {
+            bodies: Vec::new(),
+            gravity,
+            step_ms,
+            accumulator_ms: 0.0,
+            next_id: 1,
+            broadphase: SweepAndPrune::new(),
+            trigger_pairs: HashSet::new(),
+        }
+    }
+
+    // Adds a body and returns its id
+    pub fn add_body(&mut self, mut body: Body) -> u32 {
+        body.id = self.next_id;
+        self.next_id += 1;
+        self.bodies.push(body);
+        self.next_id - 1
+    }
+
+    // Removes a body, overlapping triggers won't send an exit event for it
+    pub fn remove_body(&mut self, id: u32) -> Option<Body> {
+        self.trigger_pairs.retain(|&(t, o)| t != id && o != id);
+        match self.bodies.iter().position(|b| b.id == id) {
+            Some(idx) => Some(self.bodies.remove(idx)),
+            None => None,
+        }
+    }
+
+    pub fn body(&self, id: u32) -> Option<&Body> {
+        self.bodies.iter().find(|b| b.id == id)
+    }
+
+    pub fn body_mut(&mut self, id: u32) -> Option<&mut Body> {
+        self.bodies.iter_mut().find(|b| b.id == id)
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    // Fraction of a step left in the accumulator, for interpolating rendering
+    pub fn alpha(&self) -> f32 {
+        self.accumulator_ms / self.step_ms
+    }
+
+    // Advances the simulation by whole fixed steps, carrying leftover time
+    // over to the next call. Returns the number of steps taken.
+    pub fn update(&mut self, delta_time_ms: u64, event_pool: &mut EventPool) -> u32 {
+        self.accumulator_ms += delta_time_ms as f32;
+        let mut steps = 0;
+        while self.accumulator_ms >= self.step_ms {
+            if steps == MAX_STEPS_PER_UPDATE {
+                // Drop the time we can't catch up on
+                self.accumulator_ms = 0.0;
+                break;
+            }
+            let dt = self.step_ms / 1000.0;
+            self.step(dt, event_pool);
+            self.accumulator_ms -= self.step_ms;
+            steps += 1;
+        }
+        steps
+    }
+
+    // Runs a single step of dt seconds
+    pub fn step(&mut self, dt: f32, event_pool: &mut EventPool) {
+        self.integrate_forces(dt);
+
+        let bounds: Vec<Aabb> = self.bodies.iter().map(|b| b.bounds()).collect();
+        let pairs = self.broadphase.find_pairs(&bounds);
+
+        let mut contacts = Vec::new();
+        let mut triggers = HashSet::new();
+        for (i, j) in pairs {
+            let (a, b) = (&self.bodies[i], &self.bodies[j]);
+            if a.inv_mass == 0.0 && b.inv_mass == 0.0 && !a.is_trigger && !b.is_trigger {
+                continue;
+            }
+            let contact = match narrowphase::collide(
+                &a.collider, &a.position, a.rotation,
+                &b.collider, &b.position, b.rotation) {
+                Some(c) => c,
+                None => continue,
+            };
+            if a.is_trigger {
+                triggers.insert((a.id, b.id));
+            }
+            if b.is_trigger {
+                triggers.insert((b.id, a.id));
+            }
+            if !a.is_trigger && !b.is_trigger {
+                contacts.push((i, j, contact));
+            }
+        }
+
+        for &(i, j, ref contact) in contacts.iter() {
+            self.resolve(i, j, contact);
+        }
+
+        self.integrate_velocities(dt);
+
+        for &(i, j, ref contact) in contacts.iter() {
+            self.correct_positions(i, j, contact);
+        }
+
+        self.signal_trigger_events(triggers, event_pool);
+    }
+
+    fn integrate_forces(&mut self, dt: f32) {
+        let gravity = self.gravity;
+        for body in self.bodies.iter_mut().filter(|b| b.kind == BodyKind::Dynamic) {
+            let acceleration = body.force * body.inv_mass + gravity * body.gravity_scale;
+            body.velocity += acceleration * dt;
+            body.angular_velocity += body.torque * body.inv_inertia * dt;
+        }
+    }
+
+    // Semi-implicit Euler, velocities were already updated from forces
+    fn integrate_velocities(&mut self, dt: f32) {
+        for body in self.bodies.iter_mut() {
+            if body.kind != BodyKind::Static {
+                body.position += body.velocity * dt;
+                body.rotation += body.angular_velocity * dt;
+            }
+            body.force = Vector2::new(0.0, 0.0);
+            body.torque = 0.0;
+        }
+    }
+
+    // Applies restitution and friction impulses for one contact
+    fn resolve(&mut self, i: usize, j: usize, contact: &Contact) {
+        let (a, b) = pair_mut(&mut self.bodies, i, j);
+        let ra = contact.point - a.position;
+        let rb = contact.point - b.position;
+
+        let relative = b.velocity + cross_scalar(b.angular_velocity, &rb)
+            - a.velocity - cross_scalar(a.angular_velocity, &ra);
+        let normal_speed = relative.dot(&contact.normal);
+        // Already separating
+        if normal_speed > 0.0 {
+            return;
+        }
+
+        let ra_n = cross(&ra, &contact.normal);
+        let rb_n = cross(&rb, &contact.normal);
+        let inv_mass_sum = a.inv_mass + b.inv_mass
+            + ra_n * ra_n * a.inv_inertia + rb_n * rb_n * b.inv_inertia;
+        if inv_mass_sum == 0.0 {
+            return;
+        }
+
+        let restitution = a.restitution.min(b.restitution);
+        let j_n = -(1.0 + restitution) * normal_speed / inv_mass_sum;
+        let impulse = contact.normal * j_n;
+        a.apply_impulse(-impulse, ra);
+        b.apply_impulse(impulse, rb);
+
+        // Coulomb friction along the contact tangent
+        let relative = b.velocity + cross_scalar(b.angular_velocity, &rb)
+            - a.velocity - cross_scalar(a.angular_velocity, &ra);
+        let tangent = relative - contact.normal * relative.dot(&contact.normal);
+        let tangent_len = tangent.norm();
+        if tangent_len <= f32::EPSILON {
+            return;
+        }
+        let tangent = tangent / tangent_len;
+        let ra_t = cross(&ra, &tangent);
+        let rb_t = cross(&rb, &tangent);
+        let inv_mass_sum = a.inv_mass + b.inv_mass
+            + ra_t * ra_t * a.inv_inertia + rb_t * rb_t * b.inv_inertia;
+        let friction = (a.friction * b.friction).sqrt();
+        let j_t = (-relative.dot(&tangent) / inv_mass_sum)
+            .max(-j_n * friction).min(j_n * friction);
+        let friction_impulse = tangent * j_t;
+        a.apply_impulse(-friction_impulse, ra);
+        b.apply_impulse(friction_impulse, rb);
+    }
+
+    // Pushes overlapping bodies apart to stop them sinking into each other
+    fn correct_positions(&mut self, i: usize, j: usize, contact: &Contact) {
+        let (a, b) = pair_mut(&mut self.bodies, i, j);
+        let inv_mass_sum = a.inv_mass + b.inv_mass;
+        if inv_mass_sum == 0.0 {
+            return;
+        }
+        let amount = (contact.depth - CORRECTION_SLOP).max(0.0)
+            / inv_mass_sum * CORRECTION_PERCENT;
+        let correction = contact.normal * amount;
+        a.position -= correction * a.inv_mass;
+        b.position += correction * b.inv_mass;
+    }
+
+    fn signal_trigger_events(
+        &mut self, current: HashSet<(u32, u32)>, event_pool: &mut EventPool) {
+        for &(trigger_id, other_id) in current.difference(&self.trigger_pairs) {
+            event_pool.signal_event(
+                EventType::OnTriggerEnter,
+                Event::OnTriggerEnter { trigger_id, other_id });
+        }
+        for &(trigger_id, other_id) in self.trigger_pairs.difference(&current) {
+            event_pool.signal_event(
+                EventType::OnTriggerExit,
+                Event::OnTriggerExit { trigger_id, other_id });
+        }
+        self.trigger_pairs = current;
+    }
+}
+
+// Mutable references to two different bodies, i must be less than j
+fn pair_mut(bodies: &mut [Body], i: usize, j: usize) -> (&mut Body, &mut Body) {
+    let (left, right) = bodies.split_at_mut(j);
+    (&mut left[i], &mut right[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entered(pool: &mut EventPool, trigger_id: u32) -> Option<u32> {
+        match pool.poll_events(EventType::OnTriggerEnter, trigger_id, true) {
+            Some(&Event::OnTriggerEnter { other_id, .. }) => Some(other_id),
+            _ => None,
+        }
+    }
+
+    fn exited(pool: &mut EventPool, trigger_id: u32) -> Option<u32> {
+        match pool.poll_events(EventType::OnTriggerExit, trigger_id, true) {
+            Some(&Event::OnTriggerExit { other_id, .. }) => Some(other_id),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn trigger_enter_and_exit() {
+        let mut world = World::new(Vector2::new(0.0, 0.0));
+        let mut pool = EventPool::new();
+        let trigger = world.add_body(
+            Body::trigger(Collider::aabb(2.0, 2.0), Vector2::new(0.0, 0.0)));
+        let mut body = Body::new(BodyKind::Dynamic, Collider::circle(0.5), Vector2::new(-2.2, 0.0));
+        body.velocity = Vector2::new(60.0, 0.0);
+        let body = world.add_body(body);
+
+        // 1 unit per step, contacts are found before moving so it enters
+        // on the second step
+        world.step(1.0 / 60.0, &mut pool);
+        assert_eq!(entered(&mut pool, trigger), None);
+
+        world.step(1.0 / 60.0, &mut pool);
+        assert_eq!(entered(&mut pool, trigger), Some(body));
+        pool.remove_events();
+
+        // Still inside, no new events
+        world.step(1.0 / 60.0, &mut pool);
+        world.step(1.0 / 60.0, &mut pool);
+        assert_eq!(entered(&mut pool, trigger), None);
+        assert_eq!(exited(&mut pool, trigger), None);
+
+        // Triggers don't stop the body
+        world.step(1.0 / 60.0, &mut pool);
+        assert_eq!(exited(&mut pool, trigger), Some(body));
+        assert!((world.body(body).unwrap().velocity.x - 60.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn removed_body_sends_no_exit() {
+        let mut world = World::new(Vector2::new(0.0, 0.0));
+        let mut pool = EventPool::new();
+        let trigger = world.add_body(
+            Body::trigger(Collider::circle(1.0), Vector2::new(0.0, 0.0)));
+        let body = world.add_body(
+            Body::new(BodyKind::Dynamic, Collider::circle(0.5), Vector2::new(0.5, 0.0)));
+
+        world.step(1.0 / 60.0, &mut pool);
+        assert_eq!(entered(&mut pool, trigger), Some(body));
+        pool.remove_events();
+
+        world.remove_body(body);
+        world.step(1.0 / 60.0, &mut pool);
+        assert_eq!(exited(&mut pool, trigger), None);
+    }
+
+    #[test]
+    fn update_takes_fixed_steps() {
+        let mut world = World::with_step(Vector2::new(0.0, -10.0), 10.0);
+        let mut pool = EventPool::new();
+        assert_eq!(world.update(25, &mut pool), 2);
+        assert!((world.alpha() - 0.5).abs() < 1e-5);
+        assert_eq!(world.update(5, &mut pool), 1);
+        // Capped so a long frame can't spiral
+        assert_eq!(world.update(1000, &mut pool), MAX_STEPS_PER_UPDATE);
+        assert_eq!(world.alpha(), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "physics step must be above 0ms")]
+    fn zero_step_is_rejected() {
+        World::with_step(Vector2::new(0.0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,368 @@
+extern crate nalgebra as na;
+
+use self::na::Vector2;
+use physics::collider::{Collider, Shape, EDGE_EPSILON, cross};
+
+// Result of a narrow-phase test between two shapes
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    // Unit vector pointing from the first shape to the second
+    pub normal: Vector2<f32>,
+    // How far the shapes overlap along the normal
+    pub depth: f32,
+    // World space point the contact is applied at
+    pub point: Vector2<f32>,
+}
+
+impl Contact {
+    fn flipped(self) -> Self {
+        Contact { normal: -self.normal, ..self }
+    }
+}
+
+// Tests two colliders placed in the world, returning None if they don't touch
+pub fn collide(
+    a: &Collider, pos_a: &Vector2<f32>, rot_a: f32,
+    b: &Collider, pos_b: &Vector2<f32>, rot_b: f32) -> Option<Contact> {
+    match (&a.shape, &b.shape) {
+        (&Shape::Circle { radius: ra }, &Shape::Circle { radius: rb }) =>
+            circle_circle(
+                &a.world_center(pos_a, rot_a), ra,
+                &b.world_center(pos_b, rot_b), rb),
+        (&Shape::Aabb { half_extents: ha }, &Shape::Aabb { half_extents: hb }) =>
+            aabb_aabb(
+                &a.world_center(pos_a, rot_a), &ha,
+                &b.world_center(pos_b, rot_b), &hb),
+        (&Shape::Circle { radius }, _) =>
+            polygon_circle(
+                &b.world_vertices(pos_b, rot_b),
+                &a.world_center(pos_a, rot_a), radius)
+            .map(|c| c.flipped()),
+        (_, &Shape::Circle { radius }) =>
+            polygon_circle(
+                &a.world_vertices(pos_a, rot_a),
+                &b.world_center(pos_b, rot_b), radius),
+        _ => polygon_polygon(
+                &a.world_vertices(pos_a, rot_a),
+                &b.world_vertices(pos_b, rot_b)),
+    }
+}
+
+pub fn circle_circle(
+    center_a: &Vector2<f32>, radius_a: f32,
+    center_b: &Vector2<f32>, radius_b: f32) -> Option<Contact> {
+    let d = center_b - center_a;
+    let dist = d.norm();
+    let radii = radius_a + radius_b;
+    if dist >= radii {
+        return None;
+    }
+    // Pick an arbitrary normal for perfectly overlapping circles
+    let normal = if dist > f32::EPSILON { d / dist } else { Vector2::new(1.0, 0.0) };
+    Some(Contact {
+        normal,
+        depth: radii - dist,
+        point: center_a + normal * (radius_a - (radii - dist) / 2.0),
+    })
+}
+
+pub fn aabb_aabb(
+    center_a: &Vector2<f32>, half_a: &Vector2<f32>,
+    center_b: &Vector2<f32>, half_b: &Vector2<f32>) -> Option<Contact> {
+    let d = center_b - center_a;
+    let overlap_x = half_a.x + half_b.x - d.x.abs();
+    let overlap_y = half_a.y + half_b.y - d.y.abs();
+    if overlap_x <= 0.0 || overlap_y <= 0.0 {
+        return None;
+    }
+
+    // Center of the overlapping region
+    let min = Vector2::new(
+        (center_a.x - half_a.x).max(center_b.x - half_b.x),
+        (center_a.y - half_a.y).max(center_b.y - half_b.y));
+    let max = Vector2::new(
+        (center_a.x + half_a.x).min(center_b.x + half_b.x),
+        (center_a.y + half_a.y).min(center_b.y + half_b.y));
+    let point = (min + max) * 0.5;
+
+    // Resolve along the axis of least penetration
+    if overlap_x < overlap_y {
+        let sign = if d.x < 0.0 { -1.0 } else { 1.0 };
+        Some(Contact { normal: Vector2::new(sign, 0.0), depth: overlap_x, point })
+    } else {
+        let sign = if d.y < 0.0 { -1.0 } else { 1.0 };
+        Some(Contact { normal: Vector2::new(0.0, sign), depth: overlap_y, point })
+    }
+}
+
+// Polygon vertices must be convex and counter-clockwise
+pub fn polygon_circle(
+    vertices: &[Vector2<f32>], center: &Vector2<f32>, radius: f32)
+    -> Option<Contact> {
+    let n = vertices.len();
+    if n < 3 {
+        return None;
+    }
+
+    // Find the edge the center is furthest in front of
+    let mut separation = f32::MIN;
+    let mut face = 0;
+    for i in 0..n {
+        let normal = match edge_normal(&vertices[i], &vertices[(i + 1) % n]) {
+            Some(normal) => normal,
+            None => continue,
+        };
+        let s = normal.dot(&(center - vertices[i]));
+        if s > radius {
+            return None;
+        }
+        if s > separation {
+            separation = s;
+            face = i;
+        }
+    }
+    // Every edge was degenerate
+    if separation == f32::MIN {
+        return None;
+    }
+
+    let v1 = vertices[face];
+    let v2 = vertices[(face + 1) % n];
+    // face was picked from the edges with a normal
+    let face_normal = edge_normal(&v1, &v2).unwrap();
+
+    // Center inside the polygon, push out through the nearest face
+    if separation < f32::EPSILON {
+        let normal = face_normal;
+        return Some(Contact {
+            normal,
+            depth: radius - separation,
+            point: center - normal * radius,
+        });
+    }
+
+    // Otherwise the closest feature is either a vertex or the face itself
+    let closest = closest_point_on_segment(center, &v1, &v2);
+    let d = center - closest;
+    let dist = d.norm();
+    if dist >= radius {
+        return None;
+    }
+    let normal = if dist > f32::EPSILON { d / dist } else { face_normal };
+    Some(Contact { normal, depth: radius - dist, point: closest })
+}
+
+// Separating axis test between two convex counter-clockwise polygons
+pub fn polygon_polygon(a: &[Vector2<f32>], b: &[Vector2<f32>]) -> Option<Contact> {
+    if a.len() < 3 || b.len() < 3 {
+        return None;
+    }
+
+    let (sep_a, face_a) = max_separation(a, b)?;
+    if sep_a >= 0.0 {
+        return None;
+    }
+    let (sep_b, face_b) = max_separation(b, a)?;
+    if sep_b >= 0.0 {
+        return None;
+    }
+
+    // Use the face with the least penetration as the reference
+    let (reference, incident, face, flip) =
+        if sep_b > sep_a + 0.001 { (b, a, face_b, true) } else { (a, b, face_a, false) };
+    let n = reference.len();
+    let v1 = reference[face];
+    let v2 = reference[(face + 1) % n];
+    // max_separation only picks faces with a normal
+    let normal = edge_normal(&v1, &v2).unwrap();
+
+    // Average the incident vertices that are behind the reference face
+    let mut depth: f32 = 0.0;
+    let mut point = Vector2::new(0.0, 0.0);
+    let mut count = 0.0;
+    for v in incident {
+        let s = normal.dot(&(v - v1));
+        if s < 0.0 {
+            depth = depth.max(-s);
+            point += *v;
+            count += 1.0;
+        }
+    }
+    if count == 0.0 {
+        return None;
+    }
+    point /= count;
+
+    let contact = Contact { normal, depth, point };
+    Some(if flip { contact.flipped() } else { contact })
+}
+
+// Largest signed distance of b's vertices from any face of a, and that face.
+// Zero length edges are skipped, None if a has no other edges.
+fn max_separation(a: &[Vector2<f32>], b: &[Vector2<f32>]) -> Option<(f32, usize)> {
+    let mut best = None;
+    for i in 0..a.len() {
+        let v1 = a[i];
+        let normal = match edge_normal(&v1, &a[(i + 1) % a.len()]) {
+            Some(normal) => normal,
+            None => continue,
+        };
+        // Deepest vertex of b along -normal
+        let min = b.iter()
+            .map(|v| normal.dot(&(v - v1)))
+            .fold(f32::MAX, f32::min);
+        match best {
+            Some((separation, _)) if separation >= min => {}
+            _ => { best = Some((min, i)); }
+        }
+    }
+    best
+}
+
+// Outward normal of a counter-clockwise edge, None if the edge has no length
+fn edge_normal(v1: &Vector2<f32>, v2: &Vector2<f32>) -> Option<Vector2<f32>> {
+    let e = v2 - v1;
+    let length = e.norm();
+    if length <= EDGE_EPSILON {
+        return None;
+    }
+    Some(Vector2::new(e.y, -e.x) / length)
+}
+
+fn closest_point_on_segment(
+    p: &Vector2<f32>, a: &Vector2<f32>, b: &Vector2<f32>) -> Vector2<f32> {
+    let ab = b - a;
+    let len_sq = ab.dot(&ab);
+    if len_sq <= f32::EPSILON {
+        return *a;
+    }
+    let t = ((p - a).dot(&ab) / len_sq).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+// Whether a point lies inside a convex counter-clockwise polygon
+pub fn polygon_contains(vertices: &[Vector2<f32>], point: &Vector2<f32>) -> bool {
+    let n = vertices.len();
+    n >= 3 && (0..n).all(|i| {
+        cross(&(vertices[(i + 1) % n] - vertices[i]), &(point - vertices[i])) >= 0.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(x, y)
+    }
+
+    fn close(a: Vector2<f32>, b: Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    fn square(min: Vector2<f32>, size: f32) -> Vec<Vector2<f32>> {
+        vec![min, min + v(size, 0.0), min + v(size, size), min + v(0.0, size)]
+    }
+
+    #[test]
+    fn circle_circle_contact() {
+        let contact = circle_circle(&v(0.0, 0.0), 1.0, &v(1.5, 0.0), 1.0).unwrap();
+        assert!(close(contact.normal, v(1.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+        assert!(close(contact.point, v(0.75, 0.0)));
+
+        assert!(circle_circle(&v(0.0, 0.0), 1.0, &v(2.0, 0.0), 1.0).is_none());
+    }
+
+    #[test]
+    fn aabb_aabb_least_penetration() {
+        let contact = aabb_aabb(&v(0.0, 0.0), &v(1.0, 1.0), &v(0.0, -1.8), &v(1.0, 1.0)).unwrap();
+        assert!(close(contact.normal, v(0.0, -1.0)));
+        assert!((contact.depth - 0.2).abs() < 1e-5);
+
+        let contact = aabb_aabb(&v(0.0, 0.0), &v(1.0, 1.0), &v(1.5, 0.5), &v(1.0, 1.0)).unwrap();
+        assert!(close(contact.normal, v(1.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+
+        assert!(aabb_aabb(&v(0.0, 0.0), &v(1.0, 1.0), &v(2.0, 0.0), &v(1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn polygon_circle_face_and_vertex() {
+        let square = square(v(0.0, 0.0), 2.0);
+
+        // Touching the right face
+        let contact = polygon_circle(&square, &v(2.5, 1.0), 1.0).unwrap();
+        assert!(close(contact.normal, v(1.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+
+        // Off the top right corner
+        let contact = polygon_circle(&square, &v(2.5, 2.5), 1.0).unwrap();
+        let diagonal = v(1.0, 1.0).normalize();
+        assert!(close(contact.normal, diagonal));
+        assert!((contact.depth - (1.0 - 0.5 * 2.0f32.sqrt())).abs() < 1e-5);
+        assert!(close(contact.point, v(2.0, 2.0)));
+
+        // Center inside, pushed out through the nearest face
+        let contact = polygon_circle(&square, &v(1.0, 0.2), 0.5).unwrap();
+        assert!(close(contact.normal, v(0.0, -1.0)));
+        assert!((contact.depth - 0.7).abs() < 1e-5);
+
+        assert!(polygon_circle(&square, &v(3.5, 1.0), 1.0).is_none());
+    }
+
+    #[test]
+    fn polygon_polygon_contact() {
+        let a = square(v(0.0, 0.0), 2.0);
+        let b = square(v(1.5, 0.5), 1.0);
+        let contact = polygon_polygon(&a, &b).unwrap();
+        assert!(close(contact.normal, v(1.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+
+        // Normal still points from the first shape to the second
+        let contact = polygon_polygon(&b, &a).unwrap();
+        assert!(close(contact.normal, v(-1.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+
+        assert!(polygon_polygon(&a, &square(v(2.5, 0.0), 1.0)).is_none());
+    }
+
+    #[test]
+    fn degenerate_edges_are_skipped() {
+        // A repeated vertex, as if built without Collider::polygon
+        let a = vec![v(0.0, 0.0), v(1.0, 0.0), v(1.0, 0.0), v(1.0, 1.0), v(0.0, 1.0)];
+        let b = square(v(0.5, 0.25), 1.0);
+        let contact = polygon_polygon(&a, &b).unwrap();
+        assert!(contact.normal.x.is_finite() && contact.normal.y.is_finite());
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+
+        let contact = polygon_circle(&a, &v(1.25, 0.5), 0.5).unwrap();
+        assert!(close(contact.normal, v(1.0, 0.0)));
+        assert!((contact.depth - 0.25).abs() < 1e-5);
+
+        let point = vec![v(1.0, 1.0), v(1.0, 1.0), v(1.0, 1.0)];
+        assert!(polygon_polygon(&point, &b).is_none());
+        assert!(polygon_circle(&point, &v(1.0, 1.0), 1.0).is_none());
+    }
+
+    #[test]
+    fn collide_mixed_shapes() {
+        let circle = Collider::circle(1.0);
+        let square = Collider::polygon(square(v(-1.0, -1.0), 2.0)).unwrap();
+
+        // Circle first, the normal is flipped to point at the polygon
+        let contact = collide(
+            &circle, &v(-1.5, 0.0), 0.0,
+            &square, &v(0.0, 0.0), 0.0).unwrap();
+        assert!(close(contact.normal, v(1.0, 0.0)));
+        assert!((contact.depth - 0.5).abs() < 1e-5);
+
+        // Boxes against polygons go through the separating axis test
+        let aabb = Collider::aabb(2.0, 2.0);
+        let contact = collide(
+            &square, &v(0.0, 0.0), 0.0,
+            &aabb, &v(0.0, 1.75), 0.0).unwrap();
+        assert!(close(contact.normal, v(0.0, 1.0)));
+        assert!((contact.depth - 0.25).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,439 @@
+// Save-game files.
+//
+// A save holds a SaveData, a set of named JSON sections so each system
+// (the game clock, later ECS components, ...) can store its own state
+// without knowing about the others.
+//
+// File layout, all integers little endian:
+//   magic     8 bytes  "RASSAVE\0"
+//   version   u32      format version the payload was written with
+//   checksum  u64      FNV-1a hash of the payload
+//   length    u64      payload size in bytes
+//   payload            SaveData as JSON
+#![allow(dead_code)]
+
+extern crate serde;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::{fmt, fs, io};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use self::serde_json::Value;
+use util::{checksum, read_u32, read_u64, u32_bytes, u64_bytes, write_atomic};
+
+const MAGIC: &[u8; 8] = b"RASSAVE\0";
+const HEADER_LEN: usize = 8 + 4 + 8 + 8;
+const EXTENSION: &str = "sav";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    // Slot has no save file
+    NotFound(u32),
+    // Not a save file, or cut short
+    BadHeader,
+    // Payload doesn't match its checksum
+    Corrupt,
+    // Written by a newer version of the game
+    UnsupportedVersion(u32),
+    // No migration registered from this version
+    MissingMigration(u32),
+    Migration(u32, String),
+    Serialization(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaveError::Io(ref e) => write!(f, "save io error: {}", e),
+            SaveError::NotFound(slot) => write!(f, "no save in slot {}", slot),
+            SaveError::BadHeader => write!(f, "not a save file"),
+            SaveError::Corrupt => write!(f, "save file failed checksum"),
+            SaveError::UnsupportedVersion(v) =>
+                write!(f, "save version {} is newer than this game supports", v),
+            SaveError::MissingMigration(v) =>
+                write!(f, "no migration from save version {}", v),
+            SaveError::Migration(v, ref e) =>
+                write!(f, "migrating save from version {} failed: {}", v, e),
+            SaveError::Serialization(ref e) => write!(f, "save data error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Serialization(e.to_string())
+    }
+}
+
+// Everything stored in one save, keyed by section name
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct SaveData {
+    sections: BTreeMap<String, Value>,
+}
+
+impl SaveData {
+    pub fn new() -> Self {
+        SaveData { sections: BTreeMap::new() }
+    }
+
+    pub fn put<T: Serialize>(&mut self, name: &str, value: &T) -> Result<(), SaveError> {
+        let value = serde_json::to_value(value)?;
+        self.sections.insert(name.to_owned(), value);
+        Ok(())
+    }
+
+    // None if the section isn't in the save
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, SaveError> {
+        match self.sections.get(name) {
+            Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sections.contains_key(name)
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.sections.remove(name);
+    }
+
+    // Raw access, mostly for migrations
+    pub fn sections_mut(&mut self) -> &mut BTreeMap<String, Value> {
+        &mut self.sections
+    }
+}
+
+// Implemented by anything that stores state in a save
+pub trait Saveable {
+    fn save(&self, data: &mut SaveData) -> Result<(), SaveError>;
+    fn load(&mut self, data: &SaveData) -> Result<(), SaveError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SlotInfo {
+    pub slot: u32,
+    pub version: u32,
+    pub modified: Option<SystemTime>,
+    pub size_bytes: u64,
+}
+
+// Upgrades the JSON of a SaveData from one version to the next
+pub type Migration = Box<dyn Fn(Value) -> Result<Value, String>>;
+
+// Reads and writes numbered save slots in a directory
+pub struct SaveManager {
+    dir: PathBuf,
+    version: u32,
+    // Keyed by the version they upgrade from
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl SaveManager {
+    // version is the format version this build writes
+    pub fn new<P: AsRef<Path>>(dir: P, version: u32) -> Self {
+        SaveManager {
+            dir: dir.as_ref().to_path_buf(),
+            version,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    // Registers an upgrade from from_version to from_version + 1.
+    // Old saves are run through each migration in turn until current.
+    pub fn add_migration<F>(&mut self, from_version: u32, migration: F)
+        where F: Fn(Value) -> Result<Value, String> + 'static {
+        self.migrations.insert(from_version, Box::new(migration));
+    }
+
+    pub fn slot_path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("slot_{}.{}", slot, EXTENSION))
+    }
+
+    pub fn exists(&self, slot: u32) -> bool {
+        self.slot_path(slot).is_file()
+    }
+
+    // Writes to a temporary file first and renames it over the old save,
+    // so a crash mid-write never leaves a half written slot
+    pub fn save(&self, slot: u32, data: &SaveData) -> Result<(), SaveError> {
+        let bytes = encode(self.version, &serde_json::to_vec(data)?);
+        write_atomic(&self.slot_path(slot), &bytes)?;
+        Ok(())
+    }
+
+    pub fn load(&self, slot: u32) -> Result<SaveData, SaveError> {
+        let path = self.slot_path(slot);
+        let mut bytes = Vec::new();
+        match fs::File::open(&path) {
+            Ok(mut file) => { file.read_to_end(&mut bytes)?; }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(SaveError::NotFound(slot));
+            }
+            Err(e) => { return Err(SaveError::Io(e)); }
+        }
+
+        let (version, payload) = decode(&bytes)?;
+        if version > self.version {
+            return Err(SaveError::UnsupportedVersion(version));
+        }
+
+        let mut value: Value = serde_json::from_slice(payload)?;
+        for from in version..self.version {
+            let migration = match self.migrations.get(&from) {
+                Some(m) => m,
+                None => { return Err(SaveError::MissingMigration(from)); }
+            };
+            value = migration(value).map_err(|e| SaveError::Migration(from, e))?;
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn delete(&self, slot: u32) -> Result<(), SaveError> {
+        match fs::remove_file(self.slot_path(slot)) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Err(SaveError::NotFound(slot)),
+            Err(e) => Err(SaveError::Io(e)),
+        }
+    }
+
+    // Lists saved slots in order, skipping files that aren't valid saves
+    pub fn slots(&self) -> Result<Vec<SlotInfo>, SaveError> {
+        let mut result = Vec::new();
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(e) => e,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => { return Ok(result); }
+            Err(e) => { return Err(SaveError::Io(e)); }
+        };
+        for entry in entries {
+            let entry = entry?;
+            let slot = match parse_slot(&entry.path()) {
+                Some(s) => s,
+                None => continue,
+            };
+            let mut header = [0u8; HEADER_LEN];
+            let read = fs::File::open(entry.path())
+                .and_then(|mut f| f.read_exact(&mut header));
+            if read.is_err() || &header[0..8] != MAGIC {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            result.push(SlotInfo {
+                slot,
+                version: read_u32(&header[8..12]),
+                modified: metadata.modified().ok(),
+                size_bytes: metadata.len(),
+            });
+        }
+        result.sort_by_key(|info| info.slot);
+        Ok(result)
+    }
+}
+
+fn parse_slot(path: &Path) -> Option<u32> {
+    if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    if !stem.starts_with("slot_") {
+        return None;
+    }
+    stem["slot_".len()..].parse().ok()
+}
+
+fn encode(version: u32, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&u32_bytes(version));
+    bytes.extend_from_slice(&u64_bytes(checksum(payload)));
+    bytes.extend_from_slice(&u64_bytes(payload.len() as u64));
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+// Returns the version and payload after validating the header and checksum
+fn decode(bytes: &[u8]) -> Result<(u32, &[u8]), SaveError> {
+    if bytes.len() < HEADER_LEN || &bytes[0..8] != MAGIC {
+        return Err(SaveError::BadHeader);
+    }
+    let version = read_u32(&bytes[8..12]);
+    let expected = read_u64(&bytes[12..20]);
+    let length = read_u64(&bytes[20..28]) as usize;
+    if bytes.len() - HEADER_LEN != length {
+        return Err(SaveError::Corrupt);
+    }
+    let payload = &bytes[HEADER_LEN..];
+    if checksum(payload) != expected {
+        return Err(SaveError::Corrupt);
+    }
+    Ok((version, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    // Empty directory of its own for each test, removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("ras_save_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn data(score: u32) -> SaveData {
+        let mut data = SaveData::new();
+        data.put("score", &score).unwrap();
+        data
+    }
+
+    #[test]
+    fn header_layout() {
+        let bytes = encode(3, b"{}");
+        assert_eq!(&bytes[0..8], MAGIC);
+        assert_eq!(read_u32(&bytes[8..12]), 3);
+        assert_eq!(read_u64(&bytes[12..20]), checksum(b"{}"));
+        assert_eq!(read_u64(&bytes[20..28]), 2);
+        assert_eq!(&bytes[HEADER_LEN..], b"{}");
+        assert_eq!(decode(&bytes).unwrap(), (3, &b"{}"[..]));
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new("round_trip");
+        let saves = SaveManager::new(&dir.0, 1);
+        assert!(!saves.exists(2));
+        saves.save(2, &data(42)).unwrap();
+        saves.save(0, &data(7)).unwrap();
+        // Overwriting replaces the old save
+        saves.save(2, &data(43)).unwrap();
+
+        let loaded = saves.load(2).unwrap();
+        assert_eq!(loaded.get::<u32>("score").unwrap(), Some(43));
+        assert_eq!(loaded.get::<u32>("missing").unwrap(), None);
+
+        let slots: Vec<u32> = saves.slots().unwrap().iter().map(|s| s.slot).collect();
+        assert_eq!(slots, vec![0, 2]);
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 2);
+
+        saves.delete(2).unwrap();
+        match saves.load(2) {
+            Err(SaveError::NotFound(2)) => {}
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn corrupted_checksum() {
+        let dir = TempDir::new("corrupted");
+        let saves = SaveManager::new(&dir.0, 1);
+        saves.save(0, &data(42)).unwrap();
+
+        let path = saves.slot_path(0);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 2;
+        bytes[last] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+        match saves.load(0) {
+            Err(SaveError::Corrupt) => {}
+            other => panic!("expected Corrupt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_file() {
+        let dir = TempDir::new("truncated");
+        let saves = SaveManager::new(&dir.0, 1);
+        saves.save(0, &data(42)).unwrap();
+        let path = saves.slot_path(0);
+        let bytes = fs::read(&path).unwrap();
+
+        // Cut in the payload, the length no longer matches
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        match saves.load(0) {
+            Err(SaveError::Corrupt) => {}
+            other => panic!("expected Corrupt, got {:?}", other),
+        }
+
+        // Cut in the header
+        fs::write(&path, &bytes[..HEADER_LEN - 1]).unwrap();
+        match saves.load(0) {
+            Err(SaveError::BadHeader) => {}
+            other => panic!("expected BadHeader, got {:?}", other),
+        }
+        // And skipped when listing slots
+        assert!(saves.slots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn migration_chain() {
+        let dir = TempDir::new("migrations");
+        SaveManager::new(&dir.0, 1).save(0, &data(10)).unwrap();
+
+        let mut saves = SaveManager::new(&dir.0, 3);
+        match saves.load(0) {
+            Err(SaveError::MissingMigration(1)) => {}
+            other => panic!("expected MissingMigration, got {:?}", other),
+        }
+
+        // 1 -> 2 doubles the score, 2 -> 3 renames it
+        saves.add_migration(1, |mut value| {
+            let score = value["sections"]["score"].as_u64().ok_or("no score")?;
+            value["sections"]["score"] = Value::from(score * 2);
+            Ok(value)
+        });
+        saves.add_migration(2, |mut value| {
+            let score = value["sections"]["score"].take();
+            value["sections"]["points"] = score;
+            Ok(value)
+        });
+        let loaded = saves.load(0).unwrap();
+        assert_eq!(loaded.get::<u32>("points").unwrap(), Some(20));
+
+        // Newer saves can't be read by older builds
+        saves.save(1, &data(5)).unwrap();
+        match SaveManager::new(&dir.0, 2).load(1) {
+            Err(SaveError::UnsupportedVersion(3)) => {}
+            other => panic!("expected UnsupportedVersion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_migration() {
+        let dir = TempDir::new("failed_migration");
+        SaveManager::new(&dir.0, 1).save(0, &data(10)).unwrap();
+        let mut saves = SaveManager::new(&dir.0, 2);
+        saves.add_migration(1, |_| Err(String::from("nope")));
+        match saves.load(0) {
+            Err(SaveError::Migration(1, ref e)) if e == "nope" => {}
+            other => panic!("expected Migration, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::f32::consts::PI;
+
+// Standard easing curves, see https://easings.net for what they look like.
+// Each maps t in 0.0..1.0 to a progress value, which starts at 0.0 and ends
+// at 1.0 but may overshoot in between (Back, Elastic).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    Linear,
+    QuadIn, QuadOut, QuadInOut,
+    CubicIn, CubicOut, CubicInOut,
+    QuartIn, QuartOut, QuartInOut,
+    QuintIn, QuintOut, QuintInOut,
+    SineIn, SineOut, SineInOut,
+    ExpoIn, ExpoOut, ExpoInOut,
+    CircIn, CircOut, CircInOut,
+    BackIn, BackOut, BackInOut,
+    ElasticIn, ElasticOut, ElasticInOut,
+    BounceIn, BounceOut, BounceInOut,
+}
+
+// Overshoot used by the Back curves
+const BACK_C1: f32 = 1.70158;
+const BACK_C2: f32 = BACK_C1 * 1.525;
+const BACK_C3: f32 = BACK_C1 + 1.0;
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Ease::Linear => t,
+
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Ease::QuadInOut => in_out(t, |t| t * t),
+
+            Ease::CubicIn => t.powi(3),
+            Ease::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Ease::CubicInOut => in_out(t, |t| t.powi(3)),
+
+            Ease::QuartIn => t.powi(4),
+            Ease::QuartOut => 1.0 - (1.0 - t).powi(4),
+            Ease::QuartInOut => in_out(t, |t| t.powi(4)),
+
+            Ease::QuintIn => t.powi(5),
+            Ease::QuintOut => 1.0 - (1.0 - t).powi(5),
+            Ease::QuintInOut => in_out(t, |t| t.powi(5)),
+
+            Ease::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Ease::SineOut => (t * PI / 2.0).sin(),
+            Ease::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+
+            Ease::ExpoIn => expo_in(t),
+            Ease::ExpoOut => 1.0 - expo_in(1.0 - t),
+            Ease::ExpoInOut => in_out(t, expo_in),
+
+            Ease::CircIn => circ_in(t),
+            Ease::CircOut => 1.0 - circ_in(1.0 - t),
+            Ease::CircInOut => in_out(t, circ_in),
+
+            Ease::BackIn => BACK_C3 * t.powi(3) - BACK_C1 * t * t,
+            Ease::BackOut => 1.0 + BACK_C3 * (t - 1.0).powi(3) + BACK_C1 * (t - 1.0).powi(2),
+            Ease::BackInOut => in_out(t, |t| (BACK_C2 + 1.0) * t.powi(3) - BACK_C2 * t * t),
+
+            Ease::ElasticIn => elastic_in(t),
+            Ease::ElasticOut => 1.0 - elastic_in(1.0 - t),
+            Ease::ElasticInOut => in_out(t, elastic_in),
+
+            Ease::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Ease::BounceOut => bounce_out(t),
+            Ease::BounceInOut => in_out(t, |t| 1.0 - bounce_out(1.0 - t)),
+        }
+    }
+}
+
+// Builds an in-out curve from an in curve: first half eases in,
+// second half is the mirrored ease out
+fn in_out<F: Fn(f32) -> f32>(t: f32, ease_in: F) -> f32 {
+    if t < 0.5 {
+        ease_in(t * 2.0) / 2.0
+    } else {
+        1.0 - ease_in((1.0 - t) * 2.0) / 2.0
+    }
+}
+
+fn expo_in(t: f32) -> f32 {
+    if t == 0.0 { 0.0 } else { 2.0f32.powf(10.0 * t - 10.0) }
+}
+
+fn circ_in(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).sqrt()
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t == 0.0 || t == 1.0 {
+        return t;
+    }
+    let c4 = (2.0 * PI) / 3.0;
+    -(2.0f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * c4).sin()
+}
+
+fn bounce_out(t: f32) -> f32 {
+    let n1 = 7.5625;
+    let d1 = 2.75;
+    if t < 1.0 / d1 {
+        n1 * t * t
+    } else if t < 2.0 / d1 {
+        let t = t - 1.5 / d1;
+        n1 * t * t + 0.75
+    } else if t < 2.5 / d1 {
+        let t = t - 2.25 / d1;
+        n1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d1;
+        n1 * t * t + 0.984375
+    }
+}
@@ -0,0 +1,609 @@
+// Tweens animate a value from one point to another over time.
+// A tween writes into a shared Tweened<T> handle, which the owner of the
+// value reads each frame, e.g. a sprite position or a menu's color.
+#![allow(dead_code)]
+
+extern crate nalgebra as na;
+
+pub mod easing;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use self::na::{Vector2, Vector3, Vector4};
+use events::{Event, EventType, EventPool};
+use graphics::Color;
+pub use tween::easing::Ease;
+
+// Values that can be interpolated by a tween
+pub trait Tweenable: Copy {
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(&self, other: &f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector2<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector3<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector4<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Color {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Color::lerp(self, other, t)
+    }
+}
+
+// Shared handle to a value driven by a tween
+#[derive(Clone)]
+pub struct Tweened<T: Copy>(Rc<Cell<T>>);
+
+impl<T: Copy> Tweened<T> {
+    pub fn new(value: T) -> Self {
+        Tweened(Rc::new(Cell::new(value)))
+    }
+
+    pub fn get(&self) -> T {
+        self.0.get()
+    }
+
+    pub fn set(&self, value: T) {
+        self.0.set(value);
+    }
+}
+
+// How many times an animation plays
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    Once,
+    // Total number of plays, Times(1) is the same as Once
+    Times(u32),
+    Forever,
+}
+
+impl Repeat {
+    // Whether the play with the given zero-based index should happen
+    fn allows(self, play: u32) -> bool {
+        match self {
+            Repeat::Once => play == 0,
+            Repeat::Times(n) => play < n,
+            Repeat::Forever => true,
+        }
+    }
+}
+
+// Anything that can be driven by the tween system and grouped
+pub trait Animation {
+    // Advances by delta_ms and returns the time left over once the
+    // animation finishes, or 0 while it's still running
+    fn advance(&mut self, delta_ms: u64) -> u64;
+    fn is_finished(&self) -> bool;
+    // Restarts from the beginning
+    fn reset(&mut self);
+}
+
+pub struct Tween<T: Tweenable> {
+    target: Tweened<T>,
+    from: T,
+    to: T,
+    duration_ms: u64,
+    delay_ms: u64,
+    ease: Ease,
+    repeat: Repeat,
+    // Plays every other repeat backwards
+    yoyo: bool,
+    // Take from as the target's value when the tween starts playing
+    from_current: bool,
+    started: bool,
+    elapsed_ms: u64,
+    delay_left_ms: u64,
+    play: u32,
+    finished: bool,
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(target: &Tweened<T>, from: T, to: T, duration_ms: u64) -> Self {
+        Tween {
+            target: target.clone(),
+            from,
+            to,
+            duration_ms,
+            delay_ms: 0,
+            ease: Ease::Linear,
+            repeat: Repeat::Once,
+            yoyo: false,
+            from_current: false,
+            started: false,
+            elapsed_ms: 0,
+            delay_left_ms: 0,
+            play: 0,
+            finished: false,
+        }
+    }
+
+    // Tweens from whatever value the target has when the tween starts,
+    // so it can follow other tweens of the same target in a Sequence
+    pub fn to(target: &Tweened<T>, to: T, duration_ms: u64) -> Self {
+        let mut tween = Tween::new(target, target.get(), to, duration_ms);
+        tween.from_current = true;
+        tween
+    }
+
+    pub fn with_ease(mut self, ease: Ease) -> Self {
+        self.ease = ease;
+        self
+    }
+
+    // Waits before the first play only
+    pub fn with_delay(mut self, delay_ms: u64) -> Self {
+        self.delay_ms = delay_ms;
+        self.delay_left_ms = delay_ms;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_yoyo(mut self, yoyo: bool) -> Self {
+        self.yoyo = yoyo;
+        self
+    }
+
+    // Progress through the current play, 0.0 to 1.0, before easing
+    pub fn progress(&self) -> f32 {
+        if self.duration_ms == 0 {
+            return 1.0;
+        }
+        self.elapsed_ms as f32 / self.duration_ms as f32
+    }
+
+    fn write_value(&self) {
+        let mut t = self.progress();
+        if self.yoyo && self.play % 2 == 1 {
+            t = 1.0 - t;
+        }
+        self.target.set(self.from.lerp(&self.to, self.ease.apply(t)));
+    }
+}
+
+impl<T: Tweenable> Animation for Tween<T> {
+    fn advance(&mut self, delta_ms: u64) -> u64 {
+        let mut remaining = delta_ms;
+        if self.finished {
+            return remaining;
+        }
+
+        let delay = self.delay_left_ms.min(remaining);
+        self.delay_left_ms -= delay;
+        remaining -= delay;
+        if self.delay_left_ms > 0 {
+            return 0;
+        }
+
+        if !self.started {
+            self.started = true;
+            if self.from_current {
+                self.from = self.target.get();
+            }
+        }
+
+        loop {
+            let step = (self.duration_ms - self.elapsed_ms).min(remaining);
+            self.elapsed_ms += step;
+            remaining -= step;
+            if self.elapsed_ms < self.duration_ms {
+                break;
+            }
+            // Zero length tweens would otherwise repeat forever
+            if self.duration_ms == 0 || !self.repeat.allows(self.play + 1) {
+                self.finished = true;
+                break;
+            }
+            self.play += 1;
+            self.elapsed_ms = 0;
+        }
+
+        self.write_value();
+        if self.finished { remaining } else { 0 }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn reset(&mut self) {
+        self.elapsed_ms = 0;
+        self.delay_left_ms = self.delay_ms;
+        self.play = 0;
+        self.finished = false;
+        self.started = false;
+        if !self.from_current {
+            self.write_value();
+        }
+    }
+}
+
+// Plays its animations one after another
+pub struct Sequence {
+    animations: Vec<Box<dyn Animation>>,
+    current: usize,
+    repeat: Repeat,
+    play: u32,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Sequence {
+            animations: Vec::new(),
+            current: 0,
+            repeat: Repeat::Once,
+            play: 0,
+        }
+    }
+
+    pub fn then<A: Animation + 'static>(mut self, animation: A) -> Self {
+        self.animations.push(Box::new(animation));
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence::new()
+    }
+}
+
+impl Animation for Sequence {
+    fn advance(&mut self, delta_ms: u64) -> u64 {
+        let mut remaining = delta_ms;
+        loop {
+            let before = remaining;
+            let from_start = self.current == 0;
+            while self.current < self.animations.len() {
+                remaining = self.animations[self.current].advance(remaining);
+                if !self.animations[self.current].is_finished() {
+                    return 0;
+                }
+                self.current += 1;
+            }
+            if !self.repeat.allows(self.play + 1) {
+                return remaining;
+            }
+            // A whole play that took no time would loop forever
+            if from_start && remaining > 0 && remaining == before {
+                return remaining;
+            }
+            self.play += 1;
+            self.restart();
+            if remaining == 0 {
+                return 0;
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.current >= self.animations.len()
+    }
+
+    fn reset(&mut self) {
+        self.play = 0;
+        self.restart();
+    }
+}
+
+impl Sequence {
+    fn restart(&mut self) {
+        self.current = 0;
+        // Reset in reverse so the first animation's start value wins
+        // when several animations share a target
+        for animation in self.animations.iter_mut().rev() {
+            animation.reset();
+        }
+    }
+}
+
+// Plays its animations at the same time, finishing when all have finished
+pub struct Parallel {
+    animations: Vec<Box<dyn Animation>>,
+    repeat: Repeat,
+    play: u32,
+}
+
+impl Parallel {
+    pub fn new() -> Self {
+        Parallel {
+            animations: Vec::new(),
+            repeat: Repeat::Once,
+            play: 0,
+        }
+    }
+
+    pub fn with<A: Animation + 'static>(mut self, animation: A) -> Self {
+        self.animations.push(Box::new(animation));
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+}
+
+impl Default for Parallel {
+    fn default() -> Self {
+        Parallel::new()
+    }
+}
+
+impl Animation for Parallel {
+    fn advance(&mut self, delta_ms: u64) -> u64 {
+        let mut remaining = delta_ms;
+        loop {
+            // Time left over after the longest running animation finished
+            let mut left_over = remaining;
+            for animation in self.animations.iter_mut() {
+                left_over = left_over.min(animation.advance(remaining));
+            }
+            if !self.is_finished() {
+                return 0;
+            }
+            if !self.repeat.allows(self.play + 1) {
+                return left_over;
+            }
+            // A whole play that took no time would loop forever
+            if left_over > 0 && left_over == remaining {
+                return left_over;
+            }
+            self.play += 1;
+            for animation in self.animations.iter_mut() {
+                animation.reset();
+            }
+            remaining = left_over;
+            if remaining == 0 {
+                return 0;
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.animations.iter().all(|a| a.is_finished())
+    }
+
+    fn reset(&mut self) {
+        self.play = 0;
+        for animation in self.animations.iter_mut() {
+            animation.reset();
+        }
+    }
+}
+
+struct RunningAnimation {
+    id: u32,
+    animation: Box<dyn Animation>,
+    paused: bool,
+}
+
+// Owns running animations and advances them each frame. When an animation
+// finishes an OnTweenComplete event is signaled with its id and it's removed.
+pub struct Tweens {
+    running: Vec<RunningAnimation>,
+    next_id: u32,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Tweens { running: Vec::new(), next_id: 1 }
+    }
+
+    // Starts an animation and returns its id
+    pub fn start<A: Animation + 'static>(&mut self, animation: A) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running.push(RunningAnimation {
+            id,
+            animation: Box::new(animation),
+            paused: false,
+        });
+        id
+    }
+
+    // Stops an animation without signaling completion,
+    // its target keeps its current value
+    pub fn cancel(&mut self, id: u32) {
+        self.running.retain(|r| r.id != id);
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.running.clear();
+    }
+
+    pub fn set_paused(&mut self, id: u32, paused: bool) {
+        for running in self.running.iter_mut().filter(|r| r.id == id) {
+            running.paused = paused;
+        }
+    }
+
+    pub fn is_running(&self, id: u32) -> bool {
+        self.running.iter().any(|r| r.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    pub fn update(&mut self, delta_time_ms: u64, event_pool: &mut EventPool) {
+        for running in self.running.iter_mut().filter(|r| !r.paused) {
+            running.animation.advance(delta_time_ms);
+            if running.animation.is_finished() {
+                event_pool.signal_event(
+                    EventType::OnTweenComplete,
+                    Event::OnTweenComplete { trigger_id: running.id });
+            }
+        }
+        self.running.retain(|r| !r.animation.is_finished());
+    }
+}
+
+impl Default for Tweens {
+    fn default() -> Self {
+        Tweens::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_value(target: &Tweened<f32>, expected: f32) {
+        assert!((target.get() - expected).abs() < 1e-3, "{} != {}", target.get(), expected);
+    }
+
+    #[test]
+    fn tweens_colors() {
+        let target = Tweened::new(Color::BLACK);
+        let mut tween = Tween::new(&target, Color::BLACK, Color::new(1.0, 0.5, 0.0, 0.0), 100);
+        tween.advance(50);
+        assert_eq!(target.get(), Color::new(0.5, 0.25, 0.0, 0.5));
+        tween.advance(50);
+        assert_eq!(target.get(), Color::new(1.0, 0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn repeat_plays_back_to_back() {
+        let target = Tweened::new(0.0);
+        let mut tween = Tween::new(&target, 0.0, 100.0, 100).with_repeat(Repeat::Times(3));
+        assert_eq!(tween.advance(50), 0);
+        assert_value(&target, 50.0);
+        // Time past the end of a play carries into the next one
+        assert_eq!(tween.advance(100), 0);
+        assert_value(&target, 50.0);
+        assert_eq!(tween.advance(100), 0);
+        assert!(!tween.is_finished());
+        // 20ms past the end of the third play is handed back
+        assert_eq!(tween.advance(70), 20);
+        assert!(tween.is_finished());
+        assert_value(&target, 100.0);
+    }
+
+    #[test]
+    fn yoyo_plays_every_other_repeat_backwards() {
+        let target = Tweened::new(0.0);
+        let mut tween = Tween::new(&target, 0.0, 100.0, 100)
+            .with_repeat(Repeat::Times(2))
+            .with_yoyo(true);
+        tween.advance(75);
+        assert_value(&target, 75.0);
+        tween.advance(50);
+        assert_value(&target, 75.0);
+        tween.advance(50);
+        assert_value(&target, 25.0);
+        assert_eq!(tween.advance(50), 25);
+        assert!(tween.is_finished());
+        assert_value(&target, 0.0);
+    }
+
+    #[test]
+    fn delay_only_before_first_play() {
+        let target = Tweened::new(-1.0);
+        let mut tween = Tween::new(&target, 0.0, 100.0, 100)
+            .with_delay(50)
+            .with_repeat(Repeat::Forever);
+        tween.advance(30);
+        assert_value(&target, -1.0);
+        tween.advance(20);
+        assert_value(&target, 0.0);
+        tween.advance(150);
+        assert_value(&target, 50.0);
+        // Many plays in one long frame
+        tween.advance(1020);
+        assert_value(&target, 70.0);
+        assert!(!tween.is_finished());
+
+        tween.reset();
+        assert_value(&target, 0.0);
+        tween.advance(60);
+        assert_value(&target, 10.0);
+    }
+
+    #[test]
+    fn zero_length_tween_finishes() {
+        let target = Tweened::new(0.0);
+        let mut tween = Tween::new(&target, 0.0, 1.0, 0).with_repeat(Repeat::Forever);
+        assert_eq!(tween.advance(10), 10);
+        assert!(tween.is_finished());
+        assert_value(&target, 1.0);
+    }
+
+    #[test]
+    fn sequence_repeats_from_the_first_value() {
+        let target = Tweened::new(0.0);
+        let mut sequence = Sequence::new()
+            .then(Tween::new(&target, 0.0, 10.0, 50))
+            .then(Tween::to(&target, 0.0, 50))
+            .with_repeat(Repeat::Times(2));
+        sequence.advance(75);
+        assert_value(&target, 5.0);
+        sequence.advance(50);
+        assert_value(&target, 5.0);
+        assert!(!sequence.is_finished());
+        assert_eq!(sequence.advance(100), 25);
+        assert!(sequence.is_finished());
+        assert_value(&target, 0.0);
+    }
+
+    #[test]
+    fn parallel_waits_for_the_longest() {
+        let a = Tweened::new(0.0);
+        let b = Tweened::new(0.0);
+        let mut parallel = Parallel::new()
+            .with(Tween::new(&a, 0.0, 10.0, 50))
+            .with(Tween::new(&b, 0.0, 10.0, 100))
+            .with_repeat(Repeat::Times(2));
+        parallel.advance(120);
+        assert_value(&a, 4.0);
+        assert_value(&b, 2.0);
+        assert_eq!(parallel.advance(100), 20);
+        assert!(parallel.is_finished());
+    }
+
+    #[test]
+    fn finished_animations_signal_and_leave() {
+        let mut pool = EventPool::new();
+        let mut tweens = Tweens::new();
+        let target = Tweened::new(0.0);
+        let id = tweens.start(Tween::new(&target, 0.0, 1.0, 100).with_repeat(Repeat::Times(2)));
+        tweens.update(150, &mut pool);
+        assert!(tweens.is_running(id));
+        assert!(pool.poll_events(EventType::OnTweenComplete, id, true).is_none());
+
+        tweens.update(50, &mut pool);
+        assert!(tweens.is_empty());
+        assert!(pool.poll_events(EventType::OnTweenComplete, id, true).is_some());
+    }
+}
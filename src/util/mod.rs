@@ -0,0 +1,89 @@
+// Small helpers shared by the file formats (save slots, the program binary
+// cache): little endian integers, FNV-1a and atomic file writes.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+// 64-bit FNV-1a, catches truncation and bit rot, not tampering
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub fn u32_bytes(v: u32) -> [u8; 4] {
+    v.to_le_bytes()
+}
+
+pub fn u64_bytes(v: u64) -> [u8; 8] {
+    v.to_le_bytes()
+}
+
+// bytes must be at least 4 long, anything after is ignored
+pub fn read_u32(bytes: &[u8]) -> u32 {
+    (0..4).fold(0, |acc, i| acc | (bytes[i] as u32) << (i * 8))
+}
+
+// bytes must be at least 8 long, anything after is ignored
+pub fn read_u64(bytes: &[u8]) -> u64 {
+    (0..8).fold(0, |acc, i| acc | (bytes[i] as u64) << (i * 8))
+}
+
+// Writes to <path>.tmp first and renames it over path, so a crash mid-write
+// leaves either the old file or the new one, never half of one. Creates the
+// parent directory if needed.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    // Replaces an existing file on every platform, MoveFileExW with
+    // MOVEFILE_REPLACE_EXISTING on Windows
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn integers_round_trip() {
+        assert_eq!(u32_bytes(0x01020304), [4, 3, 2, 1]);
+        assert_eq!(read_u32(&u32_bytes(0xdeadbeef)), 0xdeadbeef);
+        assert_eq!(read_u64(&u64_bytes(0x0102030405060708)), 0x0102030405060708);
+        // Extra bytes after are ignored
+        assert_eq!(read_u32(&[1, 0, 0, 0, 9]), 1);
+    }
+
+    #[test]
+    fn fnv1a_reference_values() {
+        assert_eq!(checksum(b""), 0xcbf29ce484222325);
+        assert_eq!(checksum(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(checksum(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn write_atomic_replaces() {
+        let dir = env::temp_dir().join(format!("ras_util_{}", process::id()));
+        let path = dir.join("nested").join("file.bin");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}